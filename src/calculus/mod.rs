@@ -0,0 +1,291 @@
+pub mod calculus {
+    use crate::functions::functions::ParameterType;
+    use crate::grammar::grammar::{reevaluate, BoundOperator, Environment, EvalError, Expr};
+    use crate::symbolic::symbolic::{substitute, variable_name};
+    use crate::value::value::Value;
+
+    /// The most terms of a sum or a product, as with the longest range.
+    const MAX_TERMS: usize = 1_000_000;
+    /// The most pieces an integral is split into before settling for the error it has reached.
+    const MAX_INTERVALS: usize = 2_000;
+    /// The relative error an integral is refined to, or the absolute error when it is close to zero.
+    const TOLERANCE: f64 = 1e-10;
+
+    /// The nodes of the 15 point Gauss-Kronrod rule on `[-1, 1]`, from the outside in, along with their
+    /// weights. Every other node is also a node of the 7 point Gauss rule, with the weights in `GAUSS_WEIGHTS`.
+    const KRONROD_NODES: [f64; 8] = [
+        0.991_455_371_120_812_6,
+        0.949_107_912_342_758_5,
+        0.864_864_423_359_769_1,
+        0.741_531_185_599_394_4,
+        0.586_087_235_467_691_1,
+        0.405_845_151_377_397_2,
+        0.207_784_955_007_898_5,
+        0.0,
+    ];
+    const KRONROD_WEIGHTS: [f64; 8] = [
+        0.022_935_322_010_529_22,
+        0.063_092_092_629_978_55,
+        0.104_790_010_322_250_18,
+        0.140_653_259_715_525_92,
+        0.169_004_726_639_267_9,
+        0.190_350_578_064_785_4,
+        0.204_432_940_075_298_9,
+        0.209_482_141_084_727_83,
+    ];
+    const GAUSS_WEIGHTS: [f64; 4] = [0.129_484_966_168_869_7, 0.279_705_391_489_276_7, 0.381_830_050_505_118_9, 0.417_959_183_673_469_4];
+
+    /// The body of the operation, as a function of its variable.
+    struct Body {
+        expr: Expr,
+        operator: BoundOperator,
+        name: String,
+        /// The session's variables, with the bound variable set to wherever the body was last evaluated.
+        environment: Environment,
+    }
+
+    impl Body {
+        fn evaluate(&mut self, at: Value) -> Result<Expr, EvalError> {
+            self.environment.variables.insert(self.name.clone(), Expr::literal(at));
+            reevaluate(&self.expr, &self.environment)
+        }
+
+        fn at(&mut self, at: Value) -> Result<f64, EvalError> {
+            match self.evaluate(at)?.value() {
+                value @ (Value::Integer(_) | Value::Word(_) | Value::Float(_) | Value::Bool(_)) => Ok(value.as_f64()),
+                value => Err(EvalError::NotANumber {
+                    operator: self.operator.name().to_string(),
+                    value,
+                }),
+            }
+        }
+    }
+
+    /// An estimate of the integral over part of the range, and of its error.
+    struct Interval {
+        low: f64,
+        high: f64,
+        integral: f64,
+        error: f64,
+    }
+
+    /// Integrates over the interval with the Kronrod rule, and estimates the error from the difference
+    /// with the Gauss rule, which uses every other node. Each evaluation of the body is added to `evaluations`.
+    fn kronrod(body: &mut Body, low: f64, high: f64, evaluations: &mut usize) -> Result<Interval, EvalError> {
+        let (center, half) = ((low + high) / 2.0, (high - low) / 2.0);
+        let (mut kronrod, mut gauss) = (0.0, 0.0);
+        for (index, (node, weight)) in KRONROD_NODES.iter().zip(KRONROD_WEIGHTS).enumerate() {
+            let values = if *node == 0.0 {
+                *evaluations += 1;
+                body.at(Value::Float(center))?
+            } else {
+                *evaluations += 2;
+                body.at(Value::Float(center - half * node))? + body.at(Value::Float(center + half * node))?
+            };
+            kronrod += weight * values;
+            if index % 2 == 1 {
+                gauss += GAUSS_WEIGHTS[index / 2] * values;
+            }
+        }
+        Ok(Interval {
+            low,
+            high,
+            integral: kronrod * half,
+            error: ((kronrod - gauss) * half).abs(),
+        })
+    }
+
+    /// Integrates from `low` to `high` by repeatedly halving the piece with the largest error, returning
+    /// the integral, its estimated error and the number of times the body was evaluated.
+    fn integrate(body: &mut Body, low: f64, high: f64) -> Result<(f64, f64, usize), EvalError> {
+        let mut evaluations = 0;
+        let mut intervals = vec![kronrod(body, low, high, &mut evaluations)?];
+        loop {
+            let integral = intervals.iter().map(|interval| interval.integral).sum::<f64>();
+            let error = intervals.iter().map(|interval| interval.error).sum::<f64>();
+            let (worst, _) = intervals
+                .iter()
+                .enumerate()
+                .max_by(|(_, a), (_, b)| a.error.total_cmp(&b.error))
+                .unwrap();
+            let Interval { low, high, .. } = intervals[worst];
+            let center = (low + high) / 2.0;
+            // Near a singularity, the pieces can get as small as floats allow without the error going down.
+            let indivisible = center <= low.min(high) || center >= low.max(high);
+            if error <= (TOLERANCE * integral.abs()).max(TOLERANCE * 1e-2) || !integral.is_finite() || indivisible || intervals.len() >= MAX_INTERVALS {
+                return Ok((integral, error, evaluations));
+            }
+            intervals.swap_remove(worst);
+            intervals.push(kronrod(body, low, center, &mut evaluations)?);
+            intervals.push(kronrod(body, center, high, &mut evaluations)?);
+        }
+    }
+
+    /// The value of a bound, which must be an integer for a sum or a product.
+    fn bound(operator: BoundOperator, position: usize, expr: &Expr) -> Result<f64, EvalError> {
+        let value = expr.value();
+        let expected = match (operator, &value) {
+            (BoundOperator::Integral, Value::Integer(_) | Value::Word(_) | Value::Float(_)) => return Ok(value.as_f64()),
+            (BoundOperator::Integral, _) => ParameterType::Number,
+            (_, Value::Integer(_) | Value::Word(_) | Value::Float(_)) if value.as_integer().is_some() => return Ok(value.as_f64()),
+            _ => ParameterType::Integer,
+        };
+        Err(EvalError::ArgumentType {
+            function: operator.name().to_string(),
+            position,
+            expected,
+            found: value,
+        })
+    }
+
+    /// Evaluates `integrate(body, x, a, b)`, `sum(body, k, a, b)` or `prod(body, k, a, b)`, whose arguments
+    /// have already been evaluated.
+    pub fn eval_bound_operation(operator: BoundOperator, mut arguments: Vec<Expr>, environment: &Environment) -> Result<Expr, EvalError> {
+        if arguments.len() != 4 {
+            return Err(EvalError::WrongArity {
+                function: operator.name().to_string(),
+                expected: "4 arguments".to_string(),
+                found: arguments.len(),
+            });
+        }
+        let upper = arguments.pop().unwrap();
+        let lower = arguments.pop().unwrap();
+        let name = variable_name(operator.name(), &arguments.pop().unwrap())?;
+        let body = arguments.pop().unwrap();
+        bind(operator, body, name, lower, upper, environment)
+    }
+
+    /// Evaluates the operation with bounds that have already been evaluated. The variable is only given a
+    /// value in a copy of the environment, so it doesn't clash with a session variable of the same name.
+    pub fn bind(operator: BoundOperator, body: Expr, name: String, lower: Expr, upper: Expr, environment: &Environment) -> Result<Expr, EvalError> {
+        let body = substitute(&body, &name, &Expr::FreeVariable { name: name.clone() });
+        // With a bound that is itself a formula, such as the `n` in `sum(k, k, 1, n)`, the operation is too.
+        let formula = |bound: &Expr| !(bound.free_variables().is_empty() && bound.unbound_variables().is_empty());
+        if formula(&lower) || formula(&upper) {
+            return Ok(Expr::BoundOperation {
+                operator,
+                body: Box::new(body),
+                variable: name,
+                lower: Box::new(lower),
+                upper: Box::new(upper),
+                at: f64::NAN,
+                error: None,
+                evaluations: 0,
+                value: Value::Float(f64::NAN),
+            });
+        }
+        let (low, high) = (bound(operator, 3, &lower)?, bound(operator, 4, &upper)?);
+        let mut body = Body {
+            expr: body,
+            operator,
+            name: name.clone(),
+            environment: environment.clone(),
+        };
+
+        let (value, error, evaluations, at) = match operator {
+            BoundOperator::Integral => {
+                if !low.is_finite() || !high.is_finite() {
+                    return Err(EvalError::InvalidRange {
+                        reason: format!("can't integrate from {} to {}, the bounds of an integral must be finite", Value::Float(low), Value::Float(high)),
+                    });
+                }
+                let (integral, error, evaluations) = integrate(&mut body, low, high)?;
+                (integral, Some(error), evaluations, Value::Float((low + high) / 2.0))
+            }
+            BoundOperator::Sum | BoundOperator::Product => {
+                let terms = if high < low { 0.0 } else { high - low + 1.0 };
+                if terms > MAX_TERMS as f64 {
+                    return Err(EvalError::InvalidRange {
+                        reason: format!("{}..{} has more than {} terms", lower.value(), upper.value(), MAX_TERMS),
+                    });
+                }
+                // An empty sum is 0 and an empty product is 1, so that splitting the range doesn't change the result.
+                let mut total = if operator == BoundOperator::Sum { 0.0 } else { 1.0 };
+                for term in 0..terms as i128 {
+                    let value = body.at(Value::Integer(low as i128 + term))?;
+                    if operator == BoundOperator::Sum {
+                        total += value;
+                    } else {
+                        total *= value;
+                    }
+                }
+                (total, None, terms as usize, Value::Integer(low as i128))
+            }
+        };
+
+        // The body is shown at the middle of an integral, or at the first term of a sum or a product.
+        Ok(Expr::BoundOperation {
+            operator,
+            body: Box::new(body.evaluate(at.clone())?),
+            variable: name,
+            lower: Box::new(lower),
+            upper: Box::new(upper),
+            at: at.as_f64(),
+            error,
+            evaluations,
+            value: Value::Float(value),
+        })
+    }
+
+    #[cfg(test)]
+    mod tests {
+
+        use super::*;
+        use crate::context::context::{Context, Error};
+
+        fn close(context: &mut Context, input: &str, expected: f64) -> bool {
+            let actual = context.eval(input).unwrap().as_f64();
+            (actual - expected).abs() <= 1e-9 * expected.abs().max(1.0)
+        }
+
+        #[test]
+        fn test_integrate() {
+            let mut context = Context::new();
+            assert!(close(&mut context, "integrate(x^2, x, 0, 3)", 9.0));
+            assert!(close(&mut context, "integrate(sin(x), x, 0, pi)", 2.0));
+            assert!(close(&mut context, "integrate(1/sqrt(x), x, 0, 1)", 2.0));
+            assert!(close(&mut context, "integrate(exp(t), t, 3, -3)", (-3f64).exp() - 3f64.exp()));
+            match context.evaluate("integrate(abs(x - 1), x, 0, 2)").unwrap().expr {
+                Expr::BoundOperation { error, at, evaluations, .. } => {
+                    assert!(error.unwrap() < 1e-9);
+                    assert_eq!(at, 1.0);
+                    // The kink in the middle takes one split, into two pieces the rule is exact on.
+                    assert_eq!(evaluations, 15 * 3);
+                }
+                expr => panic!("expected an integral, found {:?}", expr),
+            }
+            assert!(matches!(context.eval("integrate(x, x, 0, 10^400)"), Err(Error::Eval(EvalError::InvalidRange { .. }))));
+            assert!(matches!(context.eval("integrate(x, 2, 0, 1)"), Err(Error::Eval(EvalError::ExpectedVariable { .. }))));
+        }
+
+        #[test]
+        fn test_sum_and_product() {
+            let mut context = Context::new();
+            assert_eq!(context.eval("sum(k^2, k, 1, 10)").unwrap(), Value::Float(385.0));
+            assert_eq!(context.eval("prod(k, k, 1, 5)").unwrap(), Value::Float(120.0));
+            assert_eq!(context.eval("sum(k, k, 5, 1)").unwrap(), Value::Float(0.0));
+            assert_eq!(context.eval("prod(k, k, 5, 1)").unwrap(), Value::Float(1.0));
+            assert_eq!(context.eval("sum(sum(j, j, 1, k), k, 1, 3)").unwrap(), Value::Float(10.0));
+            // A name second is a range even if the body doesn't use it, whether or not the name has a value.
+            assert_eq!(context.eval("sum(1, k, 1, 5)").unwrap(), Value::Float(5.0));
+            context.eval("b := 2").unwrap();
+            assert_eq!(context.eval("sum(1, b, 3, 4)").unwrap(), Value::Float(2.0));
+            // The statistics function is still there for other arguments.
+            assert_eq!(context.eval("sum(1, 2, 3, 4)").unwrap(), Value::Float(10.0));
+            assert!(matches!(context.eval("sum(k, k, 1, 2.5)"), Err(Error::Eval(EvalError::ArgumentType { position: 4, .. }))));
+            assert!(matches!(context.eval("prod(k, k, 1)"), Err(Error::Eval(EvalError::WrongArity { .. }))));
+        }
+
+        #[test]
+        fn test_scoping() {
+            let mut context = Context::new();
+            context.eval("k := 100").unwrap();
+            assert_eq!(context.eval("sum(k, k, 1, 4)").unwrap(), Value::Float(10.0));
+            assert_eq!(context.eval("k").unwrap(), Value::Integer(100));
+            context.environment_mut().symbolic = true;
+            context.eval("triangle := sum(j, j, 1, n)").unwrap();
+            assert_eq!(context.eval("triangle where n = 4").unwrap(), Value::Float(10.0));
+            assert!(context.eval("triangle").unwrap().as_f64().is_nan());
+        }
+    }
+}
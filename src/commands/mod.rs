@@ -0,0 +1,549 @@
+pub mod commands {
+    use reedline::{Completer, Span, Suggestion};
+
+    use crate::suggest::suggest::did_you_mean;
+
+    /// What a command argument may be, which decides how it is checked, completed and documented.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum ArgumentType {
+        /// One of a fixed set of words.
+        Keyword(&'static [&'static str]),
+        /// A whole number in the given range, or `off` if `off` is set.
+        Number { min: usize, max: usize, off: bool },
+        /// The name of a variable.
+        Variable,
+        /// The name of a command.
+        Command,
+        /// One of the settings of `:set`, followed by a value of that setting's type.
+        Setting,
+        /// The rest of the line, such as an expression.
+        Text,
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    pub struct Parameter {
+        pub name: &'static str,
+        pub kind: ArgumentType,
+        pub optional: bool,
+    }
+
+    #[derive(Debug)]
+    pub struct Command {
+        pub name: &'static str,
+        pub parameters: &'static [Parameter],
+        pub help: &'static str,
+    }
+
+    /// A runtime option that can be changed with `:set`.
+    #[derive(Debug, PartialEq)]
+    pub struct Setting {
+        pub name: &'static str,
+        /// Other names the setting is known by, e.g. `precision` for `digits`.
+        pub aliases: &'static [&'static str],
+        pub value: ArgumentType,
+        pub help: &'static str,
+    }
+
+    /// A checked command argument.
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Argument<'a> {
+        /// A keyword, a variable name or a command name.
+        Word(&'a str),
+        /// A number, or `None` for `off`.
+        Number(Option<usize>),
+        Setting(&'static Setting),
+        Text(String),
+    }
+
+    const fn required(name: &'static str, kind: ArgumentType) -> Parameter {
+        Parameter { name, kind, optional: false }
+    }
+
+    const fn optional(name: &'static str, kind: ArgumentType) -> Parameter {
+        Parameter { name, kind, optional: true }
+    }
+
+    const ON_OFF: ArgumentType = ArgumentType::Keyword(&["on", "off"]);
+    const LAYOUTS: ArgumentType = ArgumentType::Keyword(&["auto", "tree", "top-down", "inline"]);
+    const ANGLES: ArgumentType = ArgumentType::Keyword(&["rad", "deg", "grad"]);
+    const DEPTH: ArgumentType = ArgumentType::Number { min: 1, max: usize::MAX, off: true };
+
+    pub const COMMANDS: &[Command] = &[
+        Command {
+            name: "state",
+            parameters: &[],
+            help: "Show the variables and the built-in constants",
+        },
+        Command {
+            name: "reset",
+            parameters: &[],
+            help: "Delete all variables",
+        },
+        Command {
+            name: "del",
+            parameters: &[required("variable", ArgumentType::Variable)],
+            help: "Delete a variable",
+        },
+        Command {
+            name: "debug",
+            parameters: &[],
+            help: "Show the parsed form of the last expression",
+        },
+        Command {
+            name: "history",
+            parameters: &[],
+            help: "List the results of this session",
+        },
+        Command {
+            name: "word",
+            parameters: &[optional(
+                "size",
+                ArgumentType::Keyword(&["u8", "u16", "u32", "u64", "u128", "i8", "i16", "i32", "i64", "i128", "off"]),
+            )],
+            help: "Show or change the word size of programmer mode",
+        },
+        Command {
+            name: "overflow",
+            parameters: &[required("overflow", ArgumentType::Keyword(&["wrapping", "checked"]))],
+            help: "Choose whether overflow wraps around in programmer mode",
+        },
+        Command {
+            name: "layout",
+            parameters: &[optional("layout", LAYOUTS)],
+            help: "Show or change how expression trees are laid out",
+        },
+        Command {
+            name: "depth",
+            parameters: &[optional("levels", DEPTH)],
+            help: "Show or change how many levels of a tree are shown",
+        },
+        Command {
+            name: "angle",
+            parameters: &[optional("unit", ANGLES)],
+            help: "Show or change the angle mode",
+        },
+        Command {
+            name: "diff",
+            parameters: &[required("expression", ArgumentType::Text)],
+            help: "Differentiate an expression, e.g. `:diff x^2 wrt x` or `:diff x^2 wrt x at 3`",
+        },
+        Command {
+            name: "simplify",
+            parameters: &[required("expression", ArgumentType::Text)],
+            help: "Simplify an expression, such as `2x + 3x` to `5 * x`",
+        },
+        Command {
+            name: "steps",
+            parameters: &[optional("expression", ArgumentType::Text)],
+            help: "Show each rewrite made while simplifying an expression, or the last one",
+        },
+        Command {
+            name: "plot",
+            parameters: &[required("expressions", ArgumentType::Text)],
+            help: "Plot expressions, e.g. `:plot sin(x), cos(x) for x in -pi..pi`, adding `to plot.svg` to write an SVG file",
+        },
+        Command {
+            name: "set",
+            parameters: &[optional("setting", ArgumentType::Setting)],
+            help: "Show the settings, or change one of them",
+        },
+        Command {
+            name: "config",
+            parameters: &[],
+            help: "Show the config file and the settings in effect",
+        },
+        Command {
+            name: "help",
+            parameters: &[optional("command", ArgumentType::Command)],
+            help: "List the commands, or describe one of them",
+        },
+    ];
+
+    pub const SETTINGS: &[Setting] = &[
+        Setting {
+            name: "digits",
+            aliases: &["precision"],
+            value: ArgumentType::Number { min: 1, max: 17, off: true },
+            help: "Show floats with at most this many significant digits",
+        },
+        Setting {
+            name: "decimals",
+            aliases: &[],
+            value: ArgumentType::Number { min: 0, max: 20, off: true },
+            help: "Show floats with exactly this many digits after the decimal point",
+        },
+        Setting {
+            name: "notation",
+            aliases: &[],
+            value: ArgumentType::Keyword(&["auto", "fixed", "scientific", "engineering", "si"]),
+            help: "How to write large and small numbers",
+        },
+        Setting {
+            name: "separators",
+            aliases: &[],
+            value: ON_OFF,
+            help: "Group digits, as in 1,000,000",
+        },
+        Setting {
+            name: "radix",
+            aliases: &[],
+            value: ArgumentType::Keyword(&["bin", "oct", "dec", "hex"]),
+            help: "The base integers are written in",
+        },
+        Setting {
+            name: "angle",
+            aliases: &[],
+            value: ANGLES,
+            help: "The unit of plain numbers passed to sin, cos and tan, and of angles in the results",
+        },
+        Setting {
+            name: "layout",
+            aliases: &[],
+            value: LAYOUTS,
+            help: "How expression trees are laid out",
+        },
+        Setting {
+            name: "depth",
+            aliases: &[],
+            value: DEPTH,
+            help: "Collapse operations more than this many levels deep",
+        },
+        Setting {
+            name: "strict",
+            aliases: &[],
+            value: ON_OFF,
+            help: "Reject implicit multiplication",
+        },
+        Setting {
+            name: "symbolic",
+            aliases: &[],
+            value: ON_OFF,
+            help: "Leave unknown names free, so that formulas such as `f := 2x + 1` can be stored",
+        },
+        Setting {
+            name: "warn-precedence",
+            aliases: &[],
+            value: ON_OFF,
+            help: "Warn when the old precedence rules would give a different result",
+        },
+    ];
+
+    pub fn command(name: &str) -> Option<&'static Command> {
+        COMMANDS.iter().find(|command| command.name == name)
+    }
+
+    pub fn setting(name: &str) -> Option<&'static Setting> {
+        SETTINGS
+            .iter()
+            .find(|setting| setting.name == name || setting.aliases.contains(&name))
+    }
+
+    fn command_names() -> impl Iterator<Item = &'static str> {
+        COMMANDS.iter().map(|command| command.name)
+    }
+
+    fn setting_names() -> impl Iterator<Item = &'static str> {
+        SETTINGS
+            .iter()
+            .flat_map(|setting| std::iter::once(setting.name).chain(setting.aliases.iter().copied()))
+    }
+
+    /// Looks up a command by name, suggesting the closest one if there is no such command.
+    pub fn find_command(name: &str) -> Result<&'static Command, String> {
+        command(name).ok_or_else(|| match did_you_mean(name, command_names()) {
+            Some(suggestion) => format!("Unknown command `:{}`, did you mean `:{}`?", name, suggestion),
+            None => format!(
+                "Unknown command `:{}`, expected one of: {}",
+                name,
+                command_names().collect::<Vec<_>>().join(", ")
+            ),
+        })
+    }
+
+    fn find_setting(name: &str) -> Result<&'static Setting, String> {
+        setting(name).ok_or_else(|| match did_you_mean(name, setting_names()) {
+            Some(suggestion) => format!("Unknown setting `{}`, did you mean `{}`?", name, suggestion),
+            None => format!(
+                "Unknown setting `{}`, expected one of: {}",
+                name,
+                SETTINGS.iter().map(|setting| setting.name).collect::<Vec<_>>().join(", ")
+            ),
+        })
+    }
+
+    /// `a`, `a or b`, `a, b or c`, with each word quoted.
+    fn one_of(words: &[&str]) -> String {
+        let quoted = words.iter().map(|word| format!("`{}`", word)).collect::<Vec<_>>();
+        match quoted.split_last() {
+            Some((last, [])) => last.clone(),
+            Some((last, rest)) => format!("{} or {}", rest.join(", "), last),
+            None => String::new(),
+        }
+    }
+
+    impl ArgumentType {
+        /// What a valid value looks like, for error messages and help.
+        pub fn describe(&self) -> String {
+            match self {
+                ArgumentType::Keyword(words) => one_of(words),
+                ArgumentType::Number { min, max, off } => {
+                    let range = if *max == usize::MAX {
+                        if *min == 1 {
+                            "a positive number".to_string()
+                        } else {
+                            format!("a number from {}", min)
+                        }
+                    } else {
+                        format!("{} to {}", min, max)
+                    };
+                    if *off {
+                        format!("{} or `off`", range)
+                    } else {
+                        range
+                    }
+                }
+                ArgumentType::Variable => "a variable name".to_string(),
+                ArgumentType::Command => "a command".to_string(),
+                ArgumentType::Setting => "a setting".to_string(),
+                ArgumentType::Text => "some text".to_string(),
+            }
+        }
+
+        /// The words that can be completed for this type of argument.
+        fn candidates(&self) -> Vec<&'static str> {
+            match self {
+                ArgumentType::Keyword(words) => words.to_vec(),
+                ArgumentType::Number { off: true, .. } => vec!["off"],
+                ArgumentType::Command => command_names().collect(),
+                ArgumentType::Setting => setting_names().collect(),
+                _ => vec![],
+            }
+        }
+
+        /// Checks a single word against this type. `name` is what the value is called in errors.
+        fn check<'a>(&self, name: &str, word: &'a str) -> Result<Argument<'a>, String> {
+            match self {
+                ArgumentType::Keyword(words) if words.contains(&word) => Ok(Argument::Word(word)),
+                ArgumentType::Keyword(words) => Err(match did_you_mean(word, words.iter().copied()) {
+                    Some(suggestion) => format!("invalid {} `{}`, did you mean `{}`?", name, word, suggestion),
+                    None => format!("invalid {} `{}`, expected {}", name, word, self.describe()),
+                }),
+                ArgumentType::Number { off: true, .. } if word == "off" => Ok(Argument::Number(None)),
+                ArgumentType::Number { min, max, .. } => match word.parse::<usize>() {
+                    Ok(number) if (*min..=*max).contains(&number) => Ok(Argument::Number(Some(number))),
+                    _ => Err(format!("invalid {} `{}`, expected {}", name, word, self.describe())),
+                },
+                ArgumentType::Variable => {
+                    let mut chars = word.chars();
+                    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+                        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+                    if valid {
+                        Ok(Argument::Word(word))
+                    } else {
+                        Err(format!("invalid variable name `{}`", word))
+                    }
+                }
+                ArgumentType::Command => find_command(word).map(|command| Argument::Word(command.name)),
+                ArgumentType::Setting => find_setting(word).map(Argument::Setting),
+                ArgumentType::Text => Ok(Argument::Text(word.to_string())),
+            }
+        }
+    }
+
+    impl Command {
+        /// How the command is written, e.g. `:depth [levels]`.
+        pub fn usage(&self) -> String {
+            let mut usage = format!(":{}", self.name);
+            for parameter in self.parameters {
+                let placeholder = match parameter.kind {
+                    ArgumentType::Setting => "<setting> <value>".to_string(),
+                    _ => format!("<{}>", parameter.name),
+                };
+                if parameter.optional {
+                    usage.push_str(&format!(" [{}]", placeholder));
+                } else {
+                    usage.push_str(&format!(" {}", placeholder));
+                }
+            }
+            usage
+        }
+
+        /// Checks the arguments against the parameters. Optional arguments that were left out are
+        /// missing from the result, and a setting is followed by its value.
+        pub fn parse<'a>(&self, arguments: &[&'a str]) -> Result<Vec<Argument<'a>>, String> {
+            let usage = || format!("Usage: {}", self.usage());
+            let mut parsed = Vec::new();
+            let mut remaining = arguments;
+            for parameter in self.parameters {
+                let Some((word, rest)) = remaining.split_first() else {
+                    if parameter.optional {
+                        break;
+                    }
+                    return Err(usage());
+                };
+                remaining = rest;
+                match parameter.kind {
+                    ArgumentType::Text => {
+                        parsed.push(Argument::Text(arguments[arguments.len() - remaining.len() - 1..].join(" ")));
+                        remaining = &[];
+                    }
+                    ArgumentType::Setting => {
+                        let setting = find_setting(word)?;
+                        let Some((value, rest)) = remaining.split_first() else {
+                            return Err(format!("missing value for `{}`, expected {}", setting.name, setting.value.describe()));
+                        };
+                        remaining = rest;
+                        parsed.push(Argument::Setting(setting));
+                        parsed.push(setting.value.check(setting.name, value)?);
+                    }
+                    kind => parsed.push(kind.check(parameter.name, word)?),
+                }
+            }
+            if !remaining.is_empty() {
+                return Err(usage());
+            }
+            Ok(parsed)
+        }
+
+        /// The usage, the description and what each argument may be.
+        pub fn help(&self) -> String {
+            let mut help = format!("{}\n{}\n", self.usage(), self.help);
+            for parameter in self.parameters {
+                match parameter.kind {
+                    ArgumentType::Setting => {
+                        let width = SETTINGS.iter().map(|setting| setting.name.len()).max().unwrap_or(0);
+                        help.push_str("Settings:\n");
+                        for setting in SETTINGS {
+                            help.push_str(&format!(
+                                "  {:width$}  {} ({})\n",
+                                setting.name,
+                                setting.help,
+                                setting.value.describe(),
+                                width = width
+                            ));
+                        }
+                    }
+                    ArgumentType::Command | ArgumentType::Text => {}
+                    kind => help.push_str(&format!("  {}: {}\n", parameter.name, kind.describe())),
+                }
+            }
+            help
+        }
+    }
+
+    /// One line for each command, with its usage and description.
+    pub fn help() -> String {
+        let width = COMMANDS.iter().map(|command| command.usage().len()).max().unwrap_or(0);
+        let mut help = String::new();
+        for command in COMMANDS {
+            help.push_str(&format!("{:width$}  {}\n", command.usage(), command.help, width = width));
+        }
+        help
+    }
+
+    /// Completes the word before the cursor in a command line: the command name, then its keyword
+    /// arguments and settings. Returns where the word starts and the candidates for it.
+    pub fn complete(line: &str, position: usize) -> (usize, Vec<&'static str>) {
+        let before = &line[..position];
+        let Some(rest) = before.strip_prefix(':') else {
+            return (position, vec![]);
+        };
+        let start = before.rfind(' ').map(|index| index + 1).unwrap_or(1);
+        let word = &before[start..];
+        let mut words = rest.split(' ').filter(|word| !word.is_empty()).collect::<Vec<_>>();
+        if !word.is_empty() {
+            words.pop();
+        }
+
+        let candidates = match words.split_first() {
+            None => command_names().collect(),
+            Some((name, arguments)) => match command(name) {
+                Some(command) => candidates(command, arguments),
+                None => vec![],
+            },
+        };
+        let matches = candidates
+            .into_iter()
+            .filter(|candidate| candidate.starts_with(word))
+            .collect();
+        (start, matches)
+    }
+
+    /// The candidates for the argument after the given ones.
+    fn candidates(command: &Command, mut arguments: &[&str]) -> Vec<&'static str> {
+        for parameter in command.parameters {
+            match (parameter.kind, arguments) {
+                (ArgumentType::Setting, [name]) => {
+                    return setting(name).map(|setting| setting.value.candidates()).unwrap_or_default()
+                }
+                (ArgumentType::Setting, [_, _, rest @ ..]) => arguments = rest,
+                (kind, []) => return kind.candidates(),
+                (_, [_, rest @ ..]) => arguments = rest,
+            }
+        }
+        vec![]
+    }
+
+    /// Completes commands and their arguments in the REPL.
+    pub struct CommandCompleter;
+
+    impl Completer for CommandCompleter {
+        fn complete(&mut self, line: &str, pos: usize) -> Vec<Suggestion> {
+            let (start, candidates) = complete(line, pos);
+            candidates
+                .into_iter()
+                .map(|candidate| Suggestion {
+                    value: candidate.to_string(),
+                    span: Span::new(start, pos),
+                    append_whitespace: true,
+                    ..Default::default()
+                })
+                .collect()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+
+        use super::*;
+
+        #[test]
+        fn test_parse() {
+            let set = command("set").unwrap();
+            assert_eq!(set.parse(&[]), Ok(vec![]));
+            assert_eq!(
+                set.parse(&["precision", "20"]),
+                Err("invalid digits `20`, expected 1 to 17 or `off`".to_string())
+            );
+            assert_eq!(
+                set.parse(&["precision", "8"]),
+                Ok(vec![Argument::Setting(setting("digits").unwrap()), Argument::Number(Some(8))])
+            );
+            assert_eq!(set.parse(&["notaton", "si"]), Err("Unknown setting `notaton`, did you mean `notation`?".to_string()));
+            assert_eq!(set.parse(&["notation", "sci"]), Err("invalid notation `sci`, did you mean `si`?".to_string()));
+            assert_eq!(set.parse(&["radix"]), Err("missing value for `radix`, expected `bin`, `oct`, `dec` or `hex`".to_string()));
+
+            let del = command("del").unwrap();
+            assert_eq!(del.parse(&["x"]), Ok(vec![Argument::Word("x")]));
+            assert_eq!(del.parse(&[]), Err("Usage: :del <variable>".to_string()));
+            assert_eq!(del.parse(&["x", "y"]), Err("Usage: :del <variable>".to_string()));
+            assert_eq!(find_command("hlep").unwrap_err(), "Unknown command `:hlep`, did you mean `:help`?");
+        }
+
+        #[test]
+        fn test_complete() {
+            assert_eq!(complete(":de", 3), (1, vec!["del", "debug", "depth"]));
+            assert_eq!(complete(":set n", 6), (5, vec!["notation"]));
+            assert_eq!(complete(":set notation e", 15), (14, vec!["engineering"]));
+            assert_eq!(complete(":depth ", 7), (7, vec!["off"]));
+            assert_eq!(complete(":set radix hex ", 15), (15, vec![]));
+            assert_eq!(complete("1 + 2", 5), (5, vec![]));
+        }
+
+        #[test]
+        fn test_help() {
+            assert!(help().contains(":set [<setting> <value>]"));
+            assert!(command("layout").unwrap().help().ends_with("  layout: `auto`, `tree`, `top-down` or `inline`\n"));
+        }
+    }
+}
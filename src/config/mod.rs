@@ -0,0 +1,360 @@
+pub mod config {
+    use std::fmt;
+    use std::path::{Path, PathBuf};
+    use std::str::FromStr;
+
+    use serde::de::Error as _;
+    use serde::{Deserialize, Deserializer};
+
+    use crate::expr_writer::expr_writer::{ColorChoice, Layout};
+    use crate::format::format::{Notation, NumberFormat, Precision};
+    use crate::functions::functions::bundled_plugin;
+    use crate::grammar::grammar::{AngleUnit, Radix};
+    use crate::repl::repl::Session;
+    use crate::value::value::{IntegerMode, Overflow, WordSize};
+
+    /// The key bindings used when editing a line in the REPL.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub enum EditMode {
+        #[default]
+        Emacs,
+        Vi,
+    }
+
+    impl fmt::Display for EditMode {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                EditMode::Emacs => write!(f, "emacs"),
+                EditMode::Vi => write!(f, "vi"),
+            }
+        }
+    }
+
+    impl FromStr for EditMode {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s {
+                "emacs" => Ok(EditMode::Emacs),
+                "vi" => Ok(EditMode::Vi),
+                _ => Err(format!("invalid edit mode `{}`, expected `emacs` or `vi`", s)),
+            }
+        }
+    }
+
+    /// Deserializes a setting through its `FromStr` implementation, so that the config file accepts
+    /// exactly what the command line and `:set` do.
+    fn parsed<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: FromStr<Err = String>,
+    {
+        Option::<String>::deserialize(deserializer)?
+            .map(|text| text.parse().map_err(D::Error::custom))
+            .transpose()
+    }
+
+    /// Settings read from the config file or given on the command line. Every setting is optional, so
+    /// that the two can be layered with [`Config::merge`].
+    #[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+    #[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+    pub struct Config {
+        pub digits: Option<usize>,
+        pub decimals: Option<usize>,
+        #[serde(deserialize_with = "parsed")]
+        pub notation: Option<Notation>,
+        pub separators: Option<bool>,
+        #[serde(deserialize_with = "parsed")]
+        pub radix: Option<Radix>,
+        /// The angle mode: `rad`, `deg` or `grad`.
+        #[serde(deserialize_with = "parsed")]
+        pub angle: Option<AngleUnit>,
+        #[serde(deserialize_with = "parsed")]
+        pub color: Option<ColorChoice>,
+        #[serde(deserialize_with = "parsed")]
+        pub layout: Option<Layout>,
+        pub depth: Option<usize>,
+        /// The prompt, where `{n}` is replaced by the number of the next result.
+        pub prompt: Option<String>,
+        #[serde(deserialize_with = "parsed")]
+        pub edit_mode: Option<EditMode>,
+        /// The integers to compute with: a word size such as `u32` for programmer mode, or `float`.
+        #[serde(deserialize_with = "parsed")]
+        pub word: Option<Backend>,
+        #[serde(deserialize_with = "parsed")]
+        pub overflow: Option<Overflow>,
+        pub strict: Option<bool>,
+        pub symbolic: Option<bool>,
+        pub warn_precedence: Option<bool>,
+        pub plugins: Option<Vec<String>>,
+        /// A script of definitions to run before the first prompt.
+        pub rc: Option<PathBuf>,
+    }
+
+    /// The numbers expressions are evaluated with.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Backend {
+        /// Floats, with integers kept exact where possible.
+        Float,
+        /// Fixed-width integers, as in programmer mode.
+        Word(WordSize),
+    }
+
+    impl fmt::Display for Backend {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                Backend::Float => write!(f, "float"),
+                Backend::Word(word) => write!(f, "{}", word),
+            }
+        }
+    }
+
+    impl FromStr for Backend {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s {
+                "float" | "off" => Ok(Backend::Float),
+                word => word.parse().map(Backend::Word),
+            }
+        }
+    }
+
+    #[derive(Debug, thiserror::Error)]
+    pub enum ConfigError {
+        #[error("{}: {source}", .path.display())]
+        Read { path: PathBuf, source: std::io::Error },
+        #[error("{}: {source}", .path.display())]
+        Parse { path: PathBuf, source: Box<toml::de::Error> },
+        #[error("{}: {message}", .path.display())]
+        Invalid { path: PathBuf, message: String },
+    }
+
+    /// The directory the config file and rc script are looked for in: `$XDG_CONFIG_HOME/calc`, or
+    /// `~/.config/calc` if that isn't set.
+    pub fn config_dir() -> Option<PathBuf> {
+        let base = match std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(base.join("calc"))
+    }
+
+    pub fn default_config_path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join("config.toml"))
+    }
+
+    pub fn default_rc_path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join("rc"))
+    }
+
+    impl Config {
+        /// Reads the config file. A missing file is the same as an empty one.
+        pub fn load(path: &Path) -> Result<Config, ConfigError> {
+            match std::fs::read_to_string(path) {
+                Ok(text) => Config::parse(&text).map_err(|source| ConfigError::Parse {
+                    path: path.to_path_buf(),
+                    source: Box::new(source),
+                }),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+                Err(source) => Err(ConfigError::Read {
+                    path: path.to_path_buf(),
+                    source,
+                }),
+            }
+        }
+
+        pub fn parse(text: &str) -> Result<Config, toml::de::Error> {
+            toml::from_str(text)
+        }
+
+        /// Layers the other settings on top of these ones, e.g. the command line flags on top of the
+        /// config file.
+        pub fn merge(self, other: Config) -> Config {
+            Config {
+                digits: other.digits.or(self.digits),
+                decimals: other.decimals.or(self.decimals),
+                notation: other.notation.or(self.notation),
+                separators: other.separators.or(self.separators),
+                radix: other.radix.or(self.radix),
+                angle: other.angle.or(self.angle),
+                color: other.color.or(self.color),
+                layout: other.layout.or(self.layout),
+                depth: other.depth.or(self.depth),
+                prompt: other.prompt.or(self.prompt),
+                edit_mode: other.edit_mode.or(self.edit_mode),
+                word: other.word.or(self.word),
+                overflow: other.overflow.or(self.overflow),
+                strict: other.strict.or(self.strict),
+                symbolic: other.symbolic.or(self.symbolic),
+                warn_precedence: other.warn_precedence.or(self.warn_precedence),
+                plugins: other.plugins.or(self.plugins),
+                rc: other.rc.or(self.rc),
+            }
+        }
+
+        /// Applies the settings to a session. Colours are left to the caller, since they depend on
+        /// whether the output is a terminal.
+        pub fn apply(&self, session: &mut Session) -> Result<(), String> {
+            let numbers = &mut session.style.numbers;
+            // Decimals win over digits, so that a config file can set digits and the command line decimals.
+            numbers.precision = match (self.digits, self.decimals) {
+                (_, Some(decimals)) => Precision::Decimals(decimals),
+                (Some(digits @ 1..=17), None) => Precision::Significant(digits),
+                (Some(digits), None) => return Err(format!("invalid number of digits `{}`, expected 1 to 17", digits)),
+                (None, None) => numbers.precision,
+            };
+            numbers.notation = self.notation.unwrap_or(numbers.notation);
+            numbers.separators = self.separators.unwrap_or(numbers.separators);
+            numbers.radix = self.radix.unwrap_or(numbers.radix);
+            if let Some(angle) = self.angle {
+                session.set_angle_unit(angle);
+            }
+
+            session.layout.layout = self.layout.unwrap_or(session.layout.layout);
+            session.layout.max_depth = self.depth.or(session.layout.max_depth);
+            if let Some(prompt) = &self.prompt {
+                session.prompt = prompt.clone();
+            }
+            session.edit_mode = self.edit_mode.unwrap_or(session.edit_mode);
+            session.warn_precedence = self.warn_precedence.unwrap_or(session.warn_precedence);
+
+            let context = &mut session.context;
+            let environment = context.environment_mut();
+            environment.strict = self.strict.unwrap_or(environment.strict);
+            environment.symbolic = self.symbolic.unwrap_or(environment.symbolic);
+            match self.word {
+                Some(Backend::Word(word)) => {
+                    environment.integer_mode = Some(IntegerMode {
+                        word,
+                        overflow: self.overflow.unwrap_or_default(),
+                    })
+                }
+                Some(Backend::Float) => environment.integer_mode = None,
+                None => {}
+            }
+            for name in self.plugins.iter().flatten() {
+                match bundled_plugin(name) {
+                    Some(plugin) => context.register(plugin.as_ref()),
+                    None => return Err(format!("unknown plugin `{}`", name)),
+                }
+            }
+            Ok(())
+        }
+
+        /// The settings a session is currently using, for `:config`.
+        pub fn from_session(session: &Session) -> Config {
+            let numbers: &NumberFormat = &session.style.numbers;
+            let (digits, decimals) = match numbers.precision {
+                Precision::Significant(digits) => (Some(digits), None),
+                Precision::Decimals(decimals) => (None, Some(decimals)),
+            };
+            let integer_mode = session.context.environment().integer_mode;
+            Config {
+                digits,
+                decimals,
+                notation: Some(numbers.notation),
+                separators: Some(numbers.separators),
+                radix: Some(numbers.radix),
+                angle: Some(session.context.environment().angle_unit),
+                color: session.color,
+                layout: Some(session.layout.layout),
+                depth: session.layout.max_depth,
+                prompt: Some(session.prompt.clone()),
+                edit_mode: Some(session.edit_mode),
+                word: Some(integer_mode.map_or(Backend::Float, |mode| Backend::Word(mode.word))),
+                overflow: integer_mode.map(|mode| mode.overflow),
+                strict: Some(session.context.environment().strict),
+                symbolic: Some(session.context.environment().symbolic),
+                warn_precedence: Some(session.warn_precedence),
+                plugins: None,
+                rc: None,
+            }
+        }
+    }
+
+    /// Writes the settings in the same form as the config file.
+    impl fmt::Display for Config {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            fn setting(f: &mut fmt::Formatter, key: &str, value: Option<impl fmt::Display>) -> fmt::Result {
+                match value {
+                    Some(value) => writeln!(f, "{} = {}", key, value),
+                    None => Ok(()),
+                }
+            }
+            fn quoted(value: &Option<impl fmt::Display>) -> Option<String> {
+                value.as_ref().map(|value| format!("\"{}\"", value))
+            }
+
+            setting(f, "digits", self.digits)?;
+            setting(f, "decimals", self.decimals)?;
+            setting(f, "notation", quoted(&self.notation))?;
+            setting(f, "separators", self.separators)?;
+            setting(f, "radix", quoted(&self.radix))?;
+            setting(f, "angle", quoted(&self.angle))?;
+            setting(f, "color", quoted(&self.color))?;
+            setting(f, "layout", quoted(&self.layout))?;
+            setting(f, "depth", self.depth)?;
+            setting(f, "prompt", self.prompt.as_ref().map(|prompt| format!("{:?}", prompt)))?;
+            setting(f, "edit-mode", quoted(&self.edit_mode))?;
+            setting(f, "word", quoted(&self.word))?;
+            setting(f, "overflow", quoted(&self.overflow))?;
+            setting(f, "strict", self.strict)?;
+            setting(f, "symbolic", self.symbolic)?;
+            setting(f, "warn-precedence", self.warn_precedence)?;
+            setting(f, "plugins", self.plugins.as_ref().map(|plugins| format!("{:?}", plugins)))?;
+            setting(f, "rc", self.rc.as_ref().map(|rc| format!("{:?}", rc.display().to_string())))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+
+        use super::*;
+
+        #[test]
+        fn test_parse() {
+            let config = Config::parse(
+                r#"
+                digits = 6
+                notation = "si"
+                edit-mode = "vi"
+                word = "u32"
+                prompt = "calc[{n}]> "
+                "#,
+            )
+            .unwrap();
+            assert_eq!(config.digits, Some(6));
+            assert_eq!(config.notation, Some(Notation::Si));
+            assert_eq!(config.edit_mode, Some(EditMode::Vi));
+            assert_eq!(config.word, Some(Backend::Word("u32".parse().unwrap())));
+
+            let error = Config::parse("notation = \"roman\"").unwrap_err();
+            assert!(error.to_string().contains("invalid notation `roman`"));
+            assert!(Config::parse("colour = \"never\"").is_err());
+        }
+
+        #[test]
+        fn test_command_line_overrides_file() {
+            let file = Config::parse("digits = 6\nseparators = true").unwrap();
+            let flags = Config {
+                decimals: Some(2),
+                separators: Some(false),
+                ..Default::default()
+            };
+            let mut session = Session::default();
+            file.merge(flags).apply(&mut session).unwrap();
+            assert_eq!(session.style.numbers.precision, Precision::Decimals(2));
+            assert!(!session.style.numbers.separators);
+        }
+
+        #[test]
+        fn test_round_trip() {
+            let mut session = Session::default();
+            Config::parse("radix = \"hex\"\nangle = \"deg\"\nprompt = \"> \"").unwrap().apply(&mut session).unwrap();
+            assert_eq!(session.style.numbers.angle, AngleUnit::Degrees);
+            let current = Config::from_session(&session);
+            assert_eq!(Config::parse(&current.to_string()).unwrap(), current);
+        }
+    }
+}
@@ -0,0 +1,78 @@
+pub mod constants {
+
+    #[derive(Debug, Clone, Copy)]
+    pub struct Constant {
+        pub name: &'static str,
+        pub value: f64,
+        pub description: &'static str,
+    }
+
+    /// Constants that are available in every session. Physical constants are in SI units, using
+    /// the exact values from the 2019 redefinition where there is one, and are named `phys.` so that
+    /// short names such as `c` and `h` are left for variables.
+    pub const CONSTANTS: &[Constant] = &[
+        Constant {
+            name: "pi",
+            value: std::f64::consts::PI,
+            description: "ratio of a circle's circumference to its diameter",
+        },
+        Constant {
+            name: "e",
+            value: std::f64::consts::E,
+            description: "base of the natural logarithm",
+        },
+        Constant {
+            name: "tau",
+            value: std::f64::consts::TAU,
+            description: "ratio of a circle's circumference to its radius",
+        },
+        Constant {
+            name: "phi",
+            value: 1.618033988749895,
+            description: "golden ratio",
+        },
+        Constant {
+            name: "inf",
+            value: f64::INFINITY,
+            description: "infinity",
+        },
+        Constant {
+            name: "nan",
+            value: f64::NAN,
+            description: "not a number",
+        },
+        Constant {
+            name: "phys.c",
+            value: 299_792_458.0,
+            description: "speed of light in vacuum (m/s)",
+        },
+        Constant {
+            name: "phys.G",
+            value: 6.67430e-11,
+            description: "Newtonian constant of gravitation (m^3/(kg s^2))",
+        },
+        Constant {
+            name: "phys.h",
+            value: 6.62607015e-34,
+            description: "Planck constant (J s)",
+        },
+        Constant {
+            name: "phys.k_B",
+            value: 1.380649e-23,
+            description: "Boltzmann constant (J/K)",
+        },
+        Constant {
+            name: "phys.N_A",
+            value: 6.02214076e23,
+            description: "Avogadro constant (1/mol)",
+        },
+    ];
+
+    pub fn lookup(name: &str) -> Option<&'static Constant> {
+        CONSTANTS.iter().find(|constant| constant.name == name)
+    }
+
+    pub fn names() -> impl Iterator<Item = &'static str> {
+        CONSTANTS.iter().map(|constant| constant.name)
+    }
+}
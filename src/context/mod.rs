@@ -0,0 +1,355 @@
+pub mod context {
+    use std::collections::{BTreeMap, HashMap};
+    use std::ops::Range;
+
+    use pest::error::InputLocation;
+    use pest::iterators::{Pair, Pairs};
+
+    use crate::constants::constants;
+    use crate::functions::functions::{Parameters, Plugin};
+    use crate::grammar::grammar::{eval, eval_legacy, parse_equation, Environment, EvalError, Expr, HistoryEntry, Rule};
+    use crate::value::value::Value;
+
+    #[derive(Debug, thiserror::Error)]
+    pub enum Error {
+        #[error("parse failed: {}", .0.variant.message())]
+        Parse(Box<pest::error::Error<Rule>>),
+        #[error(transparent)]
+        Eval(#[from] EvalError),
+        #[error("implicit multiplication is not allowed in strict mode, use an explicit `*`")]
+        ImplicitMultiplication { position: usize },
+        #[error("`{0}` is a built-in constant, use `shadow {0} := ...` to replace it for this session")]
+        ProtectedConstant(String),
+        #[error("{}", describe_unbound_variables(.0))]
+        UnboundVariables(BTreeMap<String, Option<String>>),
+        #[error("`{0}` can't be defined in terms of itself")]
+        SelfReference(String),
+        #[error("`:{0}` is a command, not an expression")]
+        Command(String),
+    }
+
+    impl Error {
+        /// The position in the input that the error refers to, if there is one.
+        pub fn position(&self) -> Option<usize> {
+            self.spans().first().map(|span| span.start)
+        }
+
+        /// The parts of the input that the error refers to, such as both operands of an operation.
+        pub fn spans(&self) -> Vec<Range<usize>> {
+            let position = match self {
+                Error::Parse(e) => match e.location {
+                    InputLocation::Pos(position) => position,
+                    InputLocation::Span((start, _)) => start,
+                },
+                Error::ImplicitMultiplication { position } | Error::Eval(EvalError::ImplicitMultiplication { position: Some(position) }) => *position,
+                Error::Eval(EvalError::LengthMismatch { lhs_span, rhs_span, .. } | EvalError::ShapeMismatch { lhs_span, rhs_span, .. }) => {
+                    return vec![lhs_span.clone(), rhs_span.clone()]
+                }
+                _ => return vec![],
+            };
+            // A single character, which the caret points at.
+            std::iter::once(position..position + 1).collect()
+        }
+    }
+
+    pub fn describe_unbound_variables(unbound: &BTreeMap<String, Option<String>>) -> String {
+        unbound
+            .iter()
+            .map(|(name, suggestion)| match suggestion {
+                Some(suggestion) => format!("unknown variable `{}`, did you mean `{}`?", name, suggestion),
+                None => format!("unknown variable `{}`", name),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// The outcome of evaluating one line of input.
+    #[derive(Debug, Clone)]
+    pub struct Evaluation {
+        /// The expression tree, with the value of every node.
+        pub expr: Expr,
+        /// The variable that was assigned, for input such as `a := 1 + 2`.
+        pub assigned: Option<String>,
+    }
+
+    impl Evaluation {
+        pub fn value(&self) -> Value {
+            self.expr.value()
+        }
+    }
+
+    /// An evaluation session, which owns the variables and the history of results.
+    #[derive(Debug, Clone, Default)]
+    pub struct Context {
+        environment: Environment,
+    }
+
+    impl Context {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        pub fn environment(&self) -> &Environment {
+            &self.environment
+        }
+
+        pub fn environment_mut(&mut self) -> &mut Environment {
+            &mut self.environment
+        }
+
+        pub fn variables(&self) -> &HashMap<String, Expr> {
+            &self.environment.variables
+        }
+
+        pub fn set_variable(&mut self, name: &str, value: Value) {
+            self.environment.variables.insert(name.to_string(), Expr::literal(value));
+        }
+
+        /// Adds the plugin's functions and variable resolvers to the session.
+        pub fn register(&mut self, plugin: &dyn Plugin) {
+            self.environment.registry.register(plugin);
+        }
+
+        /// Registers a native function, which can then be called as `name(...)`. The number and types
+        /// of the arguments are checked against the parameters before the function is called.
+        pub fn register_function(
+            &mut self,
+            name: &str,
+            parameters: Parameters,
+            function: impl Fn(&[Value]) -> Result<Value, String> + Send + Sync + 'static,
+        ) {
+            self.environment.registry.register_function(name, parameters, function);
+        }
+
+        /// Registers a callback for variables that aren't defined in the session or built in, such as
+        /// `config.timeout`.
+        pub fn register_resolver(&mut self, resolver: impl Fn(&str) -> Option<Value> + Send + Sync + 'static) {
+            self.environment.registry.register_resolver(resolver);
+        }
+
+        /// Evaluates an expression or assignment and returns its value. Unlike [`Context::evaluate`],
+        /// an expression that refers to unknown variables is an error, and isn't added to the history.
+        pub fn eval(&mut self, input: &str) -> Result<Value, Error> {
+            let evaluation = self.evaluate_unrecorded(input)?;
+            let unbound = evaluation.expr.unbound_variables();
+            if !unbound.is_empty() {
+                return Err(Error::UnboundVariables(unbound));
+            }
+            self.record(input, &evaluation);
+            Ok(evaluation.value())
+        }
+
+        /// Evaluates an expression or assignment, returning the whole expression tree. Unknown variables
+        /// evaluate to NaN and are left in the tree, and an assignment that refers to them is not stored.
+        /// In symbolic mode they are free variables instead, and an assignment stores the formula. The
+        /// result is added to the history either way, unless it is an error.
+        pub fn evaluate(&mut self, input: &str) -> Result<Evaluation, Error> {
+            let evaluation = self.evaluate_unrecorded(input)?;
+            self.record(input, &evaluation);
+            Ok(evaluation)
+        }
+
+        fn evaluate_unrecorded(&mut self, input: &str) -> Result<Evaluation, Error> {
+            let mut pairs = self.parse(input)?;
+            let pair = pairs.next().unwrap();
+            let scoped = self.scope(pairs.next(), eval)?;
+            let environment = scoped.as_ref().unwrap_or(&self.environment);
+            let evaluation = match pair.as_rule() {
+                Rule::assignment => {
+                    let mut inner_pairs = pair.into_inner().peekable();
+                    let shadow = inner_pairs.next_if(|pair| pair.as_rule() == Rule::shadow).is_some();
+                    let variable = inner_pairs.next().unwrap().as_str().to_string();
+                    if !shadow && constants::lookup(&variable).is_some() && !self.environment.variables.contains_key(&variable) {
+                        return Err(Error::ProtectedConstant(variable));
+                    }
+
+                    let expr = eval(inner_pairs.next().unwrap().into_inner(), environment)?;
+                    if expr.free_variables().contains(&variable) {
+                        return Err(Error::SelfReference(variable));
+                    }
+                    let assigned = if expr.unbound_variables().is_empty() {
+                        self.environment.variables.insert(variable.clone(), expr.clone());
+                        Some(variable)
+                    } else {
+                        None
+                    };
+                    Evaluation { expr, assigned }
+                }
+                Rule::command => return Err(Error::Command(pair.into_inner().next().unwrap().as_str().to_string())),
+                _ => Evaluation {
+                    expr: eval(pair.into_inner(), environment)?,
+                    assigned: None,
+                },
+            };
+            Ok(evaluation)
+        }
+
+        fn record(&mut self, input: &str, evaluation: &Evaluation) {
+            self.environment.history.push(HistoryEntry {
+                source: input.trim().to_string(),
+                expr: evaluation.expr.clone(),
+            });
+        }
+
+        /// Evaluates the expression (or the right hand side of the assignment) under the precedence rules
+        /// from before `^` became right-associative, without assigning or recording anything.
+        pub fn evaluate_legacy(&self, input: &str) -> Result<Expr, Error> {
+            let mut pairs = self.parse(input)?;
+            let pair = pairs.next().unwrap();
+            let scoped = self.scope(pairs.next(), eval_legacy)?;
+            let expr_pair = match pair.as_rule() {
+                Rule::assignment => pair.into_inner().last().unwrap(),
+                Rule::command => return Err(Error::Command(pair.into_inner().next().unwrap().as_str().to_string())),
+                _ => pair,
+            };
+            Ok(eval_legacy(expr_pair.into_inner(), scoped.as_ref().unwrap_or(&self.environment))?)
+        }
+
+        /// The environment with the values given in a `where` clause, if the input has one. Each value
+        /// can refer to the ones before it.
+        fn scope(&self, clause: Option<Pair<Rule>>, eval: fn(Pairs<Rule>, &Environment) -> Result<Expr, EvalError>) -> Result<Option<Environment>, Error> {
+            let Some(clause) = clause.filter(|pair| pair.as_rule() == Rule::where_clause) else {
+                return Ok(None);
+            };
+            let mut environment = self.environment.clone();
+            for binding in clause.into_inner().filter(|pair| pair.as_rule() == Rule::binding) {
+                let mut inner_pairs = binding.into_inner();
+                let name = inner_pairs.next().unwrap().as_str().to_string();
+                let expr = eval(inner_pairs.next().unwrap().into_inner(), &environment)?;
+                environment.variables.insert(name, expr);
+            }
+            Ok(Some(environment))
+        }
+
+        fn parse<'i>(&self, input: &'i str) -> Result<Pairs<'i, Rule>, Error> {
+            let pairs = parse_equation(input).map_err(|e| Error::Parse(Box::new(e)))?;
+            if self.environment.strict {
+                if let Some(implicit) = pairs.clone().flatten().find(|pair| pair.as_rule() == Rule::implicit_multiply) {
+                    return Err(Error::ImplicitMultiplication {
+                        position: implicit.as_span().start(),
+                    });
+                }
+            }
+            Ok(pairs)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+
+        use super::*;
+        use crate::functions::functions::ParameterType;
+
+        #[test]
+        fn test_eval() {
+            let mut context = Context::new();
+            assert_eq!(context.eval("1 + 2 * 3").unwrap(), Value::Float(7.0));
+            assert_eq!(context.eval("a := 6 * 7").unwrap(), Value::Float(42.0));
+            assert_eq!(context.eval("a / 2").unwrap(), Value::Float(21.0));
+            assert_eq!(context.eval("$1 + ans").unwrap(), Value::Float(28.0));
+        }
+
+        #[test]
+        fn test_lists() {
+            let mut context = Context::new();
+            context.eval("xs := [4, 8, 15, 16, 23, 42]").unwrap();
+            assert_eq!(context.eval("mean(xs)").unwrap(), Value::Float(18.0));
+            assert_eq!(context.eval("count(xs, [1, 2])").unwrap(), Value::Integer(8));
+            assert!(matches!(context.variables()["xs"], Expr::List { .. }));
+        }
+
+        #[test]
+        fn test_set_variable() {
+            let mut context = Context::new();
+            context.set_variable("rate", Value::Float(0.5));
+            assert_eq!(context.eval("rate * 10").unwrap(), Value::Float(5.0));
+        }
+
+        #[test]
+        fn test_native_functions_and_resolvers() {
+            let mut context = Context::new();
+            context.register_function("price", Parameters::Fixed(vec![ParameterType::String]), |arguments| match &arguments[0] {
+                Value::String(sku) if sku == "SKU1" => Ok(Value::Float(9.5)),
+                sku => Err(format!("no such product {}", sku)),
+            });
+            context.register_resolver(|name| (name == "config.timeout").then_some(Value::Integer(30)));
+            context.set_variable("quantity", Value::Integer(2));
+
+            assert_eq!(context.eval("price(\"SKU1\") * quantity").unwrap(), Value::Float(19.0));
+            assert_eq!(context.eval("config.timeout / 2").unwrap(), Value::Float(15.0));
+            assert_eq!(context.eval("sqrt(16) + max(1, 5, 3)").unwrap(), Value::Float(9.0));
+            assert_eq!(
+                context.eval("price(\"SKU2\")").unwrap_err().to_string(),
+                "`price` failed: no such product \"SKU2\""
+            );
+            assert_eq!(
+                context.eval("price(1)").unwrap_err().to_string(),
+                "argument 1 of `price` must be a string, found 1"
+            );
+            assert_eq!(context.eval("sqrt(1, 2)").unwrap_err().to_string(), "`sqrt` takes 1 argument, found 2");
+            assert_eq!(
+                context.eval("sqr(4)").unwrap_err().to_string(),
+                "unknown function `sqr`, did you mean `sqrt`?"
+            );
+            // Session variables are still multiplied implicitly.
+            assert_eq!(context.eval("quantity(3 + 1)").unwrap(), Value::Float(8.0));
+        }
+
+        #[test]
+        fn test_errors() {
+            let mut context = Context::new();
+            assert!(matches!(context.eval("1 +"), Err(Error::Parse(_))));
+            assert!(matches!(context.eval("pi := 3"), Err(Error::ProtectedConstant(_))));
+            assert!(matches!(context.eval(":state"), Err(Error::Command(_))));
+            match context.eval("vlaue + 1") {
+                Err(e @ Error::UnboundVariables(_)) => assert_eq!(e.to_string(), "unknown variable `vlaue`"),
+                other => panic!("Expected unbound variables, found {:?}", other),
+            }
+            // Only results are numbered, so `$1` is still the first one to succeed.
+            assert!(context.environment().history.is_empty());
+
+            context.environment_mut().strict = true;
+            assert!(matches!(
+                context.eval("2pi"),
+                Err(Error::ImplicitMultiplication { position: 1 })
+            ));
+            context.eval("x := 3").unwrap();
+            let error = context.eval("x(2)").unwrap_err();
+            assert!(matches!(error, Error::Eval(EvalError::ImplicitMultiplication { position: Some(1) })));
+            assert_eq!(error.position(), Some(1));
+            assert_eq!(context.eval("x * (2)").unwrap(), Value::Float(6.0));
+        }
+
+        #[test]
+        fn test_unbound_assignment_is_not_stored() {
+            let mut context = Context::new();
+            let evaluation = context.evaluate("a := b + 1").unwrap();
+            assert_eq!(evaluation.assigned, None);
+            assert!(!context.variables().contains_key("a"));
+        }
+
+        #[test]
+        fn test_symbolic_mode() {
+            let mut context = Context::new();
+            context.environment_mut().symbolic = true;
+            let evaluation = context.evaluate("f := 2*x + 1").unwrap();
+            assert_eq!(evaluation.assigned.as_deref(), Some("f"));
+            assert_eq!(evaluation.expr.free_variables().into_iter().collect::<Vec<_>>(), ["x"]);
+            assert_eq!(context.eval("f where x = 3").unwrap(), Value::Float(7.0));
+            assert!(context.eval("f").unwrap().as_f64().is_nan());
+            context.eval("x := 4").unwrap();
+            assert_eq!(context.eval("f * 2").unwrap(), Value::Float(18.0));
+            context.environment_mut().variables.remove("x");
+            assert!(matches!(context.evaluate("x := f - 1"), Err(Error::SelfReference(name)) if name == "x"));
+        }
+
+        #[test]
+        fn test_where() {
+            let mut context = Context::new();
+            assert_eq!(context.eval("a * b where a = 2, b = a + 1").unwrap(), Value::Float(6.0));
+            assert!(!context.variables().contains_key("a"));
+            assert_eq!(context.eval("area := y^2 where y = 3").unwrap(), Value::Float(9.0));
+            assert!(matches!(context.eval("y where z = 1"), Err(Error::UnboundVariables(_))));
+        }
+    }
+}
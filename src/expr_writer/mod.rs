@@ -1,9 +1,119 @@
 pub mod expr_writer {
+    use std::fmt;
+    use std::io::{self, Write};
+    use std::str::FromStr;
+
     use nu_ansi_term::{Color, Style};
     use reedline::StyledText;
 
-    use crate::grammar::grammar::Expr;
+    use crate::format::format::NumberFormat;
+    use crate::grammar::grammar::{BinaryOperator, Expr, Radix};
+    use crate::matrix::matrix::Matrix;
+    use crate::value::value::{Value, Word};
+
+    /// Whether to colour the output.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub enum ColorChoice {
+        /// Colour the output if it is a terminal and `NO_COLOR` isn't set.
+        #[default]
+        Auto,
+        Always,
+        Never,
+    }
+
+    impl ColorChoice {
+        /// The style to write with, given whether the output is a terminal.
+        pub fn style(&self, is_terminal: bool) -> TreeStyle {
+            let colored = match self {
+                ColorChoice::Always => true,
+                ColorChoice::Never => false,
+                // See https://no-color.org: any non-empty value disables colour.
+                ColorChoice::Auto => is_terminal && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()),
+            };
+            if colored {
+                TreeStyle::default()
+            } else {
+                TreeStyle::plain()
+            }
+        }
+    }
+
+    impl fmt::Display for ColorChoice {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                ColorChoice::Auto => write!(f, "auto"),
+                ColorChoice::Always => write!(f, "always"),
+                ColorChoice::Never => write!(f, "never"),
+            }
+        }
+    }
 
+    impl FromStr for ColorChoice {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s {
+                "auto" => Ok(ColorChoice::Auto),
+                "always" => Ok(ColorChoice::Always),
+                "never" => Ok(ColorChoice::Never),
+                _ => Err(format!("invalid colour choice `{}`, expected `auto`, `always` or `never`", s)),
+            }
+        }
+    }
+
+    /// The styles used for each part of the tree, and how the numbers in it are written.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct TreeStyle {
+        pub numbers: NumberFormat,
+        /// Literals and the values of variables.
+        pub value: Style,
+        /// The result of an operation or function call.
+        pub result: Style,
+        pub operator: Style,
+        /// The name a value came from, such as a variable or `$1`.
+        pub variable: Style,
+        /// Notes such as the decimal value of a hex literal, or that a multiplication was implicit.
+        pub annotation: Style,
+        pub string: Style,
+        pub unevaluated: Style,
+        pub error: Style,
+        pub hint: Style,
+    }
+
+    impl TreeStyle {
+        /// No colours at all, for files, pipes and tests.
+        pub fn plain() -> Self {
+            TreeStyle {
+                numbers: NumberFormat::default(),
+                value: Style::new(),
+                result: Style::new(),
+                operator: Style::new(),
+                variable: Style::new(),
+                annotation: Style::new(),
+                string: Style::new(),
+                unevaluated: Style::new(),
+                error: Style::new(),
+                hint: Style::new(),
+            }
+        }
+    }
+
+    impl Default for TreeStyle {
+        fn default() -> Self {
+            TreeStyle {
+                numbers: NumberFormat::default(),
+                value: Style::new().fg(Color::Blue),
+                result: Style::new().fg(Color::Cyan),
+                operator: Style::new().fg(Color::White),
+                variable: Style::new().fg(Color::Purple),
+                annotation: Style::new().fg(Color::DarkGray),
+                string: Style::new().fg(Color::Green),
+                unevaluated: Style::new().fg(Color::DarkGray).dimmed(),
+                error: Style::new().fg(Color::Red),
+                hint: Style::new().fg(Color::Yellow),
+            }
+        }
+    }
 
     #[derive(Debug, Clone)]
     struct Column {
@@ -19,101 +129,890 @@ pub mod expr_writer {
         End,
     }
 
-    fn format_value(value: f64) -> String {
-        if value.is_nan() {
-            "???".to_string()
+    /// The columns for a child of the current node: the parent's columns carried on to the next line,
+    /// plus a new column of the given width for the child's own edge.
+    fn child_columns(columns: &[Column], width: usize, state: ColumnState) -> Vec<Column> {
+        let mut child_columns = continued_columns(columns);
+        child_columns.push(Column { width, state });
+        child_columns
+    }
+
+    /// The parent's columns carried on to the next line.
+    fn continued_columns(columns: &[Column]) -> Vec<Column> {
+        columns
+            .iter()
+            .map(|column| {
+                let updated_state = match column.state {
+                    ColumnState::Empty => ColumnState::Empty,
+                    ColumnState::Start => ColumnState::Open,
+                    ColumnState::Open => ColumnState::Open,
+                    ColumnState::End => ColumnState::Empty,
+                };
+                Column {
+                    width: column.width,
+                    state: updated_state,
+                }
+            })
+            .collect()
+    }
+
+    /// The value of a node, converted to the angle mode if it is an angle.
+    fn value_text(expr: &Expr, style: &TreeStyle) -> String {
+        match expr.value() {
+            // A formula in a variable without a value has no value itself, so it is written out instead.
+            Value::Float(value) if value.is_nan() && !(expr.free_variables().is_empty() && expr.unbound_variables().is_empty()) => match expr {
+                Expr::Derivative { derivative, .. } => derivative.to_string(),
+                Expr::Float { variable: Some(variable), .. } => variable.expr.to_string(),
+                expr => expr.to_string(),
+            },
+            // Calculated lists, such as ranges, can be long.
+            Value::List(values) => list_text(values.iter().map(|value| style.numbers.format(value)).collect()),
+            value if expr.is_angle() => style.numbers.format_angle(value.as_f64()),
+            value => style.numbers.format(&value),
+        }
+    }
+
+    /// The most elements of a list that are written out, the rest being elided in the middle.
+    const LIST_ELEMENTS: usize = 6;
+
+    /// A list on one line, eliding the middle of a long one, e.g. `[1, 2, 3, …, 9, 10] (10 items)`.
+    fn list_text(elements: Vec<String>) -> String {
+        if elements.len() <= LIST_ELEMENTS {
+            format!("[{}]", elements.join(", "))
         } else {
-            format!("{}", value)
+            let (head, tail) = (LIST_ELEMENTS / 2, LIST_ELEMENTS / 2 - 1);
+            format!(
+                "[{}, …, {}] ({} items)",
+                elements[..head].join(", "),
+                elements[elements.len() - tail..].join(", "),
+                elements.len()
+            )
         }
     }
 
-    fn do_write_expr_tree(expr: &Expr, columns: Vec<Column>, active_column: usize) {
+    /// The most rows or columns of a matrix that are drawn as a grid, rather than on one line as a list.
+    const GRID_SIZE: usize = 10;
+
+    /// The rows of a matrix value with the columns aligned, or `None` if the value isn't a matrix that
+    /// is worth drawing as a grid.
+    fn grid_lines(expr: &Expr, style: &TreeStyle) -> Option<Vec<String>> {
+        let value = expr.value();
+        let matrix = Matrix::from_value(&value)?;
+        if matrix.rows() < 2 || matrix.rows() > GRID_SIZE || matrix.columns() > GRID_SIZE {
+            return None;
+        }
+        let Value::List(rows) = value else {
+            return None;
+        };
+        let cells = rows
+            .iter()
+            .map(|row| match row {
+                Value::List(values) => values.iter().map(|value| style.numbers.format(value)).collect::<Vec<_>>(),
+                _ => unreachable!("A matrix is a list of rows"),
+            })
+            .collect::<Vec<_>>();
+        let widths = (0..matrix.columns())
+            .map(|column| cells.iter().map(|row| text_width(&row[column])).max().unwrap_or(0))
+            .collect::<Vec<_>>();
+        let last = cells.len() - 1;
+        let lines = cells
+            .iter()
+            .enumerate()
+            .map(|(index, row)| {
+                let (open, close) = match index {
+                    0 => ('⎡', '⎤'),
+                    _ if index == last => ('⎣', '⎦'),
+                    _ => ('⎢', '⎥'),
+                };
+                let row = row.iter().zip(&widths).map(|(cell, width)| format!("{:>width$}", cell, width = width));
+                format!("{}{}{}", open, row.collect::<Vec<_>>().join(" "), close)
+            })
+            .collect();
+        Some(lines)
+    }
+
+    /// The label of a node, which takes more than one line when its value is a matrix. The rest of
+    /// the label follows the first row of the grid.
+    fn label_lines(expr: &Expr, style: &TreeStyle) -> Vec<StyledText> {
+        let mut first = label(expr, style);
+        let Some(grid) = grid_lines(expr, style) else {
+            return vec![first];
+        };
+        let value_style = first.buffer[0].0;
+        first.buffer[0].1 = grid[0].clone();
+        let mut lines = vec![first];
+        lines.extend(grid[1..].iter().map(|row| {
+            let mut line = StyledText::new();
+            line.push((value_style, row.clone()));
+            line
+        }));
+        lines
+    }
+
+    /// The text for a single node: the value of a leaf, or the result and operator of an operation.
+    fn label(expr: &Expr, style: &TreeStyle) -> StyledText {
+        let mut styled_text = StyledText::new();
+        let variable = match expr {
+            Expr::Integer { value, radix, variable } => {
+                // Literals keep the radix they were written in, with the value in the output radix alongside.
+                let formatted = style.numbers.format_integer(*value);
+                if *radix != Radix::Decimal && radix.format(*value) != formatted {
+                    styled_text.push((style.value, radix.format(*value)));
+                    styled_text.push((style.annotation, format!(" ({})", formatted)));
+                } else {
+                    styled_text.push((style.value, formatted));
+                }
+                variable
+            }
+            Expr::Word { value, radix, variable } => {
+                let formatted = style.numbers.format_word(value);
+                if *radix != Radix::Decimal && radix.format_word(value) != formatted {
+                    styled_text.push((style.value, radix.format_word(value)));
+                    styled_text.push((style.annotation, format!(" ({})", formatted)));
+                } else {
+                    styled_text.push((style.value, formatted));
+                }
+                variable
+            }
+            Expr::Float { variable, .. } => {
+                styled_text.push((style.value, value_text(expr, style)));
+                variable
+            }
+            Expr::Angle { amount, unit, value } => {
+                // Like radix literals, angles keep the unit they were written in, converted alongside.
+                styled_text.push((style.value, format!("{}{}", style.numbers.format_float(*amount), unit)));
+                if *unit != style.numbers.angle {
+                    styled_text.push((style.annotation, format!(" ({})", style.numbers.format_angle(*value))));
+                }
+                &None
+            }
+            Expr::Boolean { value, variable } => {
+                styled_text.push((style.value, value.to_string()));
+                variable
+            }
+            Expr::String { value, variable } => {
+                styled_text.push((style.string, format!("\"{}\"", value)));
+                variable
+            }
+            Expr::List { elements, variable } => {
+                styled_text.push((style.value, list_text(elements.iter().map(|element| value_text(element, style)).collect())));
+                variable
+            }
+            Expr::Unevaluated { source } => {
+                styled_text.push((style.unevaluated, format!("{} (not evaluated)", source)));
+                &None
+            }
+            Expr::UnboundVariable { name, suggestion } => {
+                styled_text.push((style.error, format!("{} <- unbound variable", name)));
+                if let Some(suggestion) = suggestion {
+                    styled_text.push((style.hint, format!(", did you mean `{}`?", suggestion)));
+                }
+                &None
+            }
+            Expr::BinaryOperation { op, .. } => {
+                styled_text.push((style.result, value_text(expr, style)));
+                styled_text.push((style.operator, " = ".to_string()));
+                styled_text.push((style.operator, format!("{}", op)));
+                if let Some(note) = trailing_note(expr, style) {
+                    styled_text.push((style.annotation, note));
+                }
+                &None
+            }
+            Expr::UnaryOperation { op, .. } => {
+                styled_text.push((style.result, value_text(expr, style)));
+                styled_text.push((style.operator, " = ".to_string()));
+                styled_text.push((style.operator, format!("{}", op)));
+                &None
+            }
+            Expr::Conditional { .. } => {
+                styled_text.push((style.result, value_text(expr, style)));
+                styled_text.push((style.operator, " = ".to_string()));
+                styled_text.push((style.operator, "if".to_string()));
+                &None
+            }
+            Expr::Range { .. } => {
+                styled_text.push((style.result, value_text(expr, style)));
+                styled_text.push((style.operator, " = ".to_string()));
+                styled_text.push((style.operator, "..".to_string()));
+                &None
+            }
+            Expr::FreeVariable { name } => {
+                styled_text.push((style.variable, name.clone()));
+                styled_text.push((style.annotation, " (free)".to_string()));
+                &None
+            }
+            Expr::Derivative { variable, .. } => {
+                styled_text.push((style.result, value_text(expr, style)));
+                styled_text.push((style.operator, " = ".to_string()));
+                styled_text.push((style.operator, format!("d/d{}", variable)));
+                if let Some(note) = trailing_note(expr, style) {
+                    styled_text.push((style.annotation, note));
+                }
+                &None
+            }
+            Expr::Solution { variable, .. } => {
+                styled_text.push((style.result, value_text(expr, style)));
+                styled_text.push((style.operator, " = ".to_string()));
+                styled_text.push((style.operator, format!("solve for {}", variable)));
+                if let Some(note) = trailing_note(expr, style) {
+                    styled_text.push((style.annotation, note));
+                }
+                &None
+            }
+            Expr::BoundOperation {
+                operator,
+                variable,
+                lower,
+                upper,
+                ..
+            } => {
+                styled_text.push((style.result, value_text(expr, style)));
+                styled_text.push((style.operator, " = ".to_string()));
+                styled_text.push((style.operator, format!("{} {} = {}..{}", operator, variable, value_text(lower, style), value_text(upper, style))));
+                if let Some(note) = trailing_note(expr, style) {
+                    styled_text.push((style.annotation, note));
+                }
+                &None
+            }
+            Expr::FunctionCall { name, .. } => {
+                styled_text.push((style.result, value_text(expr, style)));
+                styled_text.push((style.operator, " = ".to_string()));
+                styled_text.push((style.operator, format!("{}()", name)));
+                if let Some(note) = trailing_note(expr, style) {
+                    styled_text.push((style.annotation, note));
+                }
+                &None
+            }
+            Expr::Assignment { .. } => unreachable!("Unexpected expression: {:?}", expr),
+        };
+        if let Some(variable) = variable {
+            styled_text.push((style.variable, format!(" ({})", variable.name)));
+        }
+        styled_text
+    }
+
+    /// A note at the end of the label of an operation, which its operands are not lined up under.
+    fn trailing_note(expr: &Expr, style: &TreeStyle) -> Option<String> {
+        match expr {
+            Expr::BinaryOperation {
+                op: BinaryOperator::ImplicitMultiply,
+                ..
+            } => Some(" (implicit)".to_string()),
+            // The angle mode the argument was read in, as mixing up radians and degrees is easy to do.
+            Expr::FunctionCall { angle: Some(angle), .. } => Some(format!(" ({})", angle)),
+            Expr::Derivative { variable, at: Some(at), .. } => Some(format!(" (at {} = {})", variable, value_text(at, style))),
+            Expr::Solution {
+                method,
+                iterations,
+                residual,
+                ..
+            } => {
+                // How the solution was found, and how far it is from exact, since numeric methods can be fooled.
+                let iterations = match iterations {
+                    0 => String::new(),
+                    1 => ", 1 iteration".to_string(),
+                    iterations => format!(", {} iterations", iterations),
+                };
+                Some(format!(" ({}{}, residual {})", method, iterations, style.numbers.format_float(*residual)))
+            }
+            // Where the body below is evaluated, which is the only value of the variable that is shown.
+            Expr::BoundOperation {
+                variable,
+                at,
+                error,
+                evaluations,
+                ..
+            } if !at.is_nan() => {
+                let counted = match (error, evaluations) {
+                    (Some(error), _) => format!("error {}, {} evaluations", style.numbers.format_float(*error), evaluations),
+                    (None, 1) => "1 term".to_string(),
+                    (None, terms) => format!("{} terms", terms),
+                };
+                Some(format!(" ({}, shown at {} = {})", counted, variable, style.numbers.format_float(*at)))
+            }
+            _ => None,
+        }
+    }
+
+    /// The operands of an operation, in the order they are drawn.
+    fn children(expr: &Expr) -> Vec<&Expr> {
+        match expr {
+            Expr::BinaryOperation { lhs, rhs, .. } => vec![lhs, rhs],
+            Expr::UnaryOperation { expr, .. } => vec![expr],
+            Expr::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => vec![condition, then_branch, else_branch],
+            Expr::Range { start, end, step, .. } => [Some(start), Some(end), step.as_ref()].into_iter().flatten().map(|expr| &**expr).collect(),
+            Expr::FunctionCall { arguments, .. } => arguments.iter().collect(),
+            Expr::Derivative { derivative, .. } => vec![derivative],
+            Expr::Solution { lhs, rhs, .. } => vec![lhs, rhs],
+            Expr::BoundOperation { body, .. } => vec![body],
+            // A list of plain numbers is a single node, and only gets children if an element was calculated.
+            Expr::List { elements, .. } if elements.iter().any(|element| !children(element).is_empty()) => {
+                elements.iter().collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    /// The number of levels in the tree.
+    fn depth(expr: &Expr) -> usize {
+        1 + children(expr).into_iter().map(depth).max().unwrap_or(0)
+    }
+
+    fn text_width(text: &str) -> usize {
+        text.chars().count()
+    }
+
+    /// The number of levels below this one that may still be drawn, or `None` if there is no limit.
+    fn remaining_depth(max_depth: Option<usize>) -> Option<usize> {
+        max_depth.map(|depth| depth.max(1) - 1)
+    }
+
+    fn do_write_expr_tree(out: &mut dyn Write, expr: &Expr, style: &TreeStyle, columns: Vec<Column>, remaining: Option<usize>) -> io::Result<()> {
         const EDGE: &str = "└─";
         const PIPE: &str = "│ ";
         const BRANCH: &str = "├─";
 
         // Draw all of the columns before this one, for this line, making sure that the
         // width of the column is honoured.
-        for column in columns.iter() {
-            match column.state {
-                ColumnState::Empty => print!("{:>width$} ", "", width = column.width),
-                ColumnState::Start => print!("{:>width$} ", BRANCH, width = column.width),
-                ColumnState::Open => print!("{:>width$} ", PIPE, width = column.width),
-                ColumnState::End => print!("{:>width$} ", EDGE, width = column.width),
+        let write_columns = |out: &mut dyn Write, columns: &[Column]| {
+            for column in columns.iter() {
+                match column.state {
+                    ColumnState::Empty => write!(out, "{:>width$} ", "", width = column.width)?,
+                    ColumnState::Start => write!(out, "{:>width$} ", BRANCH, width = column.width)?,
+                    ColumnState::Open => write!(out, "{:>width$} ", PIPE, width = column.width)?,
+                    ColumnState::End => write!(out, "{:>width$} ", EDGE, width = column.width)?,
+                }
+            }
+            Ok::<_, io::Error>(())
+        };
+        write_columns(out, &columns)?;
+
+        let mut lines = label_lines(expr, style);
+        let children = children(expr);
+        let collapsed = remaining == Some(0) && !children.is_empty();
+        if collapsed {
+            lines[0].push((style.annotation, " …".to_string()));
+        }
+        writeln!(out, "{}", lines[0].render_simple())?;
+        // Leave room beside the rest of a grid for the edge to the operands, which goes under the operator
+        // rather than after a note such as ` (implicit)`.
+        let note_width = trailing_note(expr, style).map_or(0, |note| text_width(&note));
+        let width = lines
+            .iter()
+            .enumerate()
+            .map(|(index, line)| if index == 0 { text_width(&line.raw_string()) - note_width + 1 } else { text_width(&line.raw_string()) + 3 })
+            .max()
+            .unwrap_or(0);
+
+        // The rest of a grid goes under the first line, with the edge to the operands running beside it.
+        for line in &lines[1..] {
+            write_columns(out, &continued_columns(&columns))?;
+            let mut line = line.clone();
+            if !children.is_empty() && !collapsed {
+                line = padded(line, width - 2);
+                line.push((Style::new(), PIPE.trim_end().to_string()));
             }
+            writeln!(out, "{}", line.render_simple())?;
+        }
+        if collapsed {
+            return Ok(());
         }
 
-        match expr {
-            Expr::Integer { value, variable } => {
-                let mut styled_text = StyledText::new();
-                styled_text.push((Style::new().fg(Color::Blue), value.to_string()));
-                if let Some(variable) = variable {
-                    styled_text.push((Style::new().fg(Color::Purple), format!(" ({})", variable.name)));
-                }
+        for (index, child) in children.iter().enumerate() {
+            let state = if index + 1 == children.len() {
+                ColumnState::End
+            } else {
+                ColumnState::Start
+            };
+            do_write_expr_tree(out, child, style, child_columns(&columns, width, state), remaining.map(|depth| depth - 1))?;
+        }
+        Ok(())
+    }
+
+    pub fn write_expr_tree(out: &mut dyn Write, expr: &Expr, style: &TreeStyle) -> io::Result<()> {
+        let columns: Vec<Column> = vec![];
+        do_write_expr_tree(out, expr, style, columns, None)
+    }
 
-                println!("{}", styled_text.render_simple());
+    /// How an expression is laid out.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub enum Layout {
+        /// The tree if it fits in the width, otherwise the inline layout, otherwise the tree with as
+        /// many levels collapsed as it takes to fit.
+        #[default]
+        Auto,
+        /// One line per node, with each operand indented under its operator.
+        Tree,
+        /// Each operator centred above its operands.
+        TopDown,
+        /// A single line, such as `(1 + 4 = 5) * 3 = 15`.
+        Inline,
+    }
+
+    impl fmt::Display for Layout {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                Layout::Auto => write!(f, "auto"),
+                Layout::Tree => write!(f, "tree"),
+                Layout::TopDown => write!(f, "top-down"),
+                Layout::Inline => write!(f, "inline"),
             }
-            Expr::Float { value, variable } => {
+        }
+    }
+
+    impl FromStr for Layout {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s {
+                "auto" => Ok(Layout::Auto),
+                "tree" => Ok(Layout::Tree),
+                "top-down" => Ok(Layout::TopDown),
+                "inline" => Ok(Layout::Inline),
+                _ => Err(format!("invalid layout `{}`, expected `auto`, `tree`, `top-down` or `inline`", s)),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub struct LayoutOptions {
+        pub layout: Layout,
+        /// Operations below this many levels are collapsed into `…`.
+        pub max_depth: Option<usize>,
+        /// The width of the terminal, which the auto layout tries to fit in.
+        pub width: Option<usize>,
+    }
+
+    /// Writes the expression in the given layout.
+    pub fn write_expr(out: &mut dyn Write, expr: &Expr, style: &TreeStyle, options: &LayoutOptions) -> io::Result<()> {
+        let remaining = remaining_depth(options.max_depth);
+        match options.layout {
+            Layout::Tree => do_write_expr_tree(out, expr, style, vec![], remaining),
+            Layout::TopDown => {
+                for line in top_down_block(expr, style, remaining).lines {
+                    writeln!(out, "{}", line.render_simple().trim_end())?;
+                }
+                Ok(())
+            }
+            Layout::Inline => {
                 let mut styled_text = StyledText::new();
-                styled_text.push((Style::new().fg(Color::Blue), format_value(*value)));
+                write_inline(&mut styled_text, expr, style, remaining, true);
+                writeln!(out, "{}", styled_text.render_simple())
+            }
+            Layout::Auto => {
+                let fits = |options: &LayoutOptions| {
+                    let mut plain = Vec::new();
+                    write_expr(&mut plain, expr, &TreeStyle::plain(), options)?;
+                    let width = options.width.unwrap_or(usize::MAX);
+                    Ok::<_, io::Error>(String::from_utf8_lossy(&plain).lines().all(|line| text_width(line) <= width))
+                };
+
+                let levels = options.max_depth.unwrap_or(usize::MAX).min(depth(expr));
+                let mut candidates = vec![
+                    LayoutOptions {
+                        layout: Layout::Tree,
+                        max_depth: Some(levels),
+                        ..*options
+                    },
+                    LayoutOptions {
+                        layout: Layout::Inline,
+                        max_depth: Some(levels),
+                        ..*options
+                    },
+                ];
+                candidates.extend((1..levels).rev().map(|levels| LayoutOptions {
+                    layout: Layout::Tree,
+                    max_depth: Some(levels),
+                    ..*options
+                }));
 
-                if let Some(variable) = variable {
-                    styled_text.push((Style::new().fg(Color::Purple), format!(" ({})", variable.name)));
+                for candidate in &candidates {
+                    if fits(candidate)? {
+                        return write_expr(out, expr, style, candidate);
+                    }
                 }
+                write_expr(out, expr, style, candidates.last().unwrap())
+            }
+        }
+    }
 
-                println!("{}", styled_text.render_simple());
+    /// Appends the expression as a single line, with every operation followed by its result and wrapped
+    /// in brackets unless it is the outermost one.
+    fn write_inline(styled_text: &mut StyledText, expr: &Expr, style: &TreeStyle, remaining: Option<usize>, outermost: bool) {
+        let children = children(expr);
+        if children.is_empty() {
+            styled_text.buffer.extend(label(expr, style).buffer);
+            return;
+        }
+
+        let nested = remaining.map(|depth| depth.saturating_sub(1));
+        let push = |styled_text: &mut StyledText, text: &str| styled_text.push((style.operator, text.to_string()));
+        // A list is already bracketed, and its value is the elements themselves.
+        if let Expr::List { elements, .. } = expr {
+            push(styled_text, "[");
+            for (index, element) in elements.iter().enumerate() {
+                if index > 0 {
+                    push(styled_text, ", ");
+                }
+                write_inline(styled_text, element, style, nested, true);
             }
-            Expr::UnboundVariable { name } => {
-                let mut styled_text = StyledText::new();
-                styled_text.push((Style::new().fg(Color::Red), format!("{} <- unbound variable", name.to_string())));
-                println!("{}", styled_text.render_simple());
+            push(styled_text, "]");
+            return;
+        }
+        if !outermost {
+            push(styled_text, "(");
+        }
+        match expr {
+            _ if remaining == Some(0) => styled_text.push((style.annotation, "…".to_string())),
+            Expr::BinaryOperation { lhs, op, rhs, .. } => {
+                write_inline(styled_text, lhs, style, nested, false);
+                push(styled_text, &format!(" {} ", op));
+                write_inline(styled_text, rhs, style, nested, false);
             }
-            Expr::BinaryOperation { lhs, op, rhs, value } => {
-                let mut styled_text = StyledText::new();
-                styled_text.push((Style::new().fg(Color::Cyan), format_value(*value)));
+            Expr::UnaryOperation { op, expr, .. } => {
+                push(styled_text, &op.to_string());
+                write_inline(styled_text, expr, style, nested, false);
+            }
+            Expr::Conditional {
+                condition,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                push(styled_text, "if ");
+                write_inline(styled_text, condition, style, nested, false);
+                push(styled_text, " then ");
+                write_inline(styled_text, then_branch, style, nested, false);
+                push(styled_text, " else ");
+                write_inline(styled_text, else_branch, style, nested, false);
+            }
+            Expr::Range { start, end, step, .. } => {
+                write_inline(styled_text, start, style, nested, false);
+                push(styled_text, "..");
+                write_inline(styled_text, end, style, nested, false);
+                if let Some(step) = step {
+                    push(styled_text, " step ");
+                    write_inline(styled_text, step, style, nested, false);
+                }
+            }
+            Expr::FunctionCall { name, arguments, .. } => {
+                push(styled_text, &format!("{}(", name));
+                for (index, argument) in arguments.iter().enumerate() {
+                    if index > 0 {
+                        push(styled_text, ", ");
+                    }
+                    // The argument list already separates the arguments, so they don't need brackets.
+                    write_inline(styled_text, argument, style, nested, true);
+                }
+                push(styled_text, ")");
+            }
+            Expr::Derivative { variable, derivative, .. } => {
+                push(styled_text, &format!("d/d{} ", variable));
+                write_inline(styled_text, derivative, style, nested, false);
+            }
+            Expr::Solution { variable, lhs, rhs, .. } => {
+                push(styled_text, &format!("solve for {}: ", variable));
+                write_inline(styled_text, lhs, style, nested, true);
+                push(styled_text, " = ");
+                write_inline(styled_text, rhs, style, nested, true);
+            }
+            Expr::BoundOperation {
+                operator,
+                body,
+                variable,
+                lower,
+                upper,
+                ..
+            } => {
+                push(styled_text, &format!("{} {} = {}..{}: ", operator, variable, value_text(lower, style), value_text(upper, style)));
+                write_inline(styled_text, body, style, nested, true);
+            }
+            _ => unreachable!("Only operations have children"),
+        }
+        push(styled_text, " = ");
+        styled_text.push((style.result, value_text(expr, style)));
+        if !outermost {
+            push(styled_text, ")");
+        }
+    }
+
+    /// A rectangle of text for the top-down layout, along with the column its root is centred on.
+    struct Block {
+        lines: Vec<StyledText>,
+        width: usize,
+        center: usize,
+    }
+
+    fn padded(mut line: StyledText, width: usize) -> StyledText {
+        let padding = width.saturating_sub(text_width(&line.raw_string()));
+        line.push((Style::new(), " ".repeat(padding)));
+        line
+    }
 
-                styled_text.push((Style::new().fg(Color::White), " = ".to_string()));
-                styled_text.push((Style::new().fg(Color::White), format!("{}", op)));
+    fn top_down_block(expr: &Expr, style: &TreeStyle, remaining: Option<usize>) -> Block {
+        const GAP: usize = 2;
 
-                println!("{}", styled_text.render_simple());
-                let updated_columns = columns
-                    .iter()
-                    .map(|column| {
-                        let updated_state = match column.state {
-                            ColumnState::Empty => ColumnState::Empty,
-                            ColumnState::Start => ColumnState::Open,
-                            ColumnState::Open => ColumnState::Open,
-                            ColumnState::End => ColumnState::Empty,
-                        };
-                        Column {
-                            width: column.width,
-                            state: updated_state,
-                        }
-                    })
-                    .collect::<Vec<_>>();
+        let mut labels = label_lines(expr, style);
+        let children = children(expr);
+        let collapsed = remaining == Some(0) && !children.is_empty();
+        if collapsed {
+            labels[0].push((style.annotation, " …".to_string()));
+        }
+        let label_width = labels.iter().map(|line| text_width(&line.raw_string())).max().unwrap_or(0);
+        if children.is_empty() || collapsed {
+            return Block {
+                lines: labels.into_iter().map(|line| padded(line, label_width)).collect(),
+                width: label_width,
+                center: label_width / 2,
+            };
+        }
+
+        let blocks = children
+            .into_iter()
+            .map(|child| top_down_block(child, style, remaining.map(|depth| depth - 1)))
+            .collect::<Vec<_>>();
+        let mut offsets = vec![];
+        let mut children_width = 0;
+        for block in &blocks {
+            offsets.push(children_width);
+            children_width += block.width + GAP;
+        }
+        children_width -= GAP;
 
-                let mut left_columns = updated_columns.clone();
-                left_columns.push(Column {
-                    width: styled_text.raw_string().len() + 1,
-                    state: ColumnState::Start,
-                });
+        let centers = blocks.iter().zip(&offsets).map(|(block, offset)| offset + block.center).collect::<Vec<_>>();
+        let (first, last) = (centers[0], centers[centers.len() - 1]);
+        // Shift the operands right if the label would stick out past their left edge.
+        let shift = (label_width / 2).saturating_sub((first + last) / 2);
+        let center = (first + last) / 2 + shift;
+        let label_start = center - label_width / 2;
+        let width = (children_width + shift).max(label_start + label_width);
 
-                let mut right_columns = updated_columns.clone();
-                right_columns.push(Column {
-                    width: styled_text.raw_string().len() + 1,
-                    state: ColumnState::End,
-                });
+        let mut lines = vec![];
+        for label in labels {
+            let mut label_line = StyledText::new();
+            label_line.push((Style::new(), " ".repeat(label_start)));
+            label_line.buffer.extend(label.buffer);
+            lines.push(padded(label_line, width));
+        }
 
-                do_write_expr_tree(lhs.as_ref(), left_columns, active_column + 1);
+        let connector = (0..width)
+            .map(|column| {
+                let is_child = centers.iter().any(|child| child + shift == column);
+                match column {
+                    _ if centers.len() == 1 && column == center => '│',
+                    _ if centers.len() == 1 => ' ',
+                    _ if column == first + shift => '┌',
+                    _ if column == last + shift => '┐',
+                    _ if column == center => if is_child { '┼' } else { '┴' },
+                    _ if is_child => '┬',
+                    _ if column > first + shift && column < last + shift => '─',
+                    _ => ' ',
+                }
+            })
+            .collect::<String>();
+        let mut connector_line = StyledText::new();
+        connector_line.push((style.operator, connector));
+        lines.push(connector_line);
 
-                do_write_expr_tree(rhs.as_ref(), right_columns, active_column + 1);
+        let height = blocks.iter().map(|block| block.lines.len()).max().unwrap_or(0);
+        for row in 0..height {
+            let mut line = StyledText::new();
+            line.push((Style::new(), " ".repeat(shift)));
+            for (index, block) in blocks.iter().enumerate() {
+                if index > 0 {
+                    line.push((Style::new(), " ".repeat(GAP)));
+                }
+                let text = block.lines.get(row).cloned().unwrap_or_else(StyledText::new);
+                line.buffer.extend(padded(text, block.width).buffer);
             }
-            _ => unreachable!("Unexpected expression: {:?}", expr)
+            lines.push(padded(line, width));
         }
+
+        Block { lines, width, center }
     }
 
-    pub fn write_expr_tree(expr: Expr) {
-        let columns: Vec<Column> = vec![];
-        do_write_expr_tree(&expr, columns, 0);
+    /// Splits the digits into groups of the given size, counting from the right.
+    fn group_digits(digits: &str, size: usize, separator: &str) -> String {
+        let chars = digits.chars().collect::<Vec<_>>();
+        let first = match chars.len() % size {
+            0 => size,
+            n => n,
+        };
+        let mut groups = vec![chars[..first.min(chars.len())].iter().collect::<String>()];
+        groups.extend(chars[first.min(chars.len())..].chunks(size).map(|chunk| chunk.iter().collect::<String>()));
+        groups.join(separator)
+    }
+
+    /// Writes the value in hex, decimal, octal and binary, as its two's complement bit pattern in the word.
+    pub fn write_programmer_view(out: &mut dyn Write, word: Word, style: &TreeStyle) -> io::Result<()> {
+        let (bits, value, word) = (word.bits, word, word.size);
+
+        let hex = group_digits(&format!("{:0width$X}", bits, width = (word.bits as usize).div_ceil(4)), 4, "_");
+        let octal = format!("{:o}", bits);
+        let binary = group_digits(&format!("{:0width$b}", bits, width = word.bits as usize), 4, " ");
+
+        for (name, text) in [
+            ("hex", format!("0x{}", hex)),
+            ("dec", value.to_string()),
+            ("oct", format!("0o{}", octal)),
+            ("bin", binary),
+        ] {
+            let mut styled_text = StyledText::new();
+            styled_text.push((style.annotation, format!("{} ", name)));
+            styled_text.push((style.value, text));
+            writeln!(out, "{}", styled_text.render_simple())?;
+        }
+        Ok(())
+    }
+
+    #[cfg(test)]
+    mod tests {
+
+        use super::*;
+        use crate::context::context::Context;
+        use crate::value::value::Value;
+
+        fn render(input: &str, style: &TreeStyle) -> String {
+            let mut context = Context::new();
+            context.set_variable("x", Value::Integer(4));
+            let evaluation = context.evaluate(input).unwrap();
+            let mut out = Vec::new();
+            write_expr_tree(&mut out, &evaluation.expr, style).unwrap();
+            String::from_utf8(out).unwrap()
+        }
+
+        #[test]
+        fn test_tree_layout() {
+            assert_eq!(
+                render("1 + 2x * 3", &TreeStyle::plain()),
+                "\
+25 = +
+     ├─ 1
+     └─ 24 = *
+             ├─ 8 = * (implicit)
+             │      ├─ 2
+             │      └─ 4 (x)
+             └─ 3
+"
+            );
+            assert_eq!(
+                render("if 0xF > 2 then max(1, 3) else y", &TreeStyle::plain()),
+                "\
+3 = if
+     ├─ true = >
+     │         ├─ 0xF (15)
+     │         └─ 2
+     ├─ 3 = max()
+     │          ├─ 1
+     │          └─ 3
+     └─ y (not evaluated)
+"
+            );
+        }
+
+        #[test]
+        fn test_matrix_grid() {
+            assert_eq!(
+                render("[[1, 2], [3, 4]] @ [[-1, 0], [0, 10]]", &TreeStyle::plain()),
+                "\
+⎡-1 20⎤ = @
+⎣-3 40⎦   │
+          ├─ ⎡1 2⎤
+          │  ⎣3 4⎦
+          └─ ⎡-1  0⎤
+             ⎣ 0 10⎦ │
+                     ├─ [-1, 0]
+                     │        ├─ -1 = -
+                     │        │       └─ 1
+                     │        └─ 0
+                     └─ [0, 10]
+"
+            );
+            // A single row stays on one line.
+            assert_eq!(render("[[1, 2]]", &TreeStyle::plain()), "[[1, 2]]\n");
+        }
+
+        fn render_with(input: &str, options: LayoutOptions) -> String {
+            let mut context = Context::new();
+            let evaluation = context.evaluate(input).unwrap();
+            let mut out = Vec::new();
+            write_expr(&mut out, &evaluation.expr, &TreeStyle::plain(), &options).unwrap();
+            String::from_utf8(out).unwrap()
+        }
+
+        #[test]
+        fn test_layouts() {
+            let layout = |layout| LayoutOptions {
+                layout,
+                ..Default::default()
+            };
+            // Starts with a newline so that the indentation of the first line lines up with the rest.
+            let top_down = "
+      13 = -
+    ┌────┴─────┐
+ 15 = *   2 = sqrt()
+  ┌─┴──┐       │
+5 = +  3       4
+ ┌┴─┐
+ 1  4
+";
+            assert_eq!(render_with("(1 + 4) * 3 - sqrt(4)", layout(Layout::TopDown)), top_down[1..]);
+            assert_eq!(
+                render_with("(1 + 4) * 3 - sqrt(4)", layout(Layout::Inline)),
+                "((1 + 4 = 5) * 3 = 15) - (sqrt(4) = 2) = 13\n"
+            );
+        }
+
+        #[test]
+        fn test_depth_limit() {
+            let collapsed = |layout| LayoutOptions {
+                layout,
+                max_depth: Some(2),
+                width: None,
+            };
+            assert_eq!(
+                render_with("(1 + 4) * 3 - 2", collapsed(Layout::Tree)),
+                "\
+13 = -
+     ├─ 15 = * …
+     └─ 2
+"
+            );
+            assert_eq!(render_with("(1 + 4) * 3 - 2", collapsed(Layout::Inline)), "(… = 15) - 2 = 13\n");
+        }
+
+        #[test]
+        fn test_auto_layout_fits_width() {
+            let width = |width| LayoutOptions {
+                width: Some(width),
+                ..Default::default()
+            };
+            let input = "sqrt(sqrt(sqrt(sqrt(16))))";
+            assert_eq!(render_with(input, width(100)), render_with(input, LayoutOptions::default()));
+            assert_eq!(
+                render_with(input, width(75)),
+                "sqrt(sqrt(sqrt(sqrt(16) = 4) = 2) = 1.4142135623731) = 1.18920711500272\n"
+            );
+            assert_eq!(
+                render_with(input, width(60)),
+                "\
+1.18920711500272 = sqrt()
+                        └─ 1.4142135623731 = sqrt() …
+"
+            );
+        }
+
+        #[test]
+        fn test_color_choice() {
+            assert!(!render("1 + 2", &ColorChoice::Never.style(true)).contains('\x1b'));
+            assert!(!render("1 + 2", &ColorChoice::Auto.style(false)).contains('\x1b'));
+            assert!(render("1 + 2", &ColorChoice::Always.style(false)).contains('\x1b'));
+            assert_eq!("never".parse::<ColorChoice>(), Ok(ColorChoice::Never));
+        }
     }
 }
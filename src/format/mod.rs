@@ -0,0 +1,330 @@
+pub mod format {
+    use std::fmt;
+    use std::str::FromStr;
+
+    use crate::grammar::grammar::{AngleUnit, Radix};
+    use crate::value::value::{Value, Word};
+
+    /// How many digits of a float are shown.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Precision {
+        /// At most this many significant digits, without trailing zeros.
+        Significant(usize),
+        /// Exactly this many digits after the decimal point.
+        Decimals(usize),
+    }
+
+    impl Precision {
+        /// Enough to hide the rounding error in results like `0.1 + 0.2`, which `f64` can't hold exactly.
+        pub const DEFAULT: Precision = Precision::Significant(15);
+    }
+
+    /// How the magnitude of a number is shown.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub enum Notation {
+        /// Positional, except for very large or very small numbers, which use scientific notation.
+        #[default]
+        Auto,
+        /// Always positional, e.g. `1200000`.
+        Fixed,
+        /// `1.2e6`.
+        Scientific,
+        /// Scientific notation with an exponent that is a multiple of three, e.g. `1.2e6`, `12e6`, `120e6`.
+        Engineering,
+        /// Engineering notation written with an SI prefix, e.g. `1.2M`.
+        Si,
+    }
+
+    impl fmt::Display for Notation {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                Notation::Auto => write!(f, "auto"),
+                Notation::Fixed => write!(f, "fixed"),
+                Notation::Scientific => write!(f, "scientific"),
+                Notation::Engineering => write!(f, "engineering"),
+                Notation::Si => write!(f, "si"),
+            }
+        }
+    }
+
+    impl FromStr for Notation {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s {
+                "auto" => Ok(Notation::Auto),
+                "fixed" => Ok(Notation::Fixed),
+                "scientific" => Ok(Notation::Scientific),
+                "engineering" => Ok(Notation::Engineering),
+                "si" => Ok(Notation::Si),
+                _ => Err(format!(
+                    "invalid notation `{}`, expected `auto`, `fixed`, `scientific`, `engineering` or `si`",
+                    s
+                )),
+            }
+        }
+    }
+
+    const SI_PREFIXES: &[(i32, &str)] = &[
+        (-24, "y"),
+        (-21, "z"),
+        (-18, "a"),
+        (-15, "f"),
+        (-12, "p"),
+        (-9, "n"),
+        (-6, "µ"),
+        (-3, "m"),
+        (0, ""),
+        (3, "k"),
+        (6, "M"),
+        (9, "G"),
+        (12, "T"),
+        (15, "P"),
+        (18, "E"),
+        (21, "Z"),
+        (24, "Y"),
+    ];
+
+    /// How numbers are written in the tree and the history.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct NumberFormat {
+        pub precision: Precision,
+        pub notation: Notation,
+        /// Group the digits of the integer part, as in `1,000,000` or `0xFFFF_FFFF`.
+        pub separators: bool,
+        /// The base integers are written in. Numbers with a fractional part are always decimal.
+        pub radix: Radix,
+        /// The unit angles are shown in.
+        pub angle: AngleUnit,
+    }
+
+    impl Default for NumberFormat {
+        fn default() -> Self {
+            NumberFormat {
+                precision: Precision::DEFAULT,
+                notation: Notation::Auto,
+                separators: false,
+                radix: Radix::Decimal,
+                angle: AngleUnit::Radians,
+            }
+        }
+    }
+
+    impl NumberFormat {
+        pub fn format(&self, value: &Value) -> String {
+            match value {
+                Value::Integer(value) => self.format_integer(*value),
+                Value::Word(word) => self.format_word(word),
+                Value::Float(value) => self.format_float(*value),
+                Value::Bool(_) | Value::String(_) => value.to_string(),
+                Value::List(values) => {
+                    let values = values.iter().map(|value| self.format(value)).collect::<Vec<_>>();
+                    format!("[{}]", values.join(", "))
+                }
+            }
+        }
+
+        /// Writes an angle held in radians in the angle unit, followed by the unit, e.g. `30deg`.
+        pub fn format_angle(&self, radians: f64) -> String {
+            format!("{}{}", self.format_float(self.angle.from_radians(radians)), self.angle)
+        }
+
+        pub fn format_integer(&self, value: i128) -> String {
+            self.format_magnitude(value < 0, value.unsigned_abs())
+        }
+
+        /// Formats the value of a word, which only differs from an integer for the upper half of a `u128`.
+        pub fn format_word(&self, word: &Word) -> String {
+            match word.to_i128() {
+                Some(value) => self.format_integer(value),
+                None => self.format_magnitude(false, word.bits),
+            }
+        }
+
+        fn format_magnitude(&self, negative: bool, magnitude: u128) -> String {
+            match (self.radix, self.notation) {
+                (Radix::Decimal, Notation::Auto | Notation::Fixed) => {
+                    let sign = if negative { "-" } else { "" };
+                    format!("{}{}", sign, self.group(&magnitude.to_string(), 3, ","))
+                }
+                (Radix::Decimal, _) => {
+                    let sign = if negative { -1.0 } else { 1.0 };
+                    self.format_float(sign * magnitude as f64)
+                }
+                (radix, _) => {
+                    let text = radix.format_magnitude(negative, magnitude);
+                    let (prefix, digits) = text.split_at(text.find(radix.prefix()).unwrap() + radix.prefix().len());
+                    format!("{}{}", prefix, self.group(digits, 4, "_"))
+                }
+            }
+        }
+
+        pub fn format_float(&self, value: f64) -> String {
+            if value.is_nan() {
+                return Value::Float(value).to_string();
+            }
+            if value.is_infinite() {
+                return if value < 0.0 { "-inf" } else { "inf" }.to_string();
+            }
+            if self.radix != Radix::Decimal && value.fract() == 0.0 && value.abs() < i128::MAX as f64 {
+                return self.format_integer(value as i128);
+            }
+
+            let magnitude = value.abs();
+            let notation = match self.notation {
+                Notation::Auto if magnitude != 0.0 && !(1e-6..1e15).contains(&magnitude) => Notation::Scientific,
+                Notation::Auto => Notation::Fixed,
+                notation => notation,
+            };
+            let sign = if value.is_sign_negative() && value != 0.0 { "-" } else { "" };
+            let text = match notation {
+                Notation::Scientific => {
+                    let (mantissa, exponent) = self.mantissa(magnitude, 1);
+                    format!("{}e{}", mantissa, exponent)
+                }
+                Notation::Engineering => {
+                    let (mantissa, exponent) = self.mantissa(magnitude, 3);
+                    format!("{}e{}", mantissa, exponent)
+                }
+                Notation::Si => {
+                    let (mantissa, exponent) = self.mantissa(magnitude, 3);
+                    match SI_PREFIXES.iter().find(|(power, _)| *power == exponent) {
+                        Some((_, prefix)) => format!("{}{}", mantissa, prefix),
+                        None => format!("{}e{}", mantissa, exponent),
+                    }
+                }
+                _ => self.positional(magnitude),
+            };
+            format!("{}{}", sign, text)
+        }
+
+        /// Writes a non-negative number without an exponent.
+        fn positional(&self, magnitude: f64) -> String {
+            let (integer, fraction) = match self.precision {
+                Precision::Decimals(decimals) => {
+                    let text = format!("{:.*}", decimals, magnitude);
+                    match text.split_once('.') {
+                        Some((integer, fraction)) => (integer.to_string(), fraction.to_string()),
+                        None => (text, String::new()),
+                    }
+                }
+                Precision::Significant(digits) => {
+                    let (digits, exponent) = significant_digits(magnitude, digits);
+                    let (integer, fraction) = if exponent >= 0 {
+                        let split = exponent as usize + 1;
+                        let padded = format!("{:0<width$}", digits, width = split);
+                        (padded[..split].to_string(), padded[split..].to_string())
+                    } else {
+                        ("0".to_string(), format!("{}{}", "0".repeat((-exponent - 1) as usize), digits))
+                    };
+                    (integer, fraction.trim_end_matches('0').to_string())
+                }
+            };
+            let integer = self.group(&integer, 3, ",");
+            if fraction.is_empty() {
+                integer
+            } else {
+                format!("{}.{}", integer, fraction)
+            }
+        }
+
+        /// Splits a non-negative number into a mantissa and an exponent that is a multiple of `step`.
+        fn mantissa(&self, magnitude: f64, step: i32) -> (String, i32) {
+            if magnitude == 0.0 {
+                return (self.positional(0.0), 0);
+            }
+            let digits = match self.precision {
+                Precision::Significant(digits) => digits,
+                Precision::Decimals(decimals) => decimals + 1,
+            };
+            let (_, exponent) = significant_digits(magnitude, digits);
+            let exponent = exponent.div_euclid(step) * step;
+            let scaled = magnitude / 10f64.powi(exponent);
+            let format = NumberFormat {
+                separators: false,
+                ..*self
+            };
+            (format.positional(scaled), exponent)
+        }
+
+        fn group(&self, digits: &str, size: usize, separator: &str) -> String {
+            if !self.separators {
+                return digits.to_string();
+            }
+            let chars = digits.chars().collect::<Vec<_>>();
+            let first = match chars.len() % size {
+                0 => size,
+                n => n,
+            };
+            let mut groups = vec![chars[..first.min(chars.len())].iter().collect::<String>()];
+            groups.extend(chars[first.min(chars.len())..].chunks(size).map(|chunk| chunk.iter().collect::<String>()));
+            groups.join(separator)
+        }
+    }
+
+    /// Rounds a positive number to the given number of significant digits, returning the digits
+    /// without trailing zeros and the decimal exponent of the first one.
+    fn significant_digits(magnitude: f64, digits: usize) -> (String, i32) {
+        let text = format!("{:.*e}", digits.max(1) - 1, magnitude);
+        let (mantissa, exponent) = text.split_once('e').unwrap();
+        let digits = mantissa.replace('.', "");
+        let digits = digits.trim_end_matches('0');
+        (if digits.is_empty() { "0" } else { digits }.to_string(), exponent.parse().unwrap())
+    }
+
+    #[cfg(test)]
+    mod tests {
+
+        use super::*;
+
+        #[test]
+        fn test_precision() {
+            let format = NumberFormat::default();
+            assert_eq!(format.format_float(0.1 + 0.2), "0.3");
+            assert_eq!(format.format_float(1e21), "1e21");
+            assert_eq!(format.format_float(-0.00125), "-0.00125");
+            assert_eq!(format.format_float(2.5e-9), "2.5e-9");
+            assert_eq!(format.format_float(120.0), "120");
+
+            let decimals = NumberFormat {
+                precision: Precision::Decimals(2),
+                ..format
+            };
+            assert_eq!(decimals.format_float(std::f64::consts::PI), "3.14");
+            assert_eq!(decimals.format_float(2.0), "2.00");
+        }
+
+        #[test]
+        fn test_notation() {
+            let with = |notation| NumberFormat {
+                notation,
+                ..Default::default()
+            };
+            assert_eq!(with(Notation::Scientific).format_float(1234.5), "1.2345e3");
+            assert_eq!(with(Notation::Engineering).format_float(12345.0), "12.345e3");
+            assert_eq!(with(Notation::Engineering).format_float(0.00012), "120e-6");
+            assert_eq!(with(Notation::Si).format_float(1200.0), "1.2k");
+            assert_eq!(with(Notation::Si).format_float(3.4e6), "3.4M");
+            assert_eq!(with(Notation::Si).format_integer(-4700), "-4.7k");
+            assert_eq!(with(Notation::Si).format_float(1e30), "1e30");
+        }
+
+        #[test]
+        fn test_separators_and_radix() {
+            let format = NumberFormat {
+                separators: true,
+                ..Default::default()
+            };
+            assert_eq!(format.format_integer(-1234567), "-1,234,567");
+            assert_eq!(format.format_float(1234567.25), "1,234,567.25");
+
+            let hex = NumberFormat {
+                radix: Radix::Hexadecimal,
+                ..format
+            };
+            assert_eq!(hex.format_integer(0xFFFFFF), "0xFF_FFFF");
+            assert_eq!(hex.format_float(255.0), "0xFF");
+            assert_eq!(hex.format_float(0.5), "0.5");
+        }
+    }
+}
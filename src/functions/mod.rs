@@ -0,0 +1,375 @@
+pub mod functions {
+    use std::collections::BTreeMap;
+    use std::fmt;
+    use std::sync::Arc;
+
+    use crate::matrix::matrix::{Array, LinearAlgebra, Matrix};
+    use crate::statistics::statistics::Statistics;
+    use crate::value::value::Value;
+
+    /// The type of value a native function accepts in a given position.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum ParameterType {
+        Number,
+        Integer,
+        Boolean,
+        String,
+        /// A list of numbers.
+        List,
+        /// A number, or a list of numbers, whose elements count as separate arguments.
+        Numbers,
+        /// A list of rows of numbers, all of the same length.
+        Matrix,
+        /// A matrix, or a list of numbers as a vector.
+        Array,
+        Any,
+    }
+
+    impl ParameterType {
+        pub fn accepts(&self, value: &Value) -> bool {
+            match self {
+                ParameterType::Number => matches!(value, Value::Integer(_) | Value::Word(_) | Value::Float(_)),
+                ParameterType::Integer => value.as_integer().is_some(),
+                ParameterType::Boolean => matches!(value, Value::Bool(_)),
+                ParameterType::String => matches!(value, Value::String(_)),
+                ParameterType::List => {
+                    matches!(value, Value::List(values) if values.iter().all(|value| ParameterType::Number.accepts(value)))
+                }
+                ParameterType::Numbers => ParameterType::Number.accepts(value) || ParameterType::List.accepts(value),
+                ParameterType::Matrix => Matrix::from_value(value).is_some(),
+                ParameterType::Array => Array::from_value(value).is_some(),
+                ParameterType::Any => true,
+            }
+        }
+    }
+
+    impl fmt::Display for ParameterType {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                ParameterType::Number => write!(f, "number"),
+                ParameterType::Integer => write!(f, "integer"),
+                ParameterType::Boolean => write!(f, "boolean"),
+                ParameterType::String => write!(f, "string"),
+                ParameterType::List => write!(f, "list of numbers"),
+                ParameterType::Numbers => write!(f, "number or list of numbers"),
+                ParameterType::Matrix => write!(f, "matrix"),
+                ParameterType::Array => write!(f, "vector or matrix"),
+                ParameterType::Any => write!(f, "value"),
+            }
+        }
+    }
+
+    /// The arguments a native function takes.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum Parameters {
+        /// Exactly these arguments, in this order.
+        Fixed(Vec<ParameterType>),
+        /// One or more arguments, all of this type.
+        Variadic(ParameterType),
+    }
+
+    impl Parameters {
+        fn describe_arity(&self) -> String {
+            match self {
+                Parameters::Fixed(types) if types.len() == 1 => "1 argument".to_string(),
+                Parameters::Fixed(types) => format!("{} arguments", types.len()),
+                Parameters::Variadic(_) => "at least 1 argument".to_string(),
+            }
+        }
+
+        fn parameter_type(&self, index: usize) -> ParameterType {
+            match self {
+                Parameters::Fixed(types) => types[index],
+                Parameters::Variadic(parameter_type) => *parameter_type,
+            }
+        }
+    }
+
+    /// Why a call to a native function failed.
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum CallError {
+        Arity { expected: String, found: usize },
+        ArgumentType { position: usize, expected: ParameterType, found: Value },
+        /// The arguments at these positions, such as the matrix and vector passed to `solve`, don't fit together.
+        ShapeMismatch { lhs: usize, rhs: usize, lhs_shape: String, rhs_shape: String },
+        Failed(String),
+    }
+
+    pub type NativeFn = dyn Fn(&[Value]) -> Result<Value, CallError> + Send + Sync;
+
+    pub type Resolver = dyn Fn(&str) -> Option<Value> + Send + Sync;
+
+    /// A function implemented by the host application, which can be called from expressions.
+    #[derive(Clone)]
+    pub struct NativeFunction {
+        pub name: String,
+        pub parameters: Parameters,
+        function: Arc<NativeFn>,
+    }
+
+    impl NativeFunction {
+        /// Checks the number and types of the arguments, then calls the function.
+        pub fn call(&self, arguments: &[Value]) -> Result<Value, CallError> {
+            let arity_matches = match &self.parameters {
+                Parameters::Fixed(types) => types.len() == arguments.len(),
+                Parameters::Variadic(_) => !arguments.is_empty(),
+            };
+            if !arity_matches {
+                return Err(CallError::Arity {
+                    expected: self.parameters.describe_arity(),
+                    found: arguments.len(),
+                });
+            }
+
+            let mut checked = Vec::with_capacity(arguments.len());
+            for (index, argument) in arguments.iter().enumerate() {
+                let expected = self.parameters.parameter_type(index);
+                if !expected.accepts(argument) {
+                    return Err(CallError::ArgumentType {
+                        position: index + 1,
+                        expected,
+                        found: argument.clone(),
+                    });
+                }
+                // An integer that was calculated as a float, such as `6 / 2`, is passed as an integer.
+                checked.push(match (expected, argument.as_integer()) {
+                    (ParameterType::Integer, Some(value)) => Value::Integer(value),
+                    _ => argument.clone(),
+                });
+            }
+
+            (self.function)(&checked)
+        }
+    }
+
+    impl fmt::Debug for NativeFunction {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.debug_struct("NativeFunction")
+                .field("name", &self.name)
+                .field("parameters", &self.parameters)
+                .finish_non_exhaustive()
+        }
+    }
+
+    /// Extends the calculator with functions and variables from the host application.
+    pub trait Plugin {
+        fn register(&self, registry: &mut Registry);
+    }
+
+    /// The native functions and variable resolvers available to expressions.
+    #[derive(Clone)]
+    pub struct Registry {
+        functions: BTreeMap<String, NativeFunction>,
+        resolvers: Vec<Arc<Resolver>>,
+    }
+
+    impl Registry {
+        /// A registry without even the built-in functions.
+        pub fn empty() -> Self {
+            Registry {
+                functions: BTreeMap::new(),
+                resolvers: vec![],
+            }
+        }
+
+        pub fn register(&mut self, plugin: &dyn Plugin) {
+            plugin.register(self);
+        }
+
+        /// Registers a native function, replacing any existing function with the same name. The
+        /// arguments are checked against the parameters before the function is called, and an integer
+        /// parameter always gets a `Value::Integer`.
+        pub fn register_function(
+            &mut self,
+            name: &str,
+            parameters: Parameters,
+            function: impl Fn(&[Value]) -> Result<Value, String> + Send + Sync + 'static,
+        ) {
+            self.register_native(name, parameters, move |arguments| function(arguments).map_err(CallError::Failed));
+        }
+
+        /// Registers a native function that can report why it failed in more detail than a message, such
+        /// as which of its arguments have shapes that don't fit together.
+        pub fn register_native(
+            &mut self,
+            name: &str,
+            parameters: Parameters,
+            function: impl Fn(&[Value]) -> Result<Value, CallError> + Send + Sync + 'static,
+        ) {
+            self.functions.insert(
+                name.to_string(),
+                NativeFunction {
+                    name: name.to_string(),
+                    parameters,
+                    function: Arc::new(function),
+                },
+            );
+        }
+
+        /// Registers a callback that is consulted for variables that aren't defined in the session or
+        /// built in. Resolvers are consulted in the order they were registered.
+        pub fn register_resolver(&mut self, resolver: impl Fn(&str) -> Option<Value> + Send + Sync + 'static) {
+            self.resolvers.push(Arc::new(resolver));
+        }
+
+        pub fn function(&self, name: &str) -> Option<&NativeFunction> {
+            self.functions.get(name)
+        }
+
+        pub fn function_names(&self) -> impl Iterator<Item = &str> {
+            self.functions.keys().map(String::as_str)
+        }
+
+        pub fn resolve(&self, name: &str) -> Option<Value> {
+            self.resolvers.iter().find_map(|resolver| resolver(name))
+        }
+    }
+
+    impl Default for Registry {
+        /// A registry with the built-in functions.
+        fn default() -> Self {
+            let mut registry = Registry::empty();
+            registry.register(&Builtins);
+            registry
+        }
+    }
+
+    impl fmt::Debug for Registry {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.debug_struct("Registry")
+                .field("functions", &self.functions.keys().collect::<Vec<_>>())
+                .field("resolvers", &self.resolvers.len())
+                .finish()
+        }
+    }
+
+    /// The mathematical functions that are available in every session.
+    pub struct Builtins;
+
+    impl Builtins {
+        fn unary(registry: &mut Registry, name: &str, function: fn(f64) -> f64) {
+            registry.register_function(name, Parameters::Fixed(vec![ParameterType::Number]), move |arguments| {
+                Ok(Value::Float(function(arguments[0].as_f64())))
+            });
+        }
+
+        fn binary(registry: &mut Registry, name: &str, function: fn(f64, f64) -> f64) {
+            registry.register_function(
+                name,
+                Parameters::Fixed(vec![ParameterType::Number, ParameterType::Number]),
+                move |arguments| Ok(Value::Float(function(arguments[0].as_f64(), arguments[1].as_f64()))),
+            );
+        }
+    }
+
+    impl Plugin for Builtins {
+        fn register(&self, registry: &mut Registry) {
+            Builtins::unary(registry, "sin", f64::sin);
+            Builtins::unary(registry, "cos", f64::cos);
+            Builtins::unary(registry, "tan", f64::tan);
+            Builtins::unary(registry, "asin", f64::asin);
+            Builtins::unary(registry, "acos", f64::acos);
+            Builtins::unary(registry, "atan", f64::atan);
+            Builtins::binary(registry, "atan2", f64::atan2);
+            Builtins::unary(registry, "sinh", f64::sinh);
+            Builtins::unary(registry, "cosh", f64::cosh);
+            Builtins::unary(registry, "tanh", f64::tanh);
+            Builtins::unary(registry, "sqrt", f64::sqrt);
+            Builtins::unary(registry, "cbrt", f64::cbrt);
+            Builtins::unary(registry, "exp", f64::exp);
+            Builtins::unary(registry, "ln", f64::ln);
+            Builtins::unary(registry, "log10", f64::log10);
+            Builtins::unary(registry, "log2", f64::log2);
+            Builtins::binary(registry, "log", f64::log);
+            Builtins::binary(registry, "hypot", f64::hypot);
+            Builtins::unary(registry, "abs", f64::abs);
+            Builtins::unary(registry, "floor", f64::floor);
+            Builtins::unary(registry, "ceil", f64::ceil);
+            Builtins::unary(registry, "round", f64::round);
+            Builtins::unary(registry, "trunc", f64::trunc);
+            Builtins::unary(registry, "sign", f64::signum);
+            registry.register(&Statistics);
+            registry.register(&LinearAlgebra);
+        }
+    }
+
+    /// Resolves `env.NAME` to the value of the environment variable `NAME`, if it holds a number.
+    pub struct EnvironmentVariables;
+
+    impl Plugin for EnvironmentVariables {
+        fn register(&self, registry: &mut Registry) {
+            registry.register_resolver(|name| {
+                let value = std::env::var(name.strip_prefix("env.")?).ok()?;
+                let value = value.trim();
+                match value.parse::<i128>() {
+                    Ok(value) => Some(Value::Integer(value)),
+                    Err(_) => value.parse::<f64>().ok().map(Value::Float),
+                }
+            });
+        }
+    }
+
+    /// The names of the plugins that ship with the calculator.
+    pub const BUNDLED_PLUGINS: &[&str] = &["env"];
+
+    /// Looks up one of the plugins that ship with the calculator by name, for enabling them at startup.
+    pub fn bundled_plugin(name: &str) -> Option<Box<dyn Plugin>> {
+        match name {
+            "env" => Some(Box::new(EnvironmentVariables)),
+            _ => None,
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+
+        use super::*;
+
+        #[test]
+        fn test_call_checks_arguments() {
+            let mut registry = Registry::empty();
+            registry.register_function(
+                "repeat",
+                Parameters::Fixed(vec![ParameterType::String, ParameterType::Integer]),
+                |arguments| match (&arguments[0], arguments[1].as_integer()) {
+                    (Value::String(text), Some(count)) => Ok(Value::Integer(text.len() as i128 * count)),
+                    _ => Err("unexpected arguments".to_string()),
+                },
+            );
+            let repeat = registry.function("repeat").unwrap();
+
+            assert_eq!(
+                repeat.call(&[Value::String("ab".to_string()), Value::Integer(3)]),
+                Ok(Value::Integer(6))
+            );
+            assert_eq!(
+                repeat.call(&[Value::Integer(3)]),
+                Err(CallError::Arity {
+                    expected: "2 arguments".to_string(),
+                    found: 1
+                })
+            );
+            assert_eq!(
+                repeat.call(&[Value::String("ab".to_string()), Value::Float(1.5)]),
+                Err(CallError::ArgumentType {
+                    position: 2,
+                    expected: ParameterType::Integer,
+                    found: Value::Float(1.5)
+                })
+            );
+
+            registry.register_function("echo", Parameters::Fixed(vec![ParameterType::Integer]), |arguments| Ok(arguments[0].clone()));
+            let echo = registry.function("echo").unwrap();
+            assert_eq!(echo.call(&[Value::Float(3.0)]), Ok(Value::Integer(3)));
+        }
+
+        #[test]
+        fn test_resolvers() {
+            let mut registry = Registry::empty();
+            registry.register_resolver(|name| (name == "config.timeout").then_some(Value::Integer(30)));
+            registry.register_resolver(|_| Some(Value::Integer(0)));
+            assert_eq!(registry.resolve("config.timeout"), Some(Value::Integer(30)));
+            assert_eq!(registry.resolve("other"), Some(Value::Integer(0)));
+        }
+    }
+}
@@ -1,22 +1,70 @@
 pub mod grammar {
-    use std::collections::HashMap;
+    use std::collections::{BTreeMap, BTreeSet, HashMap};
+    use std::ops::Range;
 
     use pest_derive::Parser;
-    use pest::iterators::Pairs;
+    use pest::iterators::{Pair, Pairs};
     use pest::pratt_parser::PrattParser;
     use pest::Parser;
 
+    use crate::calculus::calculus;
+    use crate::constants::constants;
+    use crate::functions::functions::{CallError, ParameterType, Registry};
+    use crate::matrix::matrix::{self, ProductError};
+    use crate::solve::solve;
+    use crate::symbolic::symbolic;
+    use crate::suggest::suggest::did_you_mean;
+    use crate::value::value::{IntegerMode, Value, Word, WordSize};
+
     lazy_static::lazy_static! {
         static ref PRATT_PARSER: PrattParser<Rule> = {
             use pest::pratt_parser::{Assoc::*, Op};
             use Rule::*;
 
+            // Exponentiation binds tighter than negation and groups to the right, so
+            // `-2^2` is `-(2^2)` and `2^3^2` is `2^(3^2)`. Implicit multiplication binds tighter
+            // than the explicit operators, so `1/2x` is `1/(2x)`, but not tighter than `^`, so
+            // `2x^2` is `2(x^2)`.
+            //
+            // The bitwise operators bind more loosely than the arithmetic ones, and the comparison
+            // and logical operators more loosely still, as in Python.
             PrattParser::new()
+                .op(Op::infix(or, Left))
+                .op(Op::infix(and, Left))
+                .op(Op::infix(equal, Left) | Op::infix(not_equal, Left) | Op::infix(less, Left) | Op::infix(less_equal, Left) | Op::infix(greater, Left) | Op::infix(greater_equal, Left))
+                .op(Op::infix(bitwise_or, Left))
+                .op(Op::infix(bitwise_xor, Left))
+                .op(Op::infix(bitwise_and, Left))
+                .op(Op::infix(shift_left, Left) | Op::infix(shift_right, Left) | Op::infix(rotate_left, Left) | Op::infix(rotate_right, Left))
                 .op(Op::infix(add, Left) | Op::infix(subtract, Left))
-                .op(Op::infix(multiply, Left) | Op::infix(divide, Left))
+                .op(Op::infix(multiply, Left) | Op::infix(matrix_multiply, Left) | Op::infix(divide, Left))
                 .op(Op::infix(modulo, Left))
+                .op(Op::prefix(unary_minus) | Op::prefix(bitwise_not) | Op::prefix(not))
+                .op(Op::infix(implicit_multiply, Left))
+                .op(Op::infix(power, Right))
+                .op(Op::infix(assignment, Left))
+        };
+
+        /// The precedence rules used before exponentiation was made right-associative, kept so that
+        /// expressions whose meaning changed can be flagged.
+        static ref LEGACY_PRATT_PARSER: PrattParser<Rule> = {
+            use pest::pratt_parser::{Assoc::*, Op};
+            use Rule::*;
+
+            PrattParser::new()
+                .op(Op::infix(or, Left))
+                .op(Op::infix(and, Left))
+                .op(Op::infix(equal, Left) | Op::infix(not_equal, Left) | Op::infix(less, Left) | Op::infix(less_equal, Left) | Op::infix(greater, Left) | Op::infix(greater_equal, Left))
+                .op(Op::infix(bitwise_or, Left))
+                .op(Op::infix(bitwise_xor, Left))
+                .op(Op::infix(bitwise_and, Left))
+                .op(Op::infix(shift_left, Left) | Op::infix(shift_right, Left) | Op::infix(rotate_left, Left) | Op::infix(rotate_right, Left))
+                .op(Op::infix(add, Left) | Op::infix(subtract, Left))
+                .op(Op::infix(multiply, Left) | Op::infix(matrix_multiply, Left) | Op::infix(divide, Left))
+                .op(Op::infix(modulo, Left))
+                .op(Op::infix(implicit_multiply, Left))
                 .op(Op::infix(power, Left))
-                .op(Op::prefix(unary_minus))
+                .op(Op::prefix(unary_minus) | Op::prefix(bitwise_not) | Op::prefix(not))
                 .op(Op::infix(assignment, Left))
         };
     }
@@ -34,42 +82,738 @@ pub mod grammar {
     #[derive(Debug, Clone)]
     pub enum Expr {
         Integer {
-            value: i32,
+            value: i128,
+            radix: Radix,
+            variable: Option<Variable>,
+        },
+        /// An integer in programmer mode, including literals, which are read as a bit pattern in the word.
+        Word {
+            value: Word,
+            radix: Radix,
             variable: Option<Variable>,
         },
         Float {
             value: f64,
             variable: Option<Variable>,
         },
+        Boolean {
+            value: bool,
+            variable: Option<Variable>,
+        },
+        String {
+            value: String,
+            variable: Option<Variable>,
+        },
         UnboundVariable {
             name: String,
+            suggestion: Option<String>,
+        },
+        /// A variable that is deliberately left without a value in a formula, such as the `x` in the
+        /// derivative of `x^2`.
+        FreeVariable {
+            name: String,
+        },
+        /// `[1, 2, 3]`, or a list stored in a variable.
+        List {
+            elements: Vec<Expr>,
+            variable: Option<Variable>,
         },
         BinaryOperation {
             lhs: Box<Expr>,
             op: BinaryOperator,
             rhs: Box<Expr>,
-            value: f64,
+            value: Value,
         },
         UnaryOperation {
             op: UnaryOperator,
             expr: Box<Expr>,
+            value: Value,
+        },
+        /// An angle written with a unit, e.g. `30deg`. The value is in radians, whatever the angle mode.
+        Angle {
+            amount: f64,
+            unit: AngleUnit,
             value: f64,
         },
+        /// A call to a native function, e.g. `sqrt(2)`.
+        FunctionCall {
+            name: String,
+            arguments: Vec<Expr>,
+            /// The unit a plain number was read in, for the functions that take an angle.
+            angle: Option<AngleUnit>,
+            value: Value,
+        },
         Assignment {
             identifier: String,
             expr: Box<Expr>,
         },
+        /// `start..end step step`, whose value is the list of numbers in the range.
+        Range {
+            start: Box<Expr>,
+            end: Box<Expr>,
+            step: Option<Box<Expr>>,
+            value: Value,
+        },
+        /// `if condition then a else b`, or `condition ? a : b`.
+        Conditional {
+            condition: Box<Expr>,
+            then_branch: Box<Expr>,
+            else_branch: Box<Expr>,
+            value: Value,
+        },
+        /// `diff(expr, x)`, or `diff(expr, x, point)` to evaluate the derivative at a point.
+        Derivative {
+            expr: Box<Expr>,
+            variable: String,
+            /// The derivative, evaluated at the point or the value of the variable if it has one.
+            derivative: Box<Expr>,
+            at: Option<Box<Expr>>,
+            value: Value,
+        },
+        /// `solve(lhs = rhs, x)`, with both sides evaluated at the solution if there is only one.
+        Solution {
+            lhs: Box<Expr>,
+            rhs: Box<Expr>,
+            variable: String,
+            /// The starting point or the bracket `[a, b]`, if one was given.
+            guess: Option<Box<Expr>>,
+            /// How the solution was found, e.g. `Newton's method`.
+            method: &'static str,
+            iterations: usize,
+            /// The largest difference between the two sides at a solution.
+            residual: f64,
+            value: Value,
+        },
+        /// `integrate(body, x, a, b)`, `sum(body, k, a, b)` or `prod(body, k, a, b)`, with the body evaluated
+        /// at a single point of the range to show what is being added up.
+        BoundOperation {
+            operator: BoundOperator,
+            body: Box<Expr>,
+            variable: String,
+            lower: Box<Expr>,
+            upper: Box<Expr>,
+            /// The value of the variable the body is evaluated at.
+            at: f64,
+            /// The estimated error of an integral.
+            error: Option<f64>,
+            /// The number of times the body was evaluated.
+            evaluations: usize,
+            value: Value,
+        },
+        /// The branch of a conditional that was not taken, and so was never evaluated.
+        Unevaluated {
+            source: String,
+        },
+    }
+
+    /// The base an integer literal was written in.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub enum Radix {
+        Binary,
+        Octal,
+        #[default]
+        Decimal,
+        Hexadecimal,
+    }
+
+    impl Radix {
+        pub fn base(&self) -> u32 {
+            match self {
+                Radix::Binary => 2,
+                Radix::Octal => 8,
+                Radix::Decimal => 10,
+                Radix::Hexadecimal => 16,
+            }
+        }
+
+        pub fn prefix(&self) -> &'static str {
+            match self {
+                Radix::Binary => "0b",
+                Radix::Octal => "0o",
+                Radix::Decimal => "",
+                Radix::Hexadecimal => "0x",
+            }
+        }
+
+        /// Formats the value the way a literal in this radix would be written.
+        pub fn format(&self, value: i128) -> String {
+            self.format_magnitude(value < 0, value.unsigned_abs())
+        }
+
+        /// Formats a word the way a literal in this radix would be written: as its bit pattern, except
+        /// in decimal, so that `0xFF` is still written `0xFF` as an i8.
+        pub fn format_word(&self, word: &Word) -> String {
+            match (self, word.to_i128()) {
+                (Radix::Decimal, Some(value)) => self.format(value),
+                _ => self.format_magnitude(false, word.bits),
+            }
+        }
+
+        pub fn format_magnitude(&self, negative: bool, magnitude: u128) -> String {
+            let sign = if negative { "-" } else { "" };
+            let digits = match self {
+                Radix::Binary => format!("{:b}", magnitude),
+                Radix::Octal => format!("{:o}", magnitude),
+                Radix::Decimal => format!("{}", magnitude),
+                Radix::Hexadecimal => format!("{:X}", magnitude),
+            };
+            format!("{}{}{}", sign, self.prefix(), digits)
+        }
+    }
+
+    impl std::fmt::Display for Radix {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            match self {
+                Radix::Binary => write!(f, "bin"),
+                Radix::Octal => write!(f, "oct"),
+                Radix::Decimal => write!(f, "dec"),
+                Radix::Hexadecimal => write!(f, "hex"),
+            }
+        }
+    }
+
+    impl std::str::FromStr for Radix {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s {
+                "bin" | "2" => Ok(Radix::Binary),
+                "oct" | "8" => Ok(Radix::Octal),
+                "dec" | "10" => Ok(Radix::Decimal),
+                "hex" | "16" => Ok(Radix::Hexadecimal),
+                _ => Err(format!("invalid radix `{}`, expected `bin`, `oct`, `dec` or `hex`", s)),
+            }
+        }
+    }
+
+    /// The unit of angles: of the arguments of `sin`, `cos` and `tan` that aren't already angles, and of
+    /// the results of their inverses when they are displayed.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub enum AngleUnit {
+        #[default]
+        Radians,
+        Degrees,
+        Gradians,
+    }
+
+    impl AngleUnit {
+        pub fn to_radians(&self, amount: f64) -> f64 {
+            match self {
+                AngleUnit::Radians => amount,
+                AngleUnit::Degrees => amount.to_radians(),
+                AngleUnit::Gradians => amount * std::f64::consts::PI / 200.0,
+            }
+        }
+
+        pub fn from_radians(&self, radians: f64) -> f64 {
+            match self {
+                AngleUnit::Radians => radians,
+                AngleUnit::Degrees => radians.to_degrees(),
+                AngleUnit::Gradians => radians * 200.0 / std::f64::consts::PI,
+            }
+        }
+    }
+
+    impl std::fmt::Display for AngleUnit {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            match self {
+                AngleUnit::Radians => write!(f, "rad"),
+                AngleUnit::Degrees => write!(f, "deg"),
+                AngleUnit::Gradians => write!(f, "grad"),
+            }
+        }
+    }
+
+    impl std::str::FromStr for AngleUnit {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s {
+                "rad" | "radians" => Ok(AngleUnit::Radians),
+                "deg" | "degrees" => Ok(AngleUnit::Degrees),
+                "grad" | "gradians" => Ok(AngleUnit::Gradians),
+                _ => Err(format!("invalid angle unit `{}`, expected `rad`, `deg` or `grad`", s)),
+            }
+        }
+    }
+
+    /// The functions that take an angle.
+    const TRIGONOMETRIC_FUNCTIONS: &[&str] = &["sin", "cos", "tan"];
+
+    /// The functions that return an angle.
+    const INVERSE_TRIGONOMETRIC_FUNCTIONS: &[&str] = &["asin", "acos", "atan", "atan2"];
+
+    /// The digits of an integer literal, without its prefix and separators.
+    fn literal_digits(text: &str, radix: Radix) -> String {
+        text[radix.prefix().len()..].chars().filter(|c| *c != '_').collect()
+    }
+
+    /// Parses an integer literal, falling back to a float if it is too large to be held exactly.
+    fn parse_integer(text: &str, radix: Radix) -> Expr {
+        let digits = literal_digits(text, radix);
+        match i128::from_str_radix(&digits, radix.base()) {
+            Ok(value) => Expr::Integer {
+                value,
+                radix,
+                variable: None,
+            },
+            Err(_) => Expr::Float {
+                value: digits
+                    .chars()
+                    .fold(0f64, |acc, digit| acc * radix.base() as f64 + digit.to_digit(radix.base()).unwrap() as f64),
+                variable: None,
+            },
+        }
+    }
+
+    impl Expr {
+        pub fn value(&self) -> Value {
+            match self {
+                Expr::Integer { value, .. } => Value::Integer(*value),
+                Expr::Word { value, .. } => Value::Word(*value),
+                Expr::Float { value, .. } => Value::Float(*value),
+                Expr::Boolean { value, .. } => Value::Bool(*value),
+                Expr::String { value, .. } => Value::String(value.clone()),
+                Expr::Angle { value, .. } => Value::Float(*value),
+                Expr::List { elements, .. } => Value::List(elements.iter().map(Expr::value).collect()),
+                Expr::FunctionCall { value, .. } => value.clone(),
+                Expr::BinaryOperation { value, .. } => value.clone(),
+                Expr::Conditional { value, .. } => value.clone(),
+                Expr::Range { value, .. } => value.clone(),
+                Expr::Derivative { value, .. } => value.clone(),
+                Expr::Solution { value, .. } => value.clone(),
+                Expr::BoundOperation { value, .. } => value.clone(),
+                Expr::Unevaluated { .. } => Value::Float(f64::NAN),
+                Expr::FreeVariable { .. } => Value::Float(f64::NAN),
+                Expr::UnaryOperation { value, .. } => value.clone(),
+                Expr::UnboundVariable { .. } => Value::Float(f64::NAN),
+                Expr::Assignment { .. } => unreachable!("Can't unwrap assignment"),
+            }
+        }
+
+        /// A leaf holding the value, as if it had been written as a literal.
+        pub fn literal(value: Value) -> Expr {
+            match value {
+                Value::Integer(value) => Expr::Integer {
+                    value,
+                    radix: Radix::Decimal,
+                    variable: None,
+                },
+                Value::Word(value) => Expr::Word {
+                    value,
+                    radix: Radix::Decimal,
+                    variable: None,
+                },
+                Value::Float(value) => Expr::Float { value, variable: None },
+                Value::Bool(value) => Expr::Boolean { value, variable: None },
+                Value::String(value) => Expr::String { value, variable: None },
+                Value::List(values) => Expr::List {
+                    elements: values.into_iter().map(Expr::literal).collect(),
+                    variable: None,
+                },
+            }
+        }
+    }
+
+    impl Expr {
+        /// Whether the value is an angle, held in radians and shown in the angle mode. Angles are written
+        /// with a unit or returned by the inverse trigonometric functions, and stay angles when they are
+        /// added together or scaled.
+        pub fn is_angle(&self) -> bool {
+            match self {
+                Expr::Angle { .. } => true,
+                Expr::FunctionCall { name, .. } => INVERSE_TRIGONOMETRIC_FUNCTIONS.contains(&name.as_str()),
+                Expr::Integer { variable, .. } | Expr::Word { variable, .. } | Expr::Float { variable, .. } => {
+                    variable.as_ref().is_some_and(|variable| variable.expr.is_angle())
+                }
+                Expr::UnaryOperation {
+                    op: UnaryOperator::Minus,
+                    expr,
+                    ..
+                } => expr.is_angle(),
+                Expr::BinaryOperation { lhs, op, rhs, .. } => match op {
+                    BinaryOperator::Add | BinaryOperator::Subtract => lhs.is_angle() || rhs.is_angle(),
+                    BinaryOperator::Multiply | BinaryOperator::ImplicitMultiply => lhs.is_angle() != rhs.is_angle(),
+                    BinaryOperator::Divide => lhs.is_angle() && !rhs.is_angle(),
+                    BinaryOperator::Modulo => lhs.is_angle(),
+                    _ => false,
+                },
+                Expr::Conditional {
+                    then_branch,
+                    else_branch,
+                    ..
+                } => match then_branch.as_ref() {
+                    Expr::Unevaluated { .. } => else_branch.is_angle(),
+                    then_branch => then_branch.is_angle(),
+                },
+                _ => false,
+            }
+        }
+
+        /// Collects the unbound variables in the expression, along with the suggested replacement for each.
+        pub fn unbound_variables(&self) -> BTreeMap<String, Option<String>> {
+            let mut unbound = BTreeMap::new();
+            match self {
+                Expr::BinaryOperation { lhs, rhs, .. } => {
+                    unbound.extend(lhs.unbound_variables());
+                    unbound.extend(rhs.unbound_variables());
+                }
+                Expr::UnaryOperation { expr, .. } => {
+                    unbound.extend(expr.unbound_variables());
+                }
+                Expr::FunctionCall { arguments, .. } => {
+                    for argument in arguments {
+                        unbound.extend(argument.unbound_variables());
+                    }
+                }
+                Expr::List { elements, .. } => {
+                    for element in elements {
+                        unbound.extend(element.unbound_variables());
+                    }
+                }
+                Expr::Range { start, end, step, .. } => {
+                    unbound.extend(start.unbound_variables());
+                    unbound.extend(end.unbound_variables());
+                    if let Some(step) = step {
+                        unbound.extend(step.unbound_variables());
+                    }
+                }
+                Expr::Conditional {
+                    condition,
+                    then_branch,
+                    else_branch,
+                    ..
+                } => {
+                    unbound.extend(condition.unbound_variables());
+                    unbound.extend(then_branch.unbound_variables());
+                    unbound.extend(else_branch.unbound_variables());
+                }
+                Expr::Derivative { derivative, at, .. } => {
+                    unbound.extend(derivative.unbound_variables());
+                    if let Some(at) = at {
+                        unbound.extend(at.unbound_variables());
+                    }
+                }
+                Expr::BoundOperation { body, lower, upper, .. } => {
+                    unbound.extend(body.unbound_variables());
+                    unbound.extend(lower.unbound_variables());
+                    unbound.extend(upper.unbound_variables());
+                }
+                Expr::UnboundVariable { name, suggestion } => {
+                    unbound.insert(name.clone(), suggestion.clone());
+                }
+                _ => {}
+            }
+            unbound
+        }
+
+        /// The expressions this one is calculated from.
+        pub fn operands(&self) -> Vec<&Expr> {
+            match self {
+                Expr::BinaryOperation { lhs, rhs, .. } => vec![lhs, rhs],
+                Expr::UnaryOperation { expr, .. } => vec![expr],
+                Expr::FunctionCall { arguments, .. } => arguments.iter().collect(),
+                Expr::List { elements, .. } => elements.iter().collect(),
+                Expr::Range { start, end, step, .. } => [Some(start), Some(end), step.as_ref()].into_iter().flatten().map(|expr| &**expr).collect(),
+                Expr::Conditional {
+                    condition,
+                    then_branch,
+                    else_branch,
+                    ..
+                } => vec![condition, then_branch, else_branch],
+                Expr::Derivative { derivative, at, .. } => [Some(derivative), at.as_ref()].into_iter().flatten().map(|expr| &**expr).collect(),
+                // The sides of an equation are evaluated at the solution, so only the guess can depend on anything.
+                Expr::Solution { guess, .. } => guess.iter().map(|expr| &**expr).collect(),
+                Expr::BoundOperation { body, lower, upper, .. } => vec![body, lower, upper],
+                Expr::Assignment { expr, .. } => vec![expr],
+                _ => vec![],
+            }
+        }
+
+        /// The variables left free in the expression, which is a formula rather than a number if there are any.
+        /// A stored formula counts with the variables that are still free in it.
+        pub fn free_variables(&self) -> BTreeSet<String> {
+            match self {
+                Expr::FreeVariable { name } => BTreeSet::from([name.clone()]),
+                Expr::Integer { variable: Some(variable), .. }
+                | Expr::Word { variable: Some(variable), .. }
+                | Expr::Float { variable: Some(variable), .. }
+                | Expr::List { variable: Some(variable), .. } => variable.expr.free_variables(),
+                // The variable of a sum or an integral only has a value inside it.
+                Expr::BoundOperation { body, variable, lower, upper, .. } => {
+                    let mut free = body.free_variables();
+                    free.remove(variable);
+                    free.extend(lower.free_variables());
+                    free.extend(upper.free_variables());
+                    free
+                }
+                expr => expr.operands().into_iter().flat_map(Expr::free_variables).collect(),
+            }
+        }
+    }
+
+    impl Expr {
+        /// How tightly the expression binds when it is written out, following the precedence of the
+        /// grammar: 0 for a conditional, up to 14 for a literal or a bracketed expression.
+        fn precedence(&self) -> u8 {
+            match self {
+                Expr::Conditional { .. } | Expr::Range { .. } | Expr::Assignment { .. } | Expr::Unevaluated { .. } => 0,
+                Expr::BinaryOperation { lhs, op, rhs, .. } => match op {
+                    BinaryOperator::Or => 1,
+                    BinaryOperator::And => 2,
+                    op if op.is_comparison() => 3,
+                    BinaryOperator::BitwiseOr => 4,
+                    BinaryOperator::BitwiseXor => 5,
+                    BinaryOperator::BitwiseAnd => 6,
+                    op if op.is_bitwise() => 7,
+                    BinaryOperator::Add | BinaryOperator::Subtract => 8,
+                    BinaryOperator::ImplicitMultiply if juxtaposed(lhs, rhs) => 12,
+                    BinaryOperator::Multiply | BinaryOperator::ImplicitMultiply | BinaryOperator::MatrixMultiply | BinaryOperator::Divide => 9,
+                    BinaryOperator::Modulo => 10,
+                    _ => 13,
+                },
+                Expr::UnaryOperation { .. } => 11,
+                // A negative number is written with a minus, which binds like one.
+                Expr::Integer { value, variable: None, .. } if *value < 0 => 11,
+                Expr::Word { value, radix, variable: None } if radix.format_word(value).starts_with('-') => 11,
+                Expr::Float { value, variable: None } if value.is_sign_negative() => 11,
+                Expr::Angle { amount, .. } if amount.is_sign_negative() => 11,
+                _ => 14,
+            }
+        }
+    }
+
+    /// Whether implicit multiplication can be written without the `*`, as in `2x` or `3sin(x)`.
+    fn juxtaposed(lhs: &Expr, rhs: &Expr) -> bool {
+        let number = matches!(lhs, Expr::Integer { variable: None, .. } | Expr::Word { variable: None, .. } | Expr::Float { variable: None, .. })
+            && lhs.precedence() == 14;
+        let named = match rhs {
+            Expr::Integer { variable, .. } | Expr::Word { variable, .. } | Expr::Float { variable, .. } => variable.is_some(),
+            Expr::UnboundVariable { .. } | Expr::FreeVariable { .. } | Expr::FunctionCall { .. } => true,
+            _ => false,
+        };
+        number && named
+    }
+
+    /// Writes the operand, in brackets if it binds less tightly than the operator it belongs to.
+    fn write_operand(f: &mut std::fmt::Formatter, operand: &Expr, bracketed: bool) -> std::fmt::Result {
+        if bracketed {
+            write!(f, "({})", operand)
+        } else {
+            write!(f, "{}", operand)
+        }
+    }
+
+    /// Writes the expression the way it would be typed, with only the brackets that are needed to read it
+    /// back the same way, e.g. `3 * x^2 + 2`.
+    impl std::fmt::Display for Expr {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            let precedence = self.precedence();
+            let list = |expressions: &[Expr]| expressions.iter().map(Expr::to_string).collect::<Vec<_>>().join(", ");
+            match self {
+                Expr::Integer { variable: Some(variable), .. }
+                | Expr::Word { variable: Some(variable), .. }
+                | Expr::Float { variable: Some(variable), .. }
+                | Expr::Boolean { variable: Some(variable), .. }
+                | Expr::String { variable: Some(variable), .. }
+                | Expr::List { variable: Some(variable), .. } => write!(f, "{}", variable.name),
+                Expr::Integer { value, radix, .. } => write!(f, "{}", radix.format(*value)),
+                Expr::Word { value, radix, .. } => write!(f, "{}", radix.format_word(value)),
+                Expr::Float { value, .. } => write!(f, "{}", Value::Float(*value)),
+                Expr::Boolean { value, .. } => write!(f, "{}", value),
+                Expr::String { value, .. } => write!(f, "\"{}\"", value),
+                Expr::List { elements, .. } => write!(f, "[{}]", list(elements)),
+                Expr::UnboundVariable { name, .. } | Expr::FreeVariable { name } => write!(f, "{}", name),
+                Expr::Angle { amount, unit, .. } => write!(f, "{}{}", amount, unit),
+                Expr::BinaryOperation { lhs, op, rhs, .. } => {
+                    let right_associative = matches!(op, BinaryOperator::Power);
+                    write_operand(f, lhs, lhs.precedence() < precedence || (right_associative && lhs.precedence() == precedence))?;
+                    match op {
+                        BinaryOperator::ImplicitMultiply if precedence == 12 => {}
+                        BinaryOperator::Power => write!(f, "^")?,
+                        op => write!(f, " {} ", op)?,
+                    }
+                    write_operand(f, rhs, rhs.precedence() < precedence || (!right_associative && rhs.precedence() == precedence))
+                }
+                Expr::UnaryOperation { op, expr, .. } => {
+                    write!(f, "{}", op)?;
+                    // Only one prefix operator can be written before an operand without brackets.
+                    write_operand(f, expr, expr.precedence() <= precedence)
+                }
+                Expr::FunctionCall { name, arguments, .. } => write!(f, "{}({})", name, list(arguments)),
+                Expr::Assignment { identifier, expr } => write!(f, "{} := {}", identifier, expr),
+                Expr::Range { start, end, step, .. } => {
+                    write_operand(f, start, start.precedence() == 0)?;
+                    write!(f, "..")?;
+                    write_operand(f, end, end.precedence() == 0)?;
+                    match step {
+                        Some(step) => write!(f, " step {}", step),
+                        None => Ok(()),
+                    }
+                }
+                Expr::Conditional {
+                    condition,
+                    then_branch,
+                    else_branch,
+                    ..
+                } => write!(f, "if {} then {} else {}", condition, then_branch, else_branch),
+                Expr::Derivative { expr, variable, at, .. } => match at {
+                    Some(at) => write!(f, "diff({}, {}, {})", expr, variable, at),
+                    None => write!(f, "diff({}, {})", expr, variable),
+                },
+                Expr::Solution { lhs, rhs, variable, guess, .. } => match guess {
+                    Some(guess) => write!(f, "solve({} = {}, {}, {})", lhs, rhs, variable, guess),
+                    None => write!(f, "solve({} = {}, {})", lhs, rhs, variable),
+                },
+                Expr::BoundOperation {
+                    operator,
+                    body,
+                    variable,
+                    lower,
+                    upper,
+                    ..
+                } => write!(f, "{}({}, {}, {}, {})", operator.name(), body, variable, lower, upper),
+                Expr::Unevaluated { source } => write!(f, "{}", source),
+            }
+        }
     }
 
     impl From<Expr> for f64 {
         fn from(val: Expr) -> Self {
-            match val {
-                Expr::Integer { value, .. } => value as f64,
-                Expr::Float { value, .. } => value,
-                Expr::BinaryOperation { value, .. } => value,
-                Expr::UnaryOperation { value, .. } => value,
-                Expr::UnboundVariable { .. } => f64::NAN,
-                Expr::Assignment { .. } => unreachable!("Can't unwrap assignment"),
+            val.value().as_f64()
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq, thiserror::Error)]
+    pub enum EvalError {
+        #[error("`{operation}` overflows {word}")]
+        Overflow { operation: String, word: WordSize },
+        #[error("division by zero in `{operation}`")]
+        DivisionByZero { operation: String },
+        #[error("`{operator}` can't combine lists of different lengths, {lhs} on the left and {rhs} on the right")]
+        LengthMismatch {
+            operator: String,
+            lhs: usize,
+            rhs: usize,
+            /// Where the operands are in the input, which is only known once the operation has been parsed.
+            lhs_span: Range<usize>,
+            rhs_span: Range<usize>,
+        },
+        #[error("`{operator}` needs shapes that fit together, found a {lhs} and a {rhs}")]
+        ShapeMismatch {
+            operator: String,
+            lhs: String,
+            rhs: String,
+            lhs_span: Range<usize>,
+            rhs_span: Range<usize>,
+        },
+        #[error("`{operator}` requires matrices or vectors, found {value}")]
+        NotAMatrix { operator: String, value: Value },
+        #[error("`=` can only be used in the first argument of `solve`, in `{function}()` use `==` to compare")]
+        MisplacedEquation { function: String },
+        #[error("can't solve the equation: {reason}")]
+        Unsolvable { reason: String },
+        #[error("can't differentiate {what}")]
+        NotDifferentiable { what: String },
+        #[error("`{function}` needs the name of a variable, found `{found}`")]
+        ExpectedVariable { function: String, found: String },
+        #[error("invalid range: {reason}")]
+        InvalidRange { reason: String },
+        #[error("`{operator}` requires integer operands, found {value}")]
+        NotAnInteger { operator: String, value: Value },
+        #[error("`{operator}` requires numbers, found {value}")]
+        NotANumber { operator: String, value: Value },
+        #[error("`{operator}` requires booleans, found {value}")]
+        NotABoolean { operator: String, value: Value },
+        #[error("cannot shift by {amount} bits")]
+        InvalidShift { amount: i128 },
+        #[error("there is no result `{reference}`, there are {count} results in this session")]
+        NoSuchResult { reference: String, count: usize },
+        #[error("unknown function `{name}`{}", .suggestion.as_ref().map(|suggestion| format!(", did you mean `{}`?", suggestion)).unwrap_or_default())]
+        UnknownFunction { name: String, suggestion: Option<String> },
+        #[error("`{function}` takes {expected}, found {found}")]
+        WrongArity { function: String, expected: String, found: usize },
+        #[error("argument {position} of `{function}` must be a {expected}, found {found}")]
+        ArgumentType {
+            function: String,
+            position: usize,
+            expected: ParameterType,
+            found: Value,
+        },
+        #[error("`{function}` failed: {message}")]
+        FunctionFailed { function: String, message: String },
+        /// A variable followed by brackets in strict mode, where the input doesn't show the multiplication.
+        #[error("implicit multiplication is not allowed in strict mode, use an explicit `*`")]
+        ImplicitMultiplication { position: Option<usize> },
+    }
+
+    impl EvalError {
+        /// Records where the operands of the failed operation are in the input.
+        fn with_spans(self, lhs: &Range<usize>, rhs: &Range<usize>) -> EvalError {
+            match self {
+                EvalError::LengthMismatch { operator, lhs: l, rhs: r, .. } => EvalError::LengthMismatch {
+                    operator,
+                    lhs: l,
+                    rhs: r,
+                    lhs_span: lhs.clone(),
+                    rhs_span: rhs.clone(),
+                },
+                EvalError::ShapeMismatch { operator, lhs: l, rhs: r, .. } => EvalError::ShapeMismatch {
+                    operator,
+                    lhs: l,
+                    rhs: r,
+                    lhs_span: lhs.clone(),
+                    rhs_span: rhs.clone(),
+                },
+                error => error,
+            }
+        }
+    }
+
+    /// A previously evaluated result, along with the input that produced it.
+    #[derive(Debug, Clone)]
+    pub struct HistoryEntry {
+        pub source: String,
+        pub expr: Expr,
+    }
+
+    /// Everything an expression can refer to while it is being evaluated.
+    #[derive(Debug, Clone, Default)]
+    pub struct Environment {
+        pub variables: HashMap<String, Expr>,
+        /// Fixed-width integer semantics, when programmer mode is enabled.
+        pub integer_mode: Option<IntegerMode>,
+        /// The results of the session so far, which `$1`, `%1`, `_` and `ans` refer back to.
+        pub history: Vec<HistoryEntry>,
+        /// Native functions, and resolvers for variables that aren't defined in the session.
+        pub registry: Registry,
+        /// The unit plain numbers are read in by the trigonometric functions.
+        pub angle_unit: AngleUnit,
+        /// Leave unknown names free, so that an expression such as `2x + 1` is a formula rather than an error.
+        pub symbolic: bool,
+        /// Reject implicit multiplication such as `2x`, including a variable followed by brackets, as in `x(2)`.
+        pub strict: bool,
+    }
+
+    impl Environment {
+        /// Looks up a result by its one-based number, as used in `$3`.
+        fn result(&self, reference: &str, number: usize) -> Result<&Expr, EvalError> {
+            number
+                .checked_sub(1)
+                .and_then(|index| self.history.get(index))
+                .map(|entry| &entry.expr)
+                .ok_or_else(|| EvalError::NoSuchResult {
+                    reference: reference.to_string(),
+                    count: self.history.len(),
+                })
+        }
+    }
+
+    impl From<HashMap<String, Expr>> for Environment {
+        fn from(variables: HashMap<String, Expr>) -> Self {
+            Environment {
+                variables,
+                ..Default::default()
             }
         }
     }
@@ -77,6 +821,47 @@ pub mod grammar {
     #[derive(Debug, Clone)]
     pub enum UnaryOperator {
         Minus,
+        BitwiseNot,
+        Not,
+    }
+
+    impl std::fmt::Display for UnaryOperator {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            match self {
+                UnaryOperator::Minus => write!(f, "-"),
+                UnaryOperator::BitwiseNot => write!(f, "~"),
+                UnaryOperator::Not => write!(f, "!"),
+            }
+        }
+    }
+
+    /// An operation over a range of values of a variable, e.g. `sum(k^2, k, 1, 10)`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum BoundOperator {
+        Integral,
+        Sum,
+        Product,
+    }
+
+    impl BoundOperator {
+        /// The function the operation is written as.
+        pub fn name(&self) -> &'static str {
+            match self {
+                BoundOperator::Integral => "integrate",
+                BoundOperator::Sum => "sum",
+                BoundOperator::Product => "prod",
+            }
+        }
+    }
+
+    impl std::fmt::Display for BoundOperator {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            match self {
+                BoundOperator::Integral => write!(f, "∫"),
+                BoundOperator::Sum => write!(f, "Σ"),
+                BoundOperator::Product => write!(f, "Π"),
+            }
+        }
     }
 
     #[derive(Debug, Clone)]
@@ -84,9 +869,55 @@ pub mod grammar {
         Add,
         Subtract,
         Multiply,
+        /// Multiplication written by juxtaposition, e.g. `2x`.
+        ImplicitMultiply,
+        /// The matrix product `a @ b`, as opposed to `*`, which multiplies element by element.
+        MatrixMultiply,
         Divide,
         Modulo,
         Power,
+        BitwiseAnd,
+        BitwiseOr,
+        BitwiseXor,
+        ShiftLeft,
+        ShiftRight,
+        RotateLeft,
+        RotateRight,
+        Equal,
+        NotEqual,
+        Less,
+        LessEqual,
+        Greater,
+        GreaterEqual,
+        And,
+        Or,
+    }
+
+    impl BinaryOperator {
+        fn is_bitwise(&self) -> bool {
+            matches!(
+                self,
+                BinaryOperator::BitwiseAnd
+                    | BinaryOperator::BitwiseOr
+                    | BinaryOperator::BitwiseXor
+                    | BinaryOperator::ShiftLeft
+                    | BinaryOperator::ShiftRight
+                    | BinaryOperator::RotateLeft
+                    | BinaryOperator::RotateRight
+            )
+        }
+
+        fn is_comparison(&self) -> bool {
+            matches!(
+                self,
+                BinaryOperator::Equal
+                    | BinaryOperator::NotEqual
+                    | BinaryOperator::Less
+                    | BinaryOperator::LessEqual
+                    | BinaryOperator::Greater
+                    | BinaryOperator::GreaterEqual
+            )
+        }
     }
 
     impl std::fmt::Display for BinaryOperator {
@@ -94,95 +925,890 @@ pub mod grammar {
             match self {
                 BinaryOperator::Add => write!(f, "+"),
                 BinaryOperator::Subtract => write!(f, "-"),
-                BinaryOperator::Multiply => write!(f, "*"),
+                BinaryOperator::Multiply | BinaryOperator::ImplicitMultiply => write!(f, "*"),
+                BinaryOperator::MatrixMultiply => write!(f, "@"),
                 BinaryOperator::Divide => write!(f, "/"),
                 BinaryOperator::Modulo => write!(f, "%"),
                 BinaryOperator::Power => write!(f, "^"),
+                BinaryOperator::BitwiseAnd => write!(f, "&"),
+                BinaryOperator::BitwiseOr => write!(f, "|"),
+                BinaryOperator::BitwiseXor => write!(f, "xor"),
+                BinaryOperator::ShiftLeft => write!(f, "<<"),
+                BinaryOperator::ShiftRight => write!(f, ">>"),
+                BinaryOperator::RotateLeft => write!(f, "rol"),
+                BinaryOperator::RotateRight => write!(f, "ror"),
+                BinaryOperator::Equal => write!(f, "=="),
+                BinaryOperator::NotEqual => write!(f, "!="),
+                BinaryOperator::Less => write!(f, "<"),
+                BinaryOperator::LessEqual => write!(f, "<="),
+                BinaryOperator::Greater => write!(f, ">"),
+                BinaryOperator::GreaterEqual => write!(f, ">="),
+                BinaryOperator::And => write!(f, "&&"),
+                BinaryOperator::Or => write!(f, "||"),
             }
         }
     }
 
-    pub fn parse_equation(input: &str) -> Result<Pairs<Rule>, pest::error::Error<Rule>> {
+    pub fn parse_equation(input: &str) -> Result<Pairs<'_, Rule>, pest::error::Error<Rule>> {
         CalculatorParser::parse(Rule::equation, input)
     }
 
-    pub fn parse_partial_term(input: &str) -> Result<Pairs<Rule>, pest::error::Error<Rule>> {
+    pub fn parse_partial_term(input: &str) -> Result<Pairs<'_, Rule>, pest::error::Error<Rule>> {
         CalculatorParser::parse(Rule::partial_term, input)
     }
 
-    pub fn eval(pairs: Pairs<Rule>, variables: &HashMap<String, Expr>) -> Expr {
-        PRATT_PARSER
-            .map_primary(|primary| match primary.as_rule() {
-                Rule::integer => Expr::Integer {
-                    value: primary.as_str().parse::<i32>().unwrap(),
-                    variable: None,
-                },
-                Rule::float => Expr::Float {
-                    value: primary.as_str().parse::<f64>().unwrap(),
-                    variable: None,
-                },
-                Rule::variable => {
-                    let variable_name = primary.as_str().to_string();
-                    if let Some(expr) = variables.get(&variable_name) {
-                        let value: f64 = expr.clone().into();
-                        Expr::Float {
-                            value,
-                            variable: Some(Variable {
-                                name: variable_name,
-                                expr: Box::new(expr.clone()),
-                            }),
+    pub fn eval(pairs: Pairs<Rule>, environment: &Environment) -> Result<Expr, EvalError> {
+        eval_with(&PRATT_PARSER, pairs, environment)
+    }
+
+    /// Evaluates the expression using the precedence rules from before exponentiation became
+    /// right-associative and started binding tighter than unary minus.
+    pub fn eval_legacy(pairs: Pairs<Rule>, environment: &Environment) -> Result<Expr, EvalError> {
+        eval_with(&LEGACY_PRATT_PARSER, pairs, environment)
+    }
+
+    fn eval_with(parser: &PrattParser<Rule>, pairs: Pairs<Rule>, environment: &Environment) -> Result<Expr, EvalError> {
+        eval_bracketed(parser, pairs, environment).map(|expr| in_word(expr, environment))
+    }
+
+    /// Evaluates an expression in brackets, whose value is a literal as much as the one inside is.
+    fn eval_bracketed(parser: &PrattParser<Rule>, pairs: Pairs<Rule>, environment: &Environment) -> Result<Expr, EvalError> {
+        let mut pairs = pairs.collect::<Vec<_>>();
+        match pairs.last().map(|pair| pair.as_rule()) {
+            Some(Rule::ternary) => {
+                let mut branches = pairs.pop().unwrap().into_inner();
+                let condition = eval_operations(parser, pairs, environment)?;
+                eval_conditional(parser, condition, branches.next().unwrap(), branches.next().unwrap(), environment)
+            }
+            Some(Rule::range) => {
+                let mut bounds = pairs.pop().unwrap().into_inner().filter(|pair| pair.as_rule() == Rule::range_bound);
+                let start = eval_operations(parser, pairs, environment)?;
+                let end = eval_operations(parser, bounds.next().unwrap().into_inner().collect(), environment)?;
+                let step = bounds
+                    .next()
+                    .map(|step| eval_operations(parser, step.into_inner().collect(), environment))
+                    .transpose()?;
+                eval_range(start, end, step)
+            }
+            _ => eval_operations(parser, pairs, environment),
+        }
+    }
+
+    /// A leaf holding the value of the named variable, which remembers the expression it came from.
+    fn variable_reference(name: String, expr: &Expr) -> Expr {
+        let variable = Some(Variable {
+            name,
+            expr: Box::new(expr.clone()),
+        });
+        match Expr::literal(expr.value()) {
+            Expr::Integer { value, radix, .. } => Expr::Integer { value, radix, variable },
+            Expr::Word { value, radix, .. } => Expr::Word { value, radix, variable },
+            Expr::Float { value, .. } => Expr::Float { value, variable },
+            Expr::Boolean { value, .. } => Expr::Boolean { value, variable },
+            Expr::String { value, .. } => Expr::String { value, variable },
+            Expr::List { elements, .. } => Expr::List { elements, variable },
+            _ => unreachable!("Expr::literal only returns leaves"),
+        }
+    }
+
+    /// Looks up a variable: user variables first, so that a constant can be shadowed, then `ans`, the
+    /// constants, and finally the registered resolvers.
+    fn eval_variable(name: &str, environment: &Environment) -> Result<Expr, EvalError> {
+        if let Some(expr) = environment.variables.get(name) {
+            Ok(variable_reference(name.to_string(), &bind_formula(expr, environment)?))
+        } else if name == "ans" {
+            let expr = environment.result(name, environment.history.len())?;
+            Ok(variable_reference(format!("${}", environment.history.len()), expr))
+        } else if let Some(constant) = constants::lookup(name) {
+            let expr = Expr::Float {
+                value: constant.value,
+                variable: None,
+            };
+            Ok(variable_reference(name.to_string(), &expr))
+        } else if let Some(value) = environment.registry.resolve(name) {
+            Ok(variable_reference(name.to_string(), &Expr::literal(value)))
+        } else {
+            if environment.symbolic {
+                return Ok(Expr::FreeVariable { name: name.to_string() });
+            }
+            let candidates = environment.variables.keys().map(String::as_str).chain(constants::names().map(|name| name as &str));
+            Ok(Expr::UnboundVariable {
+                name: name.to_string(),
+                suggestion: did_you_mean(name, candidates),
+            })
+        }
+    }
+
+    /// Calls a native function. A name that isn't a function but is followed by a single bracketed
+    /// argument, such as `x(y + 1)`, is implicit multiplication instead.
+    fn eval_function_call(parser: &PrattParser<Rule>, pair: Pair<Rule>, environment: &Environment) -> Result<Expr, EvalError> {
+        let mut inner_pairs = pair.into_inner();
+        let name = inner_pairs.next().unwrap().as_str().to_string();
+        let mut inner_pairs = inner_pairs.collect::<Vec<_>>();
+        let is_equation = |pair: &Pair<Rule>| pair.as_rule() == Rule::equals;
+        let equation = match inner_pairs.iter().position(is_equation) {
+            Some(1) if name == "solve" && inner_pairs.iter().filter(|pair| is_equation(pair)).count() == 1 => Some(inner_pairs.remove(1)),
+            Some(_) => return Err(EvalError::MisplacedEquation { function: name }),
+            None => None,
+        };
+        let spans = inner_pairs
+            .iter()
+            .map(|pair| pair.as_span().start()..pair.as_span().start() + pair.as_str().trim_end().len())
+            .collect::<Vec<_>>();
+        let arguments = inner_pairs
+            .into_iter()
+            .map(|pair| eval_with(parser, pair.into_inner(), environment))
+            .collect::<Result<Vec<_>, _>>()?;
+        let rhs = equation
+            .map(|pair| eval_with(parser, pair.into_inner().next().unwrap().into_inner(), environment))
+            .transpose()?;
+        if name == "diff" {
+            return symbolic::eval_diff(arguments, environment);
+        }
+        // `solve(a, b)` with a matrix is a system of linear equations, and anything else is solved numerically.
+        if name == "solve" && (rhs.is_some() || !matches!(arguments.first().map(Expr::value), Some(Value::List(_)))) {
+            return solve::eval_solve(arguments, rhs, environment);
+        }
+        // `sum(k^2, k, 1, n)` adds up over a range, while `sum` with any other arguments is the statistic. A
+        // name second always makes it a range, even one the first argument doesn't use, as in `sum(1, k, 1, n)`.
+        let bound_variable = arguments.len() == 4 && symbolic::variable_name(&name, &arguments[1]).is_ok();
+        let operator = match name.as_str() {
+            "integrate" => Some(BoundOperator::Integral),
+            "sum" if bound_variable => Some(BoundOperator::Sum),
+            "prod" => Some(BoundOperator::Product),
+            _ => None,
+        };
+        if let Some(operator) = operator {
+            return calculus::eval_bound_operation(operator, arguments, environment);
+        }
+        call_function(name, arguments, &spans, environment)
+    }
+
+    /// Calls the function with arguments that have already been evaluated, whose spans in the input are
+    /// given for errors that point at them.
+    fn call_function(name: String, arguments: Vec<Expr>, spans: &[Range<usize>], environment: &Environment) -> Result<Expr, EvalError> {
+        let Some(function) = environment.registry.function(&name) else {
+            let suggestion = did_you_mean(&name, environment.registry.function_names());
+            let lhs = eval_variable(&name, environment)?;
+            return match (arguments.len(), &lhs, suggestion) {
+                (_, Expr::UnboundVariable { .. }, Some(suggestion)) => Err(EvalError::UnknownFunction {
+                    name,
+                    suggestion: Some(suggestion),
+                }),
+                // `x(2)` reads as a call, so that strict mode has to catch it here rather than when parsing.
+                (1, _, _) if environment.strict => Err(EvalError::ImplicitMultiplication {
+                    position: spans.first().map(|span| span.start.saturating_sub(1)),
+                }),
+                (1, _, _) => {
+                    let rhs = arguments.into_iter().next().unwrap();
+                    let value = apply_binary(&BinaryOperator::ImplicitMultiply, &lhs.value(), &rhs.value(), environment.integer_mode)?;
+                    Ok(Expr::BinaryOperation {
+                        lhs: Box::new(lhs),
+                        op: BinaryOperator::ImplicitMultiply,
+                        rhs: Box::new(rhs),
+                        value,
+                    })
+                }
+                _ => Err(EvalError::UnknownFunction { name, suggestion: None }),
+            };
+        };
+
+        // A plain number passed to `sin`, `cos` or `tan` is read in the angle mode, while an angle such as
+        // `30deg` is already in radians.
+        let angle = TRIGONOMETRIC_FUNCTIONS
+            .contains(&name.as_str())
+            .then_some(environment.angle_unit)
+            .filter(|_| !arguments.iter().all(Expr::is_angle));
+        let values = arguments
+            .iter()
+            .map(|argument| match (angle, argument.value()) {
+                (Some(unit), value) if !argument.is_angle() && matches!(value, Value::Integer(_) | Value::Word(_) | Value::Float(_)) => {
+                    Value::Float(unit.to_radians(value.as_f64()))
+                }
+                (_, value) => value,
+            })
+            .collect::<Vec<_>>();
+        let value = function.call(&values).map_err(|e| match e {
+            CallError::Arity { expected, found } => EvalError::WrongArity {
+                function: name.clone(),
+                expected,
+                found,
+            },
+            CallError::ArgumentType { position, expected, found } => EvalError::ArgumentType {
+                function: name.clone(),
+                position,
+                expected,
+                found,
+            },
+            CallError::ShapeMismatch {
+                lhs,
+                rhs,
+                lhs_shape,
+                rhs_shape,
+            } => EvalError::ShapeMismatch {
+                operator: format!("{}()", name),
+                lhs: lhs_shape,
+                rhs: rhs_shape,
+                lhs_span: spans.get(lhs - 1).cloned().unwrap_or_default(),
+                rhs_span: spans.get(rhs - 1).cloned().unwrap_or_default(),
+            },
+            CallError::Failed(message) => EvalError::FunctionFailed {
+                function: name.clone(),
+                message,
+            },
+        })?;
+        Ok(Expr::FunctionCall {
+            name,
+            arguments,
+            angle,
+            value,
+        })
+    }
+
+    /// A stored formula with the values its free variables have now, or any other stored expression as it is.
+    fn bind_formula(expr: &Expr, environment: &Environment) -> Result<Expr, EvalError> {
+        if expr.free_variables().is_empty() {
+            Ok(expr.clone())
+        } else {
+            reevaluate(expr, environment)
+        }
+    }
+
+    /// Calculates the value of every node again, for a tree that was built rather than parsed, such as a
+    /// derivative. Free variables take their value from the environment if it has one.
+    pub fn reevaluate(expr: &Expr, environment: &Environment) -> Result<Expr, EvalError> {
+        let operand = |expr: &Expr| reevaluate(expr, environment).map(Box::new);
+        match expr {
+            Expr::FreeVariable { name } => match environment.variables.get(name) {
+                Some(value) => Ok(variable_reference(name.clone(), &bind_formula(value, environment)?)),
+                None => Ok(expr.clone()),
+            },
+            Expr::Integer { variable: Some(variable), .. }
+            | Expr::Word { variable: Some(variable), .. }
+            | Expr::Float { variable: Some(variable), .. }
+            | Expr::List { variable: Some(variable), .. }
+                if !variable.expr.free_variables().is_empty() =>
+            {
+                Ok(variable_reference(variable.name.clone(), &reevaluate(&variable.expr, environment)?))
+            }
+            Expr::BinaryOperation { lhs, op, rhs, .. } => {
+                let (lhs, rhs) = (operand(lhs)?, operand(rhs)?);
+                let value = apply_binary(op, &lhs.value(), &rhs.value(), environment.integer_mode)?;
+                Ok(Expr::BinaryOperation {
+                    lhs,
+                    op: op.clone(),
+                    rhs,
+                    value,
+                })
+            }
+            Expr::UnaryOperation { op, expr, .. } => {
+                let expr = operand(expr)?;
+                let value = apply_unary(op, &expr.value(), environment.integer_mode)?;
+                Ok(Expr::UnaryOperation { op: op.clone(), expr, value })
+            }
+            Expr::FunctionCall { name, arguments, .. } => {
+                let arguments = arguments.iter().map(|argument| reevaluate(argument, environment)).collect::<Result<Vec<_>, _>>()?;
+                call_function(name.clone(), arguments, &[], environment)
+            }
+            Expr::List { elements, variable: None } => Ok(Expr::List {
+                elements: elements.iter().map(|element| reevaluate(element, environment)).collect::<Result<Vec<_>, _>>()?,
+                variable: None,
+            }),
+            Expr::BoundOperation {
+                operator,
+                body,
+                variable,
+                lower,
+                upper,
+                ..
+            } => {
+                // The variable only has a value inside the operation, whatever it is outside.
+                let mut scoped = environment.clone();
+                scoped.variables.remove(variable);
+                let body = reevaluate(body, &scoped)?;
+                calculus::bind(*operator, body, variable.clone(), reevaluate(lower, environment)?, reevaluate(upper, environment)?, environment)
+            }
+            // Leaves already hold their value, and built trees only copy other expressions that don't
+            // depend on a free variable.
+            expr => Ok(expr.clone()),
+        }
+    }
+
+    /// The span of a bracketed expression, including the brackets, which aren't part of the pair itself.
+    fn bracketed(span: pest::Span) -> Range<usize> {
+        let input = span.get_input();
+        let start = input[..span.start()].trim_end().len().saturating_sub(1);
+        let end = span.end() + input[span.end()..].len() - input[span.end()..].trim_start().len() + 1;
+        start..end.min(input.len())
+    }
+
+    /// The most numbers a range may have, so that a typo such as `1..1e9` doesn't exhaust the memory.
+    const MAX_RANGE_LENGTH: usize = 1_000_000;
+
+    /// Lists the numbers from the start to the end inclusive. Without a step, it counts up or down by one.
+    fn eval_range(start: Expr, end: Expr, step: Option<Expr>) -> Result<Expr, EvalError> {
+        let (from, to) = (number_operand(&"..", &start.value())?, number_operand(&"..", &end.value())?);
+        let by = match &step {
+            Some(step) => number_operand(&"step", &step.value())?,
+            None if to < from => -1.0,
+            None => 1.0,
+        };
+        if by == 0.0 || !by.is_finite() || !from.is_finite() || !to.is_finite() {
+            return Err(EvalError::InvalidRange {
+                reason: format!("can't count from {} to {} in steps of {}", from, to, by),
+            });
+        }
+
+        // Allow for rounding, so that `0..1 step 0.1` ends at 1.
+        let steps = ((to - from) / by + 1e-9).floor();
+        let length = if steps < 0.0 { 0.0 } else { steps + 1.0 };
+        if length > MAX_RANGE_LENGTH as f64 {
+            return Err(EvalError::InvalidRange {
+                reason: format!("{}..{} has more than {} numbers", from, to, MAX_RANGE_LENGTH),
+            });
+        }
+        let integral = from.fract() == 0.0 && by.fract() == 0.0;
+        let values = (0..length as usize)
+            .map(|index| from + index as f64 * by)
+            .map(|value| if integral { Value::Integer(value as i128) } else { Value::Float(value) })
+            .collect();
+        Ok(Expr::Range {
+            start: Box::new(start),
+            end: Box::new(end),
+            step: step.map(Box::new),
+            value: Value::List(values),
+        })
+    }
+
+    /// Evaluates whichever branch the condition selects, leaving the other one unevaluated.
+    fn eval_conditional(
+        parser: &PrattParser<Rule>,
+        condition: Expr,
+        then_pair: Pair<Rule>,
+        else_pair: Pair<Rule>,
+        environment: &Environment,
+    ) -> Result<Expr, EvalError> {
+        let taken = match condition.value() {
+            Value::Bool(taken) => taken,
+            value => {
+                return Err(EvalError::NotABoolean {
+                    operator: "if".to_string(),
+                    value,
+                })
+            }
+        };
+        let unevaluated = |pair: Pair<Rule>| Expr::Unevaluated {
+            source: pair.as_str().trim().to_string(),
+        };
+        let (then_branch, else_branch, value) = if taken {
+            let then_branch = eval_with(parser, then_pair.into_inner(), environment)?;
+            let value = then_branch.value();
+            (then_branch, unevaluated(else_pair), value)
+        } else {
+            let else_branch = eval_with(parser, else_pair.into_inner(), environment)?;
+            let value = else_branch.value();
+            (unevaluated(then_pair), else_branch, value)
+        };
+        Ok(Expr::Conditional {
+            condition: Box::new(condition),
+            then_branch: Box::new(then_branch),
+            else_branch: Box::new(else_branch),
+            value,
+        })
+    }
+
+    /// In programmer mode, a literal is a bit pattern in the word, so `0xFF` is -1 as an i8. Literals are
+    /// read in the word where they are used rather than when they are parsed, as a shift amount isn't.
+    fn in_word(expr: Expr, environment: &Environment) -> Expr {
+        match (expr, environment.integer_mode) {
+            (Expr::Integer { value, radix, variable: None }, Some(mode)) => Expr::Word {
+                value: Word::new(value, mode.word),
+                radix,
+                variable: None,
+            },
+            (expr, _) => expr,
+        }
+    }
+
+    fn eval_operations(parser: &PrattParser<Rule>, pairs: Vec<Pair<Rule>>, environment: &Environment) -> Result<Expr, EvalError> {
+        // Each node is paired with where it is in the input, for errors that point at the operands.
+        parser
+            .map_primary(|primary| {
+                let span = match primary.as_rule() {
+                    Rule::expr => bracketed(primary.as_span()),
+                    _ => primary.as_span().start()..primary.as_span().end(),
+                };
+                let expr = match primary.as_rule() {
+                    Rule::integer | Rule::hex_integer | Rule::binary_integer | Rule::octal_integer => {
+                        let radix = match primary.as_rule() {
+                            Rule::hex_integer => Radix::Hexadecimal,
+                            Rule::binary_integer => Radix::Binary,
+                            Rule::octal_integer => Radix::Octal,
+                            _ => Radix::Decimal,
+                        };
+                        // Literals are only read in the word where they are used, see `in_word`. One too big
+                        // for an `i128` can still be the bit pattern of a `u128`, though.
+                        match (parse_integer(primary.as_str(), radix), environment.integer_mode) {
+                            (literal @ Expr::Float { .. }, Some(mode)) => {
+                                Ok(u128::from_str_radix(&literal_digits(primary.as_str(), radix), radix.base()).map_or(literal, |bits| {
+                                    Expr::Word {
+                                        value: Word::from_bits(bits, mode.word),
+                                        radix,
+                                        variable: None,
+                                    }
+                                }))
+                            }
+                            (literal, _) => Ok(literal),
                         }
-                    } else {
-                        Expr::UnboundVariable { name: variable_name }
                     }
-                }
-                Rule::expr => eval(primary.into_inner(), variables),
-                rule => unreachable!("Expr::parse expected atom, found {:?}", rule),
+                    Rule::float => Ok(Expr::Float {
+                        value: primary.as_str().replace('_', "").parse::<f64>().unwrap(),
+                        variable: None,
+                    }),
+                    Rule::angle => {
+                        let mut inner_pairs = primary.into_inner();
+                        let number = inner_pairs.next().unwrap();
+                        let amount = match number.as_rule() {
+                            Rule::float => number.as_str().replace('_', "").parse::<f64>().unwrap(),
+                            Rule::hex_integer => parse_integer(number.as_str(), Radix::Hexadecimal).value().as_f64(),
+                            Rule::binary_integer => parse_integer(number.as_str(), Radix::Binary).value().as_f64(),
+                            Rule::octal_integer => parse_integer(number.as_str(), Radix::Octal).value().as_f64(),
+                            _ => parse_integer(number.as_str(), Radix::Decimal).value().as_f64(),
+                        };
+                        let unit = inner_pairs.next().unwrap().as_str().parse::<AngleUnit>().unwrap();
+                        Ok(Expr::Angle {
+                            amount,
+                            unit,
+                            value: unit.to_radians(amount),
+                        })
+                    }
+                    Rule::boolean => Ok(Expr::Boolean {
+                        value: primary.as_str() == "true",
+                        variable: None,
+                    }),
+                    Rule::conditional => {
+                        let mut branches = primary.into_inner().filter(|pair| pair.as_rule() == Rule::expr);
+                        let condition = eval_with(parser, branches.next().unwrap().into_inner(), environment)?;
+                        eval_conditional(parser, condition, branches.next().unwrap(), branches.next().unwrap(), environment)
+                    }
+                    Rule::history_reference => {
+                        let reference = primary.as_str();
+                        let number = match reference[1..].parse::<usize>() {
+                            Ok(number) => number,
+                            Err(_) => environment.history.len(),
+                        };
+                        let expr = environment.result(reference, number)?;
+                        Ok(variable_reference(format!("${}", number), expr))
+                    }
+                    Rule::variable => eval_variable(primary.as_str(), environment),
+                    Rule::function_call => eval_function_call(parser, primary, environment),
+                    Rule::string => {
+                        let text = primary.as_str();
+                        Ok(Expr::String {
+                            value: text[1..text.len() - 1].to_string(),
+                            variable: None,
+                        })
+                    }
+                    Rule::list => Ok(Expr::List {
+                        elements: primary
+                            .into_inner()
+                            .map(|pair| eval_with(parser, pair.into_inner(), environment))
+                            .collect::<Result<Vec<_>, _>>()?,
+                        variable: None,
+                    }),
+                    // Brackets are left to the operation they are in, so `1 << (200)` is a shift by 200 too.
+                    Rule::expr => eval_bracketed(parser, primary.into_inner(), environment),
+                    rule => unreachable!("Expr::parse expected atom, found {:?}", rule),
+                };
+                expr.map(|expr| (expr, span))
             })
             .map_prefix(|op, expr| {
-                let expr_value: f64 = expr.clone().into();
-                match op.as_rule() {
-                    Rule::unary_minus => Expr::UnaryOperation {
-                        op: UnaryOperator::Minus,
-                        expr: Box::new(expr),
-                        value: -expr_value,
-                    },
+                let (expr, span) = expr?;
+                let expr = in_word(expr, environment);
+                let span = op.as_span().start()..span.end;
+                let op = match op.as_rule() {
+                    Rule::unary_minus => UnaryOperator::Minus,
+                    Rule::bitwise_not => UnaryOperator::BitwiseNot,
+                    Rule::not => UnaryOperator::Not,
                     rule => unreachable!("Expr::parse expected prefix, found {:?}", rule),
-                }
+                };
+                let value = apply_unary(&op, &expr.value(), environment.integer_mode)?;
+                let expr = Expr::UnaryOperation {
+                    op,
+                    expr: Box::new(expr),
+                    value,
+                };
+                Ok((expr, span))
             })
             .map_infix(|lhs, op, rhs| {
-                let lhs_value: f64 = lhs.clone().into();
-                let rhs_value: f64 = rhs.clone().into();
-                let (op, value) = match op.as_rule() {
-                    Rule::add => (BinaryOperator::Add, lhs_value + rhs_value),
-                    Rule::subtract => (BinaryOperator::Subtract, lhs_value - rhs_value),
-                    Rule::multiply => (BinaryOperator::Multiply, lhs_value * rhs_value),
-                    Rule::divide => (BinaryOperator::Divide, lhs_value / rhs_value),
-                    Rule::modulo => (BinaryOperator::Modulo, lhs_value % rhs_value),
-                    Rule::power => (BinaryOperator::Power, lhs_value.powf(rhs_value)),
+                let ((lhs, lhs_span), (rhs, rhs_span)) = (lhs?, rhs?);
+                let op = match op.as_rule() {
+                    Rule::add => BinaryOperator::Add,
+                    Rule::subtract => BinaryOperator::Subtract,
+                    Rule::multiply => BinaryOperator::Multiply,
+                    Rule::matrix_multiply => BinaryOperator::MatrixMultiply,
+                    Rule::implicit_multiply => BinaryOperator::ImplicitMultiply,
+                    Rule::divide => BinaryOperator::Divide,
+                    Rule::modulo => BinaryOperator::Modulo,
+                    Rule::power => BinaryOperator::Power,
+                    Rule::bitwise_and => BinaryOperator::BitwiseAnd,
+                    Rule::bitwise_or => BinaryOperator::BitwiseOr,
+                    Rule::bitwise_xor => BinaryOperator::BitwiseXor,
+                    Rule::shift_left => BinaryOperator::ShiftLeft,
+                    Rule::shift_right => BinaryOperator::ShiftRight,
+                    Rule::rotate_left => BinaryOperator::RotateLeft,
+                    Rule::rotate_right => BinaryOperator::RotateRight,
+                    Rule::equal => BinaryOperator::Equal,
+                    Rule::not_equal => BinaryOperator::NotEqual,
+                    Rule::less => BinaryOperator::Less,
+                    Rule::less_equal => BinaryOperator::LessEqual,
+                    Rule::greater => BinaryOperator::Greater,
+                    Rule::greater_equal => BinaryOperator::GreaterEqual,
+                    Rule::and => BinaryOperator::And,
+                    Rule::or => BinaryOperator::Or,
                     rule => unreachable!("Expr::parse expected infix operation, found {:?}", rule),
                 };
-                Expr::BinaryOperation {
+                // A shift amount counts bits rather than being a bit pattern, so a literal one isn't read in
+                // the word, and `1 << 200` is checked as a shift by 200.
+                let lhs = in_word(lhs, environment);
+                let rhs = match op {
+                    BinaryOperator::ShiftLeft | BinaryOperator::ShiftRight | BinaryOperator::RotateLeft | BinaryOperator::RotateRight => rhs,
+                    _ => in_word(rhs, environment),
+                };
+                let (lhs_value, rhs_value) = angle_operands(&op, &lhs, &rhs, environment.angle_unit);
+                let value = apply_binary(&op, &lhs_value, &rhs_value, environment.integer_mode)
+                    .map_err(|e| e.with_spans(&lhs_span, &rhs_span))?;
+                let expr = Expr::BinaryOperation {
                     lhs: Box::new(lhs),
                     op,
                     rhs: Box::new(rhs),
                     value,
-                }
+                };
+                Ok((expr, lhs_span.start..rhs_span.end))
             })
-            .parse(pairs)
+            .parse(pairs.into_iter())
+            .map(|(expr, _)| expr)
+    }
+
+    /// The values of the operands. When an angle is added to, subtracted from or compared with a plain
+    /// number, the number is read in the angle mode, so that `asin(1) - 30` is 60 degrees in degree mode.
+    fn angle_operands(op: &BinaryOperator, lhs: &Expr, rhs: &Expr, unit: AngleUnit) -> (Value, Value) {
+        let mixes = matches!(op, BinaryOperator::Add | BinaryOperator::Subtract) || op.is_comparison();
+        let to_radians = |expr: &Expr| match expr.value() {
+            Value::Integer(_) | Value::Word(_) | Value::Float(_) => Value::Float(unit.to_radians(expr.value().as_f64())),
+            value => value,
+        };
+        match (lhs.is_angle(), rhs.is_angle()) {
+            (true, false) if mixes => (lhs.value(), to_radians(rhs)),
+            (false, true) if mixes => (to_radians(lhs), rhs.value()),
+            _ => (lhs.value(), rhs.value()),
+        }
+    }
+
+    pub fn apply_unary(op: &UnaryOperator, value: &Value, integer_mode: Option<IntegerMode>) -> Result<Value, EvalError> {
+        match (op, value, integer_mode) {
+            (op, Value::List(values), _) => values
+                .iter()
+                .map(|value| apply_unary(op, value, integer_mode))
+                .collect::<Result<Vec<_>, _>>()
+                .map(Value::List),
+            (UnaryOperator::Minus, Value::Integer(_) | Value::Word(_), Some(mode)) => {
+                let word = word_operand(op, value, mode)?;
+                let negated = match word.to_i128() {
+                    Some(value) if mode.word.signed => mode.fit(value.checked_neg(), value.wrapping_neg()),
+                    _ => mode.fit_unsigned((word.bits == 0).then_some(0), word.bits.wrapping_neg()),
+                };
+                negated.map(Value::Word).ok_or_else(|| EvalError::Overflow {
+                    operation: format!("-{}", word),
+                    word: mode.word,
+                })
+            }
+            (UnaryOperator::Minus, value, _) => Ok(Value::Float(-number_operand(op, value)?)),
+            (UnaryOperator::Not, Value::Bool(value), _) => Ok(Value::Bool(!value)),
+            (UnaryOperator::Not, value, _) => Err(EvalError::NotABoolean {
+                operator: op.to_string(),
+                value: value.clone(),
+            }),
+            (UnaryOperator::BitwiseNot, value, Some(mode)) => {
+                let word = word_operand(op, value, mode)?;
+                Ok(Value::Word(Word::from_bits(!word.bits, mode.word)))
+            }
+            (UnaryOperator::BitwiseNot, value, None) => {
+                let word = IntegerMode::DEFAULT.word;
+                Ok(Value::Integer(word.wrap(!integer_operand(op, value)?)))
+            }
+        }
+    }
+
+    pub fn apply_binary(
+        op: &BinaryOperator,
+        lhs: &Value,
+        rhs: &Value,
+        integer_mode: Option<IntegerMode>,
+    ) -> Result<Value, EvalError> {
+        if let BinaryOperator::MatrixMultiply = op {
+            return matrix::product(lhs, rhs).map_err(|e| match e {
+                ProductError::NotAnArray(value) => EvalError::NotAMatrix {
+                    operator: op.to_string(),
+                    value,
+                },
+                ProductError::Shapes(lhs, rhs) => EvalError::ShapeMismatch {
+                    operator: op.to_string(),
+                    lhs,
+                    rhs,
+                    lhs_span: 0..0,
+                    rhs_span: 0..0,
+                },
+            });
+        }
+
+        // Operators apply element-wise to lists, with a number combined with every element.
+        match (lhs, rhs) {
+            (Value::List(lhs), Value::List(rhs)) if lhs.len() != rhs.len() => {
+                return Err(EvalError::LengthMismatch {
+                    operator: op.to_string(),
+                    lhs: lhs.len(),
+                    rhs: rhs.len(),
+                    lhs_span: 0..0,
+                    rhs_span: 0..0,
+                })
+            }
+            (Value::List(lhs), Value::List(rhs)) => {
+                return broadcast(lhs.iter().zip(rhs), |(lhs, rhs)| apply_binary(op, lhs, rhs, integer_mode))
+            }
+            (Value::List(lhs), rhs) => return broadcast(lhs.iter(), |lhs| apply_binary(op, lhs, rhs, integer_mode)),
+            (lhs, Value::List(rhs)) => return broadcast(rhs.iter(), |rhs| apply_binary(op, lhs, rhs, integer_mode)),
+            _ => {}
+        }
+
+        // Outside of programmer mode, bitwise operators work on 64-bit signed integers.
+        if op.is_bitwise() {
+            let mode = integer_mode.unwrap_or(IntegerMode::DEFAULT);
+            let result = apply_bitwise(op, word_operand(op, lhs, mode)?, rhs, mode)?;
+            return Ok(match integer_mode {
+                Some(_) => Value::Word(result),
+                None => Value::Integer(result.to_i128().expect("64-bit words fit in an i128")),
+            });
+        }
+
+        match (op, lhs, rhs) {
+            (BinaryOperator::And | BinaryOperator::Or, Value::Bool(lhs), Value::Bool(rhs)) => {
+                return Ok(Value::Bool(if let BinaryOperator::And = op { *lhs && *rhs } else { *lhs || *rhs }));
+            }
+            (BinaryOperator::And | BinaryOperator::Or, Value::Bool(_), value) | (BinaryOperator::And | BinaryOperator::Or, value, _) => {
+                return Err(EvalError::NotABoolean {
+                    operator: op.to_string(),
+                    value: value.clone(),
+                })
+            }
+            (BinaryOperator::Equal, Value::Bool(lhs), Value::Bool(rhs)) => return Ok(Value::Bool(lhs == rhs)),
+            (BinaryOperator::NotEqual, Value::Bool(lhs), Value::Bool(rhs)) => return Ok(Value::Bool(lhs != rhs)),
+            (BinaryOperator::Equal, Value::String(lhs), Value::String(rhs)) => return Ok(Value::Bool(lhs == rhs)),
+            (BinaryOperator::NotEqual, Value::String(lhs), Value::String(rhs)) => return Ok(Value::Bool(lhs != rhs)),
+            _ => {}
+        }
+
+        if op.is_comparison() {
+            let ordering = match (integer_order(lhs), integer_order(rhs)) {
+                (Some(lhs), Some(rhs)) => Some(lhs.cmp(&rhs)),
+                _ => number_operand(op, lhs)?.partial_cmp(&number_operand(op, rhs)?),
+            };
+            // Comparisons involving NaN are all false, except for `!=`.
+            let result = match ordering {
+                Some(ordering) => match op {
+                    BinaryOperator::Equal => ordering.is_eq(),
+                    BinaryOperator::NotEqual => ordering.is_ne(),
+                    BinaryOperator::Less => ordering.is_lt(),
+                    BinaryOperator::LessEqual => ordering.is_le(),
+                    BinaryOperator::Greater => ordering.is_gt(),
+                    _ => ordering.is_ge(),
+                },
+                None => matches!(op, BinaryOperator::NotEqual),
+            };
+            return Ok(Value::Bool(result));
+        }
+
+        match (lhs, rhs, integer_mode) {
+            (Value::Integer(_) | Value::Word(_), Value::Integer(_) | Value::Word(_), Some(mode)) => {
+                apply_integer(op, word_operand(op, lhs, mode)?, word_operand(op, rhs, mode)?, mode)
+            }
+            _ => {
+                let (lhs, rhs) = (number_operand(op, lhs)?, number_operand(op, rhs)?);
+                let value = match op {
+                    BinaryOperator::Add => lhs + rhs,
+                    BinaryOperator::Subtract => lhs - rhs,
+                    BinaryOperator::Multiply | BinaryOperator::ImplicitMultiply => lhs * rhs,
+                    BinaryOperator::Divide => lhs / rhs,
+                    BinaryOperator::Modulo => lhs % rhs,
+                    BinaryOperator::Power => lhs.powf(rhs),
+                    _ => unreachable!("Bitwise, comparison and logical operators are handled above"),
+                };
+                Ok(Value::Float(value))
+            }
+        }
+    }
+
+    fn broadcast<T>(elements: impl Iterator<Item = T>, apply: impl Fn(T) -> Result<Value, EvalError>) -> Result<Value, EvalError> {
+        elements.map(apply).collect::<Result<Vec<_>, _>>().map(Value::List)
+    }
+
+    fn integer_operand(op: &dyn std::fmt::Display, value: &Value) -> Result<i128, EvalError> {
+        value.as_integer().ok_or_else(|| EvalError::NotAnInteger {
+            operator: op.to_string(),
+            value: value.clone(),
+        })
+    }
+
+    /// The operand in the word, wrapping around if it came from a different word or isn't a word at all.
+    fn word_operand(op: &dyn std::fmt::Display, value: &Value, mode: IntegerMode) -> Result<Word, EvalError> {
+        match value {
+            Value::Word(word) => Ok(word.resize(mode.word)),
+            value => integer_operand(op, value).map(|value| Word::new(value, mode.word)),
+        }
+    }
+
+    /// Orders integers and words by value, with the upper half of a `u128` above every `i128`: the sign
+    /// comes first, then the two's complement bit pattern.
+    fn integer_order(value: &Value) -> Option<(bool, u128)> {
+        match value {
+            Value::Integer(value) => Some((*value >= 0, *value as u128)),
+            Value::Word(word) => match word.to_i128() {
+                Some(value) => Some((value >= 0, value as u128)),
+                None => Some((true, word.bits)),
+            },
+            _ => None,
+        }
+    }
+
+    fn number_operand(op: &dyn std::fmt::Display, value: &Value) -> Result<f64, EvalError> {
+        match value {
+            Value::Bool(_) | Value::String(_) | Value::List(_) => Err(EvalError::NotANumber {
+                operator: op.to_string(),
+                value: value.clone(),
+            }),
+            value => Ok(value.as_f64()),
+        }
+    }
+
+    /// Arithmetic on two integers in programmer mode. Signed words are worked with as `i128`, and unsigned
+    /// ones as `u128`, so that each can hold the whole of the widest word.
+    fn apply_integer(op: &BinaryOperator, lhs: Word, rhs: Word, mode: IntegerMode) -> Result<Value, EvalError> {
+        let operation = || format!("{} {} {}", lhs, op, rhs);
+        if matches!(op, BinaryOperator::Divide | BinaryOperator::Modulo) && rhs.bits == 0 {
+            return Err(EvalError::DivisionByZero { operation: operation() });
+        }
+        let result = if mode.word.signed {
+            let (lhs, rhs) = (mode.word.value_of(lhs.bits), mode.word.value_of(rhs.bits));
+            match op {
+                BinaryOperator::Add => mode.fit(lhs.checked_add(rhs), lhs.wrapping_add(rhs)),
+                BinaryOperator::Subtract => mode.fit(lhs.checked_sub(rhs), lhs.wrapping_sub(rhs)),
+                BinaryOperator::Multiply | BinaryOperator::ImplicitMultiply => mode.fit(lhs.checked_mul(rhs), lhs.wrapping_mul(rhs)),
+                BinaryOperator::Divide => mode.fit(lhs.checked_div(rhs), lhs.wrapping_div(rhs)),
+                BinaryOperator::Modulo => mode.fit(lhs.checked_rem(rhs), lhs.wrapping_rem(rhs)),
+                // A negative exponent has no integer result, so fall back to floating point.
+                BinaryOperator::Power if rhs < 0 => return Ok(Value::Float((lhs as f64).powf(rhs as f64))),
+                BinaryOperator::Power => match u32::try_from(rhs) {
+                    Ok(exponent) => mode.fit(lhs.checked_pow(exponent), lhs.wrapping_pow(exponent)),
+                    Err(_) => None,
+                },
+                _ => unreachable!("Bitwise operators are handled separately"),
+            }
+        } else {
+            let (lhs, rhs) = (lhs.bits, rhs.bits);
+            match op {
+                BinaryOperator::Add => mode.fit_unsigned(lhs.checked_add(rhs), lhs.wrapping_add(rhs)),
+                BinaryOperator::Subtract => mode.fit_unsigned(lhs.checked_sub(rhs), lhs.wrapping_sub(rhs)),
+                BinaryOperator::Multiply | BinaryOperator::ImplicitMultiply => mode.fit_unsigned(lhs.checked_mul(rhs), lhs.wrapping_mul(rhs)),
+                BinaryOperator::Divide => mode.fit_unsigned(Some(lhs / rhs), lhs / rhs),
+                BinaryOperator::Modulo => mode.fit_unsigned(Some(lhs % rhs), lhs % rhs),
+                BinaryOperator::Power => match u32::try_from(rhs) {
+                    Ok(exponent) => mode.fit_unsigned(lhs.checked_pow(exponent), lhs.wrapping_pow(exponent)),
+                    Err(_) => None,
+                },
+                _ => unreachable!("Bitwise operators are handled separately"),
+            }
+        };
+        result.map(Value::Word).ok_or_else(|| EvalError::Overflow {
+            operation: operation(),
+            word: mode.word,
+        })
+    }
+
+    /// How far to shift or rotate, which is a number of bits rather than a value in the word.
+    fn shift_amount(op: &BinaryOperator, value: &Value) -> Result<u32, EvalError> {
+        match value {
+            // Only the upper half of a `u128` doesn't fit in an `i128`, which is more bits than any word has.
+            Value::Word(word) if word.to_i128().is_none() => Ok(u32::MAX),
+            value => {
+                let amount = integer_operand(op, value)?;
+                u32::try_from(amount).map_err(|_| EvalError::InvalidShift { amount })
+            }
+        }
+    }
+
+    fn apply_bitwise(op: &BinaryOperator, lhs: Word, rhs: &Value, mode: IntegerMode) -> Result<Word, EvalError> {
+        let word = mode.word;
+        let overflow = || EvalError::Overflow {
+            operation: format!("{} {} {}", lhs, op, rhs),
+            word,
+        };
+
+        match op {
+            BinaryOperator::BitwiseAnd => Ok(Word::from_bits(lhs.bits & word_operand(op, rhs, mode)?.bits, word)),
+            BinaryOperator::BitwiseOr => Ok(Word::from_bits(lhs.bits | word_operand(op, rhs, mode)?.bits, word)),
+            BinaryOperator::BitwiseXor => Ok(Word::from_bits(lhs.bits ^ word_operand(op, rhs, mode)?.bits, word)),
+            BinaryOperator::ShiftLeft => {
+                let amount = shift_amount(op, rhs)?;
+                let shifted = match lhs.to_i128() {
+                    Some(value) if word.signed => {
+                        let wrapped = if amount < 128 { value << amount } else { 0 };
+                        mode.fit(2i128.checked_pow(amount).and_then(|factor| value.checked_mul(factor)), wrapped)
+                    }
+                    _ => {
+                        let wrapped = if amount < 128 { lhs.bits << amount } else { 0 };
+                        mode.fit_unsigned(2u128.checked_pow(amount).and_then(|factor| lhs.bits.checked_mul(factor)), wrapped)
+                    }
+                };
+                shifted.ok_or_else(overflow)
+            }
+            // Shifting right never overflows: signed words shift arithmetically, unsigned ones logically.
+            BinaryOperator::ShiftRight => {
+                let amount = shift_amount(op, rhs)?.min(127);
+                Ok(match lhs.to_i128() {
+                    Some(value) if word.signed => Word::new(value >> amount, word),
+                    _ => Word::from_bits(lhs.bits >> amount, word),
+                })
+            }
+            BinaryOperator::RotateLeft | BinaryOperator::RotateRight => {
+                let amount = shift_amount(op, rhs)? % word.bits;
+                let amount = match op {
+                    BinaryOperator::RotateLeft => amount,
+                    _ => (word.bits - amount) % word.bits,
+                };
+                let bits = lhs.bits;
+                let rotated = if amount == 0 {
+                    bits
+                } else {
+                    (bits << amount) | (bits >> (word.bits - amount))
+                };
+                Ok(Word::from_bits(rotated, word))
+            }
+            _ => unreachable!("Expected a bitwise operator, found {:?}", op),
+        }
     }
 
     #[cfg(test)]
     mod tests {
 
         use super::*;
+        use crate::value::value::Overflow;
 
         #[test]
         fn test_precedence() {
             let input = "1 + 4 * 3";
             let mut pairs = parse_equation(input).unwrap();
             println!("{:?}", pairs);
-            let result = eval(pairs.next().unwrap().into_inner(), &HashMap::new());
+            let result = eval(pairs.next().unwrap().into_inner(), &Environment::default()).unwrap();
             let value: f64 = result.into();
             assert_eq!(value, 13.0);
         }
@@ -192,7 +1818,7 @@ pub mod grammar {
             let input = "(1 + 4) * 3";
             let mut pairs = parse_equation(input).unwrap();
             println!("{:?}", pairs);
-            let result = eval(pairs.next().unwrap().into_inner(), &HashMap::new());
+            let result = eval(pairs.next().unwrap().into_inner(), &Environment::default()).unwrap();
             let value: f64 = result.into();
             assert_eq!(value, 15.0);
         }
@@ -202,11 +1828,328 @@ pub mod grammar {
             let input = "1 + 4 * -3";
             let mut pairs = parse_equation(input).unwrap();
             println!("{:?}", pairs);
-            let result = eval(pairs.next().unwrap().into_inner(), &HashMap::new());
+            let result = eval(pairs.next().unwrap().into_inner(), &Environment::default()).unwrap();
             let value: f64 = result.into();
             assert_eq!(value, -11.0);
         }
 
+        fn evaluate(input: &str) -> f64 {
+            let mut pairs = parse_equation(input).unwrap();
+            eval(pairs.next().unwrap().into_inner(), &Environment::default()).unwrap().into()
+        }
+
+        fn evaluate_legacy(input: &str) -> f64 {
+            let mut pairs = parse_equation(input).unwrap();
+            eval_legacy(pairs.next().unwrap().into_inner(), &Environment::default()).unwrap().into()
+        }
+
+        #[test]
+        fn test_power_right_associative() {
+            assert_eq!(evaluate("2^3^2"), 512.0);
+            assert_eq!(evaluate("(2^3)^2"), 64.0);
+            assert_eq!(evaluate_legacy("2^3^2"), 64.0);
+        }
+
+        #[test]
+        fn test_unary_minus_power() {
+            assert_eq!(evaluate("-2^2"), -4.0);
+            assert_eq!(evaluate("(-2)^2"), 4.0);
+            assert_eq!(evaluate("2^-1"), 0.5);
+            assert_eq!(evaluate("-2 * 3"), -6.0);
+            assert_eq!(evaluate_legacy("-2^2"), 4.0);
+        }
+
+        fn evaluate_with(input: &str, variables: &[(&str, f64)]) -> f64 {
+            let variables = variables
+                .iter()
+                .map(|(name, value)| {
+                    (
+                        name.to_string(),
+                        Expr::Float {
+                            value: *value,
+                            variable: None,
+                        },
+                    )
+                })
+                .collect::<HashMap<_, _>>();
+            let mut pairs = parse_equation(input).unwrap();
+            eval(pairs.next().unwrap().into_inner(), &variables.into()).unwrap().into()
+        }
+
+        #[test]
+        fn test_implicit_multiplication() {
+            let variables = [("x", 3.0), ("a", 1.0), ("b", 4.0)];
+            assert_eq!(evaluate_with("2x", &variables), 6.0);
+            assert_eq!(evaluate_with("2 x", &variables), 6.0);
+            assert_eq!(evaluate_with("3(a + b)", &variables), 15.0);
+            assert_eq!(evaluate_with("(a)(b)", &variables), 4.0);
+            assert_eq!(evaluate_with("2x^2", &variables), 18.0);
+            assert_eq!(evaluate_with("12/2x", &variables), 2.0);
+            assert_eq!(evaluate_with("-2x", &variables), -6.0);
+            assert_eq!(evaluate_with("x -a", &variables), 2.0);
+            assert!(parse_equation("2 3").is_err());
+        }
+
+        #[test]
+        fn test_literals() {
+            assert_eq!(evaluate("0xFF"), 255.0);
+            assert_eq!(evaluate("0b1010"), 10.0);
+            assert_eq!(evaluate("0o755"), 493.0);
+            assert_eq!(evaluate("1.5e-3"), 0.0015);
+            assert_eq!(evaluate("2E3"), 2000.0);
+            assert_eq!(evaluate(".5"), 0.5);
+            assert_eq!(evaluate("1_000_000"), 1_000_000.0);
+            assert_eq!(evaluate("0xFFFF_FFFF"), 4294967295.0);
+            assert_eq!(evaluate("123456789012345678901234567890123456789012"), 1.2345678901234568e41);
+        }
+
+        #[test]
+        fn test_literal_radix() {
+            let mut pairs = parse_equation("0x1F").unwrap();
+            match eval(pairs.next().unwrap().into_inner(), &Environment::default()).unwrap() {
+                Expr::Integer { value, radix, .. } => {
+                    assert_eq!(value, 31);
+                    assert_eq!(radix, Radix::Hexadecimal);
+                    assert_eq!(radix.format(value), "0x1F");
+                }
+                other => panic!("Expected integer, found {:?}", other),
+            }
+        }
+
+        fn evaluate_in(input: &str, word: &str, overflow: Overflow) -> Result<Value, EvalError> {
+            let environment = Environment {
+                integer_mode: Some(IntegerMode {
+                    word: word.parse().unwrap(),
+                    overflow,
+                }),
+                ..Default::default()
+            };
+            let mut pairs = parse_equation(input).unwrap();
+            eval(pairs.next().unwrap().into_inner(), &environment).map(|expr| expr.value())
+        }
+
+        #[test]
+        fn test_bitwise_operators() {
+            assert_eq!(evaluate("0b1100 & 0b1010"), 8.0);
+            assert_eq!(evaluate("0b1100 | 0b1010"), 14.0);
+            assert_eq!(evaluate("0b1100 xor 0b1010"), 6.0);
+            assert_eq!(evaluate("~0"), -1.0);
+            assert_eq!(evaluate("1 << 4 + 1"), 32.0);
+            assert_eq!(evaluate("-16 >> 2"), -4.0);
+            assert_eq!(evaluate("1 | 2 & 3"), 3.0);
+        }
+
+        #[test]
+        fn test_programmer_mode() {
+            let word = |value: i128, size: &str| Ok(Value::Word(Word::new(value, size.parse().unwrap())));
+            assert_eq!(evaluate_in("7 / 2", "u8", Overflow::Wrapping), word(3, "u8"));
+            assert_eq!(evaluate_in("200 + 100", "u8", Overflow::Wrapping), word(44, "u8"));
+            assert_eq!(evaluate_in("0 - 1", "u8", Overflow::Wrapping), word(255, "u8"));
+            assert_eq!(evaluate_in("0xFF", "i8", Overflow::Wrapping), word(-1, "i8"));
+            assert_eq!(evaluate_in("~0", "u16", Overflow::Wrapping), word(0xFFFF, "u16"));
+            assert_eq!(evaluate_in("0x81 rol 1", "u8", Overflow::Wrapping), word(0x03, "u8"));
+            assert_eq!(evaluate_in("0x81 ror 1", "u8", Overflow::Wrapping), word(0xC0, "u8"));
+            assert_eq!(evaluate_in("1 << 8", "u8", Overflow::Wrapping), word(0, "u8"));
+            assert_eq!(evaluate_in("2^10", "u16", Overflow::Checked), word(1024, "u16"));
+            assert!(matches!(
+                evaluate_in("200 + 100", "u8", Overflow::Checked),
+                Err(EvalError::Overflow { .. })
+            ));
+            assert!(matches!(evaluate_in("1 << 8", "u8", Overflow::Checked), Err(EvalError::Overflow { .. })));
+            // Shift amounts are counts, so they are not wrapped to the word like other literals.
+            assert_eq!(evaluate_in("1 << 200", "i8", Overflow::Wrapping), word(0, "i8"));
+            assert_eq!(evaluate_in("0x40 >> (0x81)", "i8", Overflow::Wrapping), word(0, "i8"));
+            assert!(matches!(evaluate_in("1 << 200", "i8", Overflow::Checked), Err(EvalError::Overflow { .. })));
+            assert!(matches!(evaluate_in("1 / 0", "u8", Overflow::Checked), Err(EvalError::DivisionByZero { .. })));
+            assert!(matches!(evaluate_in("1.5 & 1", "u8", Overflow::Checked), Err(EvalError::NotAnInteger { .. })));
+            // A u128 holds values past the largest i128.
+            let u128_max = Ok(Value::Word(Word::from_bits(u128::MAX, "u128".parse().unwrap())));
+            assert_eq!(evaluate_in("0 - 1", "u128", Overflow::Wrapping), u128_max);
+            assert_eq!(evaluate_in("0xFFFF_FFFF_FFFF_FFFF_FFFF_FFFF_FFFF_FFFF", "u128", Overflow::Checked), u128_max);
+            assert_eq!(evaluate_in("2^127 > 2^126", "u128", Overflow::Checked), Ok(Value::Bool(true)));
+            assert!(matches!(evaluate_in("2^128", "u128", Overflow::Checked), Err(EvalError::Overflow { .. })));
+            assert_eq!(evaluate_in("2^127", "u128", Overflow::Checked).unwrap().to_string(), "170141183460469231731687303715884105728");
+        }
+
+        fn evaluate_in_degrees(input: &str) -> Expr {
+            let mut pairs = parse_equation(input).unwrap();
+            let environment = Environment {
+                angle_unit: AngleUnit::Degrees,
+                ..Default::default()
+            };
+            eval(pairs.next().unwrap().into_inner(), &environment).unwrap()
+        }
+
+        #[test]
+        fn test_angles() {
+            let close = |expr: Expr, expected: f64| (expr.value().as_f64() - expected).abs() < 1e-12;
+            assert!(close(evaluate_in_degrees("sin(30)"), 0.5));
+            assert!(close(evaluate_in_degrees("cos(0.5rad * 2)"), 1f64.cos()));
+            assert!(close(evaluate_in_degrees("tan(50grad)"), 1.0));
+            assert!(evaluate_in_degrees("asin(1)").is_angle());
+            // Plain numbers added to an angle are in the angle mode too.
+            assert!(close(evaluate_in_degrees("asin(1) - 30"), 60f64.to_radians()));
+            assert!(evaluate_in_degrees("2 * 30deg").is_angle());
+            assert!(!evaluate_in_degrees("30deg / 10deg").is_angle());
+            match evaluate_in_degrees("sin(x)") {
+                Expr::FunctionCall { angle, .. } => assert_eq!(angle, Some(AngleUnit::Degrees)),
+                expr => panic!("expected a function call, found {:?}", expr),
+            }
+            assert_eq!(evaluate("180deg"), std::f64::consts::PI);
+        }
+
+        fn evaluate_value(input: &str) -> Result<Value, EvalError> {
+            let mut pairs = parse_equation(input).unwrap();
+            eval(pairs.next().unwrap().into_inner(), &Environment::default()).map(|expr| expr.value())
+        }
+
+        #[test]
+        fn test_lists() {
+            assert_eq!(
+                evaluate_value("[1, 2 * 3, []]"),
+                Ok(Value::List(vec![Value::Integer(1), Value::Float(6.0), Value::List(vec![])]))
+            );
+            assert_eq!(evaluate_value("median([3, 1, 2])"), Ok(Value::Float(2.0)));
+            assert!(matches!(evaluate_value("sqrt([4])"), Err(EvalError::ArgumentType { .. })));
+        }
+
+        #[test]
+        fn test_broadcasting() {
+            let integers = |values: &[i128]| Value::List(values.iter().copied().map(Value::Integer).collect());
+            assert_eq!(evaluate_value("[1, 2, 3] * 2"), Ok(Value::List(vec![Value::Float(2.0), Value::Float(4.0), Value::Float(6.0)])));
+            assert_eq!(evaluate_value("10 - [1, 2]"), Ok(Value::List(vec![Value::Float(9.0), Value::Float(8.0)])));
+            assert_eq!(evaluate_value("-[1]"), Ok(Value::List(vec![Value::Float(-1.0)])));
+            assert_eq!(evaluate_value("[1, 2] == [1, 3]"), Ok(Value::List(vec![Value::Bool(true), Value::Bool(false)])));
+            assert_eq!(evaluate_value("[[1], [2]] + 1"), Ok(Value::List(vec![Value::List(vec![Value::Float(2.0)]), Value::List(vec![Value::Float(3.0)])])));
+            assert_eq!(evaluate_value("[6, 12] & 4"), Ok(integers(&[4, 4])));
+            match evaluate_value("[1, 2, 3] + ([1] + [2])") {
+                Err(EvalError::LengthMismatch { lhs: 3, rhs: 1, lhs_span, rhs_span, .. }) => {
+                    assert_eq!((lhs_span, rhs_span), (0..9, 12..23));
+                }
+                result => panic!("expected a length mismatch, found {:?}", result),
+            }
+        }
+
+        #[test]
+        fn test_matrices() {
+            let rows = |rows: &[&[i128]]| Value::List(rows.iter().map(|row| Value::List(row.iter().copied().map(Value::Integer).collect())).collect());
+            assert_eq!(evaluate_value("[[1, 2], [3, 4]] @ identity(2)"), Ok(rows(&[&[1, 2], &[3, 4]])));
+            assert_eq!(evaluate_value("transpose([[1, 2]]) @ [[3, 4]]"), Ok(rows(&[&[3, 4], &[6, 8]])));
+            assert_eq!(evaluate_value("solve([[2, 0], [0, 4]], [2, 2]) == [1, 0.5]"), Ok(Value::List(vec![Value::Bool(true); 2])));
+            assert_eq!(evaluate_value("1 + 2 @ [3]"), Err(EvalError::NotAMatrix {
+                operator: "@".to_string(),
+                value: Value::Integer(2),
+            }));
+            match evaluate_value("[1, 2] @ [[1, 2, 3]]") {
+                Err(EvalError::ShapeMismatch { lhs, rhs, lhs_span, rhs_span, .. }) => {
+                    assert_eq!((lhs.as_str(), rhs.as_str()), ("vector of 2", "1×3 matrix"));
+                    assert_eq!((lhs_span, rhs_span), (0..6, 9..20));
+                }
+                result => panic!("expected a shape mismatch, found {:?}", result),
+            }
+            match evaluate_value("dot([1, 2], [1, 2, 3] )") {
+                Err(EvalError::ShapeMismatch { operator, lhs_span, rhs_span, .. }) => {
+                    assert_eq!((operator.as_str(), lhs_span, rhs_span), ("dot()", 4..10, 12..21));
+                }
+                result => panic!("expected a shape mismatch, found {:?}", result),
+            }
+            assert!(matches!(evaluate_value("inv([[1, 2], [2, 4]])"), Err(EvalError::FunctionFailed { .. })));
+            assert!(matches!(evaluate_value("det([[1, 2]])"), Err(EvalError::FunctionFailed { .. })));
+            assert!(matches!(evaluate_value("det([1, 2])"), Err(EvalError::ArgumentType { .. })));
+        }
+
+        #[test]
+        fn test_ranges() {
+            let integers = |values: &[i128]| Value::List(values.iter().copied().map(Value::Integer).collect());
+            assert_eq!(evaluate_value("1..5"), Ok(integers(&[1, 2, 3, 4, 5])));
+            assert_eq!(evaluate_value("3..1"), Ok(integers(&[3, 2, 1])));
+            assert_eq!(evaluate_value("1..2 + 3 step 2"), Ok(integers(&[1, 3, 5])));
+            assert_eq!(evaluate_value("1..0 step 1"), Ok(integers(&[])));
+            assert_eq!(evaluate_value("sum((1..4)^2)"), Ok(Value::Float(30.0)));
+            match evaluate_value("0..1 step 0.25") {
+                Ok(Value::List(values)) => assert_eq!(values.len(), 5),
+                result => panic!("expected a list, found {:?}", result),
+            }
+            match evaluate_value("0..1 step 0.1") {
+                Ok(Value::List(values)) => assert_eq!(values.len(), 11),
+                result => panic!("expected a list, found {:?}", result),
+            }
+            assert!(matches!(evaluate_value("1..2 step 0"), Err(EvalError::InvalidRange { .. })));
+            assert!(matches!(evaluate_value("1..1e9"), Err(EvalError::InvalidRange { .. })));
+            assert!(matches!(evaluate_value("1..true"), Err(EvalError::NotANumber { .. })));
+        }
+
+        #[test]
+        fn test_comparison_and_logical_operators() {
+            assert_eq!(evaluate_value("1 + 1 == 2"), Ok(Value::Bool(true)));
+            assert_eq!(evaluate_value("3 < 2 || 2 <= 2"), Ok(Value::Bool(true)));
+            assert_eq!(evaluate_value("!(1 > 2) && true"), Ok(Value::Bool(true)));
+            assert_eq!(evaluate_value("1 << 2 < 5"), Ok(Value::Bool(true)));
+            assert_eq!(evaluate_value("true != false"), Ok(Value::Bool(true)));
+            assert!(matches!(evaluate_value("true + 1"), Err(EvalError::NotANumber { .. })));
+            assert!(matches!(evaluate_value("1 && true"), Err(EvalError::NotABoolean { .. })));
+        }
+
+        #[test]
+        fn test_conditional() {
+            assert_eq!(evaluate("if 1 < 2 then 10 else 20"), 10.0);
+            assert_eq!(evaluate("1 > 2 ? 10 : 20"), 20.0);
+            assert_eq!(evaluate("1 > 2 ? 10 : 2 > 1 ? 30 : 40"), 30.0);
+            assert_eq!(evaluate("5 + if false then 1 else 2"), 7.0);
+            assert!(matches!(evaluate_value("1 ? 2 : 3"), Err(EvalError::NotABoolean { .. })));
+        }
+
+        #[test]
+        fn test_conditional_evaluates_taken_branch_only() {
+            let mut pairs = parse_equation("if true then 1 else 1 / 0").unwrap();
+            let environment = Environment {
+                integer_mode: Some(IntegerMode {
+                    word: "u8".parse().unwrap(),
+                    overflow: Overflow::Checked,
+                }),
+                ..Default::default()
+            };
+            match eval(pairs.next().unwrap().into_inner(), &environment).unwrap() {
+                Expr::Conditional { else_branch, value, .. } => {
+                    assert_eq!(value, Value::Word(Word::new(1, "u8".parse().unwrap())));
+                    assert!(matches!(*else_branch, Expr::Unevaluated { source } if source == "1 / 0"));
+                }
+                other => panic!("Expected conditional, found {:?}", other),
+            }
+        }
+
+        #[test]
+        fn test_constants() {
+            assert_eq!(evaluate("pi"), std::f64::consts::PI);
+            assert_eq!(evaluate("2pi"), std::f64::consts::TAU);
+            assert_eq!(evaluate("tau / 2 == pi ? 1 : 0"), 1.0);
+            assert_eq!(evaluate("-inf"), f64::NEG_INFINITY);
+            assert_eq!(evaluate("phys.N_A * phys.k_B"), 6.02214076e23 * 1.380649e-23);
+            assert_eq!(evaluate_with("pi", &[("pi", 3.0)]), 3.0);
+        }
+
+        #[test]
+        fn test_history_references() {
+            let entry = |source: &str, value: f64| HistoryEntry {
+                source: source.to_string(),
+                expr: Expr::Float { value, variable: None },
+            };
+            let environment = Environment {
+                history: vec![entry("1 + 1", 2.0), entry("5 * 2", 10.0)],
+                ..Default::default()
+            };
+            let evaluate_in = |input: &str| {
+                let mut pairs = parse_equation(input).unwrap();
+                eval(pairs.next().unwrap().into_inner(), &environment).map(|expr| expr.value())
+            };
+            assert_eq!(evaluate_in("ans * 2"), Ok(Value::Float(20.0)));
+            assert_eq!(evaluate_in("_ + 1"), Ok(Value::Float(11.0)));
+            assert_eq!(evaluate_in("$1 + %2"), Ok(Value::Float(12.0)));
+            assert_eq!(evaluate_in("5 %2"), Ok(Value::Float(1.0)));
+            assert!(matches!(evaluate_in("$3"), Err(EvalError::NoSuchResult { count: 2, .. })));
+            assert!(matches!(evaluate_in("$0"), Err(EvalError::NoSuchResult { .. })));
+        }
+
         #[test]
         fn test_variables() {
             let input = "a + b";
@@ -214,7 +2157,7 @@ pub mod grammar {
             println!("{:?}", pairs);
             let result = eval(
                 pairs.next().unwrap().into_inner(),
-                &HashMap::from([
+                &Environment::from(HashMap::from([
                     (
                         "a".to_string(),
                         Expr::Float {
@@ -229,8 +2172,9 @@ pub mod grammar {
                             variable: None,
                         },
                     ),
-                ]),
-            );
+                ])),
+            )
+            .unwrap();
             let value: f64 = result.into();
             assert_eq!(value, 30.0);
         }
@@ -242,7 +2186,7 @@ pub mod grammar {
             println!("{:?}", pairs);
             let result = eval(
                 pairs.next().unwrap().into_inner(),
-                &HashMap::from([
+                &Environment::from(HashMap::from([
                     (
                         "a".to_string(),
                         Expr::Float {
@@ -257,10 +2201,38 @@ pub mod grammar {
                             variable: None,
                         },
                     ),
-                ]),
-            );
+                ])),
+            )
+            .unwrap();
             let value: f64 = result.into();
             assert!(value.is_nan())
         }
+
+        #[test]
+        fn test_unbound_variable_suggestion() {
+            let input = "vlaue * 2";
+            let mut pairs = parse_equation(input).unwrap();
+            let result = eval(
+                pairs.next().unwrap().into_inner(),
+                &Environment::from(HashMap::from([(
+                    "value".to_string(),
+                    Expr::Float {
+                        value: 10f64,
+                        variable: None,
+                    },
+                )])),
+            )
+            .unwrap();
+            match result {
+                Expr::BinaryOperation { lhs, .. } => match *lhs {
+                    Expr::UnboundVariable { name, suggestion } => {
+                        assert_eq!(name, "vlaue");
+                        assert_eq!(suggestion, Some("value".to_string()));
+                    }
+                    other => panic!("Expected unbound variable, found {:?}", other),
+                },
+                other => panic!("Expected binary operation, found {:?}", other),
+            }
+        }
     }
 }
@@ -14,14 +14,41 @@ pub mod highlighter {
 
                 // Match the rule and apply colors based on the smallest matched rule
                 let styled = match pair.as_rule() {
-                    Rule::integer | Rule::float => Style::new().fg(Color::Blue),
-                    Rule::multiply => Style::new().fg(Color::Green),
+                    Rule::integer
+                    | Rule::hex_integer
+                    | Rule::binary_integer
+                    | Rule::octal_integer
+                    | Rule::float
+                    | Rule::angle_unit => Style::new().fg(Color::Blue),
+                    Rule::multiply | Rule::matrix_multiply => Style::new().fg(Color::Green),
                     Rule::divide => Style::new().fg(Color::Green),
                     Rule::add => Style::new().fg(Color::Green),
                     Rule::modulo => Style::new().fg(Color::Green),
                     Rule::power => Style::new().fg(Color::Green),
+                    Rule::bitwise_and
+                    | Rule::bitwise_or
+                    | Rule::bitwise_xor
+                    | Rule::bitwise_not
+                    | Rule::shift_left
+                    | Rule::shift_right
+                    | Rule::rotate_left
+                    | Rule::rotate_right => Style::new().fg(Color::Green),
+                    Rule::equal
+                    | Rule::not_equal
+                    | Rule::less
+                    | Rule::less_equal
+                    | Rule::greater
+                    | Rule::greater_equal
+                    | Rule::and
+                    | Rule::or
+                    | Rule::not => Style::new().fg(Color::Green),
+                    Rule::boolean => Style::new().fg(Color::Blue),
+                    Rule::if_keyword | Rule::then_keyword | Rule::else_keyword | Rule::step_keyword | Rule::where_keyword => Style::new().fg(Color::Cyan),
                     Rule::identifier => Style::new().fg(Color::Yellow),
-                    Rule::variable => Style::new().fg(Color::Purple),
+                    Rule::shadow => Style::new().fg(Color::Cyan),
+                    Rule::variable | Rule::history_reference => Style::new().fg(Color::Purple),
+                    Rule::function_name => Style::new().fg(Color::Cyan),
+                    Rule::string => Style::new().fg(Color::Green),
                     Rule::comment => Style::new().fg(Color::LightGray),
                     Rule::WHITESPACE => Style::new().fg(Color::White),
                     _ => Style::new().fg(Color::White),
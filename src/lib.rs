@@ -0,0 +1,56 @@
+//! A calculator that shows how an expression was evaluated, as a tree.
+//!
+//! [`Context`] owns a session's variables and history of results, and evaluates input against them:
+//!
+//! ```
+//! use calc::{Context, Value};
+//!
+//! let mut context = Context::new();
+//! context.eval("rate := 0.25").unwrap();
+//! assert_eq!(context.eval("rate * 8").unwrap(), Value::Float(2.0));
+//! ```
+//!
+//! Host applications can provide their own functions and variables, either one at a time with
+//! [`Context::register_function`] and [`Context::register_resolver`], or by implementing [`Plugin`]:
+//!
+//! ```
+//! use calc::{Context, ParameterType, Parameters, Value};
+//!
+//! let mut context = Context::new();
+//! context.register_function("double", Parameters::Fixed(vec![ParameterType::Number]), |arguments| {
+//!     Ok(Value::Float(arguments[0].as_f64() * 2.0))
+//! });
+//! context.register_resolver(|name| (name == "config.timeout").then_some(Value::Integer(30)));
+//! assert_eq!(context.eval("double(config.timeout)").unwrap(), Value::Float(60.0));
+//! ```
+//!
+//! [`Context::evaluate`] returns the whole expression tree instead, which can be rendered to any
+//! writer with [`expr_writer::expr_writer::write_expr_tree`], in colour or as plain text.
+
+// Each module wraps its contents in an inner module of the same name.
+#![allow(clippy::module_inception)]
+
+pub mod calculus;
+pub mod commands;
+pub mod config;
+pub mod constants;
+pub mod context;
+pub mod expr_writer;
+pub mod format;
+pub mod functions;
+pub mod grammar;
+pub mod highlighter;
+pub mod matrix;
+pub mod plot;
+pub mod repl;
+pub mod simplify;
+pub mod solve;
+pub mod statistics;
+pub mod suggest;
+pub mod symbolic;
+pub mod value;
+
+pub use context::context::{Context, Error, Evaluation};
+pub use functions::functions::{CallError, ParameterType, Parameters, Plugin, Registry};
+pub use grammar::grammar::{BinaryOperator, Environment, EvalError, Expr, UnaryOperator};
+pub use value::value::Value;
@@ -1,38 +1,260 @@
-mod expr_writer;
-mod highlighter;
-mod grammar;
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
+use std::process::ExitCode;
 
+use calc::commands::commands::CommandCompleter;
+use calc::config::config::{default_config_path, default_rc_path, Backend, Config, EditMode};
+use calc::expr_writer::expr_writer::{ColorChoice, Layout};
+use calc::format::format::Notation;
+use calc::functions::functions::BUNDLED_PLUGINS;
+use calc::grammar::grammar::{AngleUnit, Radix};
+use calc::highlighter::highlighter::ArithmeticHighlighter;
+use calc::repl::repl::Session;
+use calc::value::value::Overflow;
 use clap::Parser as ClapParser;
-use highlighter::highlighter::ArithmeticHighlighter;
-use std::collections::{HashMap, HashSet};
-use grammar::grammar::{eval, parse_equation, Expr, Rule};
-use expr_writer::expr_writer::write_expr_tree;
 
-use reedline::{DefaultPrompt, DefaultPromptSegment, Reedline, Signal};
+use reedline::{
+    default_emacs_keybindings, default_vi_insert_keybindings, default_vi_normal_keybindings, ColumnarMenu, DefaultPrompt,
+    DefaultPromptSegment, Emacs, KeyCode, KeyModifiers, Keybindings, MenuBuilder, Reedline, ReedlineEvent, ReedlineMenu,
+    Signal, Vi,
+};
 
+/// Every setting can also be given in the config file, and the flags take precedence over it.
 #[derive(Default, ClapParser, Debug)]
 struct Arguments {
     expression: Option<String>,
+
+    /// Read the settings from this file instead of `$XDG_CONFIG_HOME/calc/config.toml`.
+    #[arg(long, conflicts_with = "no_config")]
+    config: Option<PathBuf>,
+
+    /// Ignore the config file.
+    #[arg(long)]
+    no_config: bool,
+
+    /// Run this script of definitions before the first prompt, instead of `$XDG_CONFIG_HOME/calc/rc`.
+    #[arg(long, conflicts_with = "no_rc")]
+    rc: Option<PathBuf>,
+
+    /// Don't run the rc script.
+    #[arg(long)]
+    no_rc: bool,
+
+    /// Warn when an expression evaluates differently under the precedence rules of earlier versions,
+    /// where `^` was left-associative and bound more loosely than unary minus. Useful when replaying
+    /// expressions from old sessions or scripts.
+    #[arg(long, overrides_with = "no_warn_precedence")]
+    warn_precedence: bool,
+
+    /// Don't warn about expressions that evaluated differently in earlier versions.
+    #[arg(long, overrides_with = "warn_precedence")]
+    no_warn_precedence: bool,
+
+    /// Require explicit operators, rejecting implicit multiplication such as `2x` or `3(a + b)`.
+    #[arg(long, overrides_with = "no_strict")]
+    strict: bool,
+
+    /// Allow implicit multiplication.
+    #[arg(long, overrides_with = "strict")]
+    no_strict: bool,
+
+    /// Leave unknown names as free variables, so that `f := 2x + 1` stores a formula that can be
+    /// evaluated later with `f where x = 3`.
+    #[arg(long, overrides_with = "no_symbolic")]
+    symbolic: bool,
+
+    /// Report unknown names as unbound variables.
+    #[arg(long, overrides_with = "symbolic")]
+    no_symbolic: bool,
+
+    /// Enable programmer mode, using fixed-width integers of the given size (u8, u16, u32, u64, u128,
+    /// i8, i16, i32, i64 or i128), or `float` to turn it off.
+    #[arg(long)]
+    word: Option<Backend>,
+
+    /// In programmer mode, report an error when an operation overflows instead of wrapping around.
+    #[arg(long, overrides_with = "wrapping")]
+    checked: bool,
+
+    /// In programmer mode, wrap around when an operation overflows.
+    #[arg(long, overrides_with = "checked")]
+    wrapping: bool,
+
+    /// Enable one of the bundled plugins at startup. `env` makes environment variables holding
+    /// numbers available as `env.NAME`.
+    #[arg(long = "plugin", value_parser = clap::builder::PossibleValuesParser::new(BUNDLED_PLUGINS))]
+    plugins: Vec<String>,
+
+    /// When to colour the output: `auto` colours it when writing to a terminal, unless the `NO_COLOR`
+    /// environment variable is set.
+    #[arg(long)]
+    color: Option<ColorChoice>,
+
+    /// How to lay out the expression tree: `tree`, `top-down`, `inline`, or `auto` to pick whichever
+    /// fits in the terminal.
+    #[arg(long)]
+    layout: Option<Layout>,
+
+    /// Collapse operations more than this many levels deep into `…`.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    depth: Option<u64>,
+
+    /// Show floats with at most this many significant digits (1 to 17).
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..=17), conflicts_with = "decimals")]
+    digits: Option<u64>,
+
+    /// Show floats with exactly this many digits after the decimal point.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(0..=20))]
+    decimals: Option<u64>,
+
+    /// How to write large and small numbers: `auto`, `fixed`, `scientific`, `engineering` or `si`
+    /// (SI prefixes such as `1.2k`).
+    #[arg(long)]
+    notation: Option<Notation>,
+
+    /// Group digits with separators, as in `1,000,000`.
+    #[arg(long, overrides_with = "no_separators")]
+    separators: bool,
+
+    /// Don't group digits.
+    #[arg(long, overrides_with = "separators")]
+    no_separators: bool,
+
+    /// The base to write integers in: `dec`, `hex`, `oct` or `bin`.
+    #[arg(long)]
+    radix: Option<Radix>,
+
+    /// The unit `sin`, `cos` and `tan` read plain numbers in, and angles are shown in: `rad`, `deg` or `grad`.
+    #[arg(long)]
+    angle: Option<AngleUnit>,
+
+    /// The prompt, where `n` in braces is replaced by the number of the next result.
+    #[arg(long)]
+    prompt: Option<String>,
+
+    /// The key bindings for editing the input: `emacs` or `vi`.
+    #[arg(long)]
+    edit_mode: Option<EditMode>,
+}
+
+/// A setting turned on or off by a pair of flags, or left to the config file if neither was given.
+/// Clap keeps only the last of the pair.
+fn switch(on: bool, off: bool) -> Option<bool> {
+    match (on, off) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    }
+}
+
+impl Arguments {
+    /// The settings given on the command line.
+    fn config(&self) -> Config {
+        Config {
+            digits: self.digits.map(|digits| digits as usize),
+            decimals: self.decimals.map(|decimals| decimals as usize),
+            notation: self.notation,
+            separators: switch(self.separators, self.no_separators),
+            radix: self.radix,
+            angle: self.angle,
+            color: self.color,
+            layout: self.layout,
+            depth: self.depth.map(|depth| depth as usize),
+            prompt: self.prompt.clone(),
+            edit_mode: self.edit_mode,
+            word: self.word,
+            overflow: switch(self.checked, self.wrapping).map(|checked| if checked { Overflow::Checked } else { Overflow::Wrapping }),
+            strict: switch(self.strict, self.no_strict),
+            symbolic: switch(self.symbolic, self.no_symbolic),
+            warn_precedence: switch(self.warn_precedence, self.no_warn_precedence),
+            plugins: (!self.plugins.is_empty()).then(|| self.plugins.clone()),
+            rc: self.rc.clone(),
+        }
+    }
+}
+
+/// Binds tab to the completion menu for commands.
+fn with_completion(mut keybindings: Keybindings) -> Keybindings {
+    keybindings.add_binding(
+        KeyModifiers::NONE,
+        KeyCode::Tab,
+        ReedlineEvent::UntilFound(vec![ReedlineEvent::Menu("completion_menu".to_string()), ReedlineEvent::MenuNext]),
+    );
+    keybindings
 }
 
-fn main() {
+/// The width of the terminal, if the output is going to one.
+fn terminal_width() -> Option<usize> {
+    if !io::stdout().is_terminal() {
+        return None;
+    }
+    crossterm::terminal::size().ok().map(|(columns, _)| columns as usize)
+}
+
+fn main() -> io::Result<ExitCode> {
     let args = Arguments::parse();
-    let mut variables = HashMap::new();
+
+    let config_path = if args.no_config {
+        None
+    } else {
+        args.config.clone().or_else(default_config_path)
+    };
+    let file = match config_path.as_deref().map(Config::load).transpose() {
+        Ok(file) => file.unwrap_or_default(),
+        Err(e) => {
+            eprintln!("calc: {}", e);
+            return Ok(ExitCode::from(2));
+        }
+    };
+    let config = file.merge(args.config());
+
+    let mut session = Session::default();
+    session.color = config.color;
+    session.style = config.color.unwrap_or_default().style(io::stdout().is_terminal());
+    if let Err(e) = config.apply(&mut session) {
+        eprintln!("calc: {}", e);
+        return Ok(ExitCode::from(2));
+    }
+    session.config_path = config_path.filter(|path| path.exists());
+    session.layout.width = terminal_width();
+
+    let rc_path = if args.no_rc { None } else { config.rc.clone().or_else(default_rc_path) };
+    if let Some(rc_path) = rc_path {
+        match std::fs::read_to_string(&rc_path) {
+            Ok(source) => session.run_script(&source, &rc_path.display().to_string(), &mut io::stderr())?,
+            // The default rc script is optional, but one that was asked for has to exist.
+            Err(e) if e.kind() == io::ErrorKind::NotFound && config.rc.is_none() => {}
+            Err(e) => eprintln!("calc: {}: {}", rc_path.display(), e),
+        }
+    }
+
+    let mut out = io::stdout();
     if let Some(expression) = args.expression {
-        handle_input(expression, &mut variables, &mut None);
+        session.handle_input(&expression, &mut out)?;
     } else {
-        let mut last_expr = None;
-        let mut line_editor = Reedline::create().with_highlighter(Box::new(ArithmeticHighlighter));
-        let prompt = DefaultPrompt {
-            left_prompt: DefaultPromptSegment::Basic("> ".to_string()),
-            right_prompt: DefaultPromptSegment::Empty,
+        let menu = ColumnarMenu::default().with_name("completion_menu");
+        let mut line_editor = Reedline::create()
+            .with_highlighter(Box::new(ArithmeticHighlighter))
+            .with_completer(Box::new(CommandCompleter))
+            .with_menu(ReedlineMenu::EngineCompleter(Box::new(menu)));
+        line_editor = match session.edit_mode {
+            EditMode::Emacs => line_editor.with_edit_mode(Box::new(Emacs::new(with_completion(default_emacs_keybindings())))),
+            EditMode::Vi => line_editor.with_edit_mode(Box::new(Vi::new(
+                with_completion(default_vi_insert_keybindings()),
+                default_vi_normal_keybindings(),
+            ))),
         };
 
         loop {
+            let prompt = DefaultPrompt {
+                left_prompt: DefaultPromptSegment::Basic(session.prompt()),
+                right_prompt: DefaultPromptSegment::Basic(session.right_prompt()),
+            };
             let sig = line_editor.read_line(&prompt);
             match sig {
                 Ok(Signal::Success(buffer)) => {
-                    handle_input(buffer, &mut variables, &mut last_expr);
+                    session.layout.width = terminal_width();
+                    session.handle_input(&buffer, &mut out)?;
                 }
                 Ok(Signal::CtrlD) | Ok(Signal::CtrlC) => {
                     println!("\nAborted!");
@@ -44,87 +266,32 @@ fn main() {
             }
         }
     }
+    Ok(ExitCode::SUCCESS)
 }
 
-fn unbound_variables(expr: &Expr) -> HashSet<String> {
-    let mut unbound = HashSet::new();
-    match expr {
-        Expr::BinaryOperation { lhs, rhs, .. } => {
-            unbound.extend(unbound_variables(lhs));
-            unbound.extend(unbound_variables(rhs));
-        }
-        Expr::UnaryOperation { expr, .. } => {
-            unbound.extend(unbound_variables(expr));
-        }
-        Expr::UnboundVariable { name } => {
-            unbound.insert(name.clone());
-        }
-        _ => {}
-    }
-    unbound
-}
+#[cfg(test)]
+mod tests {
 
-fn handle_input(buffer: String, variables: &mut HashMap<String, Expr>, last_expr: &mut Option<Expr>) {
-    match parse_equation(&buffer) {
-        Ok(mut pairs) => {
-            if let Some(pair) = pairs.next() {
-                match pair.as_rule() {
-                    Rule::assignment => {
-                        // If the first pair is an assignment, evaluate it and store the variable
-                        let mut inner_pairs = pair.into_inner();
-                        let variable = inner_pairs.next().unwrap().as_str().to_string();
-                        let expr = eval(inner_pairs.next().unwrap().into_inner(), variables);
-
-                        let unbound = unbound_variables(&expr);
-                        if unbound.len() > 0 {
-                            println!("Unbound variables: {:?}", unbound);
-                        } else {
-                            variables.insert(variable.clone(), expr.clone());
-                        }
-
-                        write_expr_tree(expr.clone());
-                        last_expr.replace(expr);
-                    }
-                    Rule::command => {
-                        let command = pair.into_inner().next().unwrap().as_str();
-                        match command {
-                            "state" => {
-                                for (key, value) in variables.iter() {
-                                    println!("{} = {:?}", key, value);
-                                }
-                            }
-                            "reset" => {
-                                variables.clear();
-                            }
-                            "debug" => {
-                                println!("{:?}", last_expr);
-                            }
-                            _ => println!("Unknown command: {}", command),
-                        }
-                    }
-                    _ => {
-                        let expr = eval(pair.into_inner(), variables);
-                        let unbound = unbound_variables(&expr);
-                        if unbound.len() > 0 {
-                            println!("Unbound variables: {:?}", unbound);
-                        }
-                        write_expr_tree(expr.clone());
-                        last_expr.replace(expr);
-                    }
-                }
-            }
-        }
-        Err(e) => {
-            println!("{}", buffer);
-            // Draw a ^ in the location of the error
-            let location = match e.location {
-                pest::error::InputLocation::Pos(col) => col,
-                pest::error::InputLocation::Span((start, _)) => start,
-            };
-            let output = format!("{:>width$}^", "", width = location);
-            println!("{}", output);
+    use super::*;
 
-            println!("Parse failed: {:?}", e);
-        }
+    #[test]
+    fn test_flags_before_the_expression() {
+        let args = Arguments::try_parse_from(["calc", "--strict", "1+1"]).unwrap();
+        assert!(args.strict);
+        assert_eq!(args.expression.as_deref(), Some("1+1"));
+        assert_eq!(args.config().strict, Some(true));
+        assert_eq!(Arguments::try_parse_from(["calc"]).unwrap().config().strict, None);
+    }
+
+    #[test]
+    fn test_negative_flags() {
+        let config = |args: &[&str]| Arguments::try_parse_from(["calc"].iter().chain(args)).unwrap().config();
+        assert_eq!(config(&["--no-strict"]).strict, Some(false));
+        assert_eq!(config(&["--strict", "--no-strict"]).strict, Some(false));
+        assert_eq!(config(&["--no-symbolic", "--symbolic"]).symbolic, Some(true));
+        assert_eq!(config(&["--no-separators"]).separators, Some(false));
+        assert_eq!(config(&["--no-warn-precedence"]).warn_precedence, Some(false));
+        assert_eq!(config(&["--checked", "--wrapping"]).overflow, Some(Overflow::Wrapping));
+        assert_eq!(config(&[]).overflow, None);
     }
 }
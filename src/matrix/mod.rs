@@ -0,0 +1,428 @@
+pub mod matrix {
+    use std::fmt;
+
+    use crate::functions::functions::{CallError, ParameterType, Parameters, Plugin, Registry};
+    use crate::value::value::Value;
+
+    /// A grid of numbers, stored row by row.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Matrix {
+        rows: usize,
+        columns: usize,
+        elements: Vec<f64>,
+    }
+
+    /// A matrix or vector operand, as it is written in an expression.
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Array {
+        /// A list of numbers, such as `[1, 2, 3]`.
+        Vector(Vec<f64>),
+        /// A list of rows of the same length, such as `[[1, 2], [3, 4]]`.
+        Matrix(Matrix),
+    }
+
+    impl Array {
+        pub fn from_value(value: &Value) -> Option<Array> {
+            match value {
+                Value::List(values) if values.iter().all(|value| matches!(value, Value::Integer(_) | Value::Word(_) | Value::Float(_))) => {
+                    Some(Array::Vector(values.iter().map(Value::as_f64).collect()))
+                }
+                value => Matrix::from_value(value).map(Array::Matrix),
+            }
+        }
+
+        /// The shape for error messages, such as `2×3 matrix` or `vector of 3`.
+        pub fn shape(&self) -> String {
+            match self {
+                Array::Vector(elements) => format!("vector of {}", elements.len()),
+                Array::Matrix(matrix) => matrix.shape(),
+            }
+        }
+    }
+
+    impl Matrix {
+        pub fn new(rows: usize, columns: usize, elements: Vec<f64>) -> Matrix {
+            assert_eq!(rows * columns, elements.len(), "a {}×{} matrix needs {} elements", rows, columns, rows * columns);
+            Matrix { rows, columns, elements }
+        }
+
+        pub fn identity(size: usize) -> Matrix {
+            let mut matrix = Matrix::zeros(size, size);
+            for index in 0..size {
+                matrix.elements[index * size + index] = 1.0;
+            }
+            matrix
+        }
+
+        pub fn zeros(rows: usize, columns: usize) -> Matrix {
+            Matrix::new(rows, columns, vec![0.0; rows * columns])
+        }
+
+        /// A single column holding the elements of the vector.
+        pub fn column(elements: &[f64]) -> Matrix {
+            Matrix::new(elements.len(), 1, elements.to_vec())
+        }
+
+        /// Reads a list of rows of numbers, which must all have the same, non-zero, length.
+        pub fn from_value(value: &Value) -> Option<Matrix> {
+            let Value::List(rows) = value else {
+                return None;
+            };
+            let mut elements = vec![];
+            let mut columns = None;
+            for row in rows {
+                let Value::List(row) = row else {
+                    return None;
+                };
+                if row.is_empty() || columns.is_some_and(|columns| columns != row.len()) {
+                    return None;
+                }
+                columns = Some(row.len());
+                for element in row {
+                    match element {
+                        Value::Integer(_) | Value::Word(_) | Value::Float(_) => elements.push(element.as_f64()),
+                        _ => return None,
+                    }
+                }
+            }
+            Some(Matrix::new(rows.len(), columns?, elements))
+        }
+
+        /// The matrix as a list of rows, with whole numbers as integers.
+        pub fn to_value(&self) -> Value {
+            Value::List(self.row_values().map(|row| Value::List(row.iter().map(|x| number(*x)).collect())).collect())
+        }
+
+        /// The elements of a single row or column matrix as a list.
+        pub fn to_vector(&self) -> Value {
+            Value::List(self.elements.iter().map(|x| number(*x)).collect())
+        }
+
+        fn row_values(&self) -> impl Iterator<Item = &[f64]> {
+            self.elements.chunks(self.columns)
+        }
+
+        pub fn rows(&self) -> usize {
+            self.rows
+        }
+
+        pub fn columns(&self) -> usize {
+            self.columns
+        }
+
+        pub fn get(&self, row: usize, column: usize) -> f64 {
+            self.elements[row * self.columns + column]
+        }
+
+        pub fn shape(&self) -> String {
+            format!("{}×{} matrix", self.rows, self.columns)
+        }
+
+        pub fn is_square(&self) -> bool {
+            self.rows == self.columns
+        }
+
+        pub fn transpose(&self) -> Matrix {
+            let elements = (0..self.columns)
+                .flat_map(|column| (0..self.rows).map(move |row| (row, column)))
+                .map(|(row, column)| self.get(row, column))
+                .collect();
+            Matrix::new(self.columns, self.rows, elements)
+        }
+
+        /// The product, or `None` if the columns of this matrix don't match the rows of the other.
+        pub fn multiply(&self, other: &Matrix) -> Option<Matrix> {
+            if self.columns != other.rows {
+                return None;
+            }
+            let elements = (0..self.rows)
+                .flat_map(|row| (0..other.columns).map(move |column| (row, column)))
+                .map(|(row, column)| (0..self.columns).map(|k| self.get(row, k) * other.get(k, column)).sum())
+                .collect();
+            Some(Matrix::new(self.rows, other.columns, elements))
+        }
+
+        /// The Frobenius norm, which is the Euclidean norm of a vector.
+        pub fn norm(&self) -> f64 {
+            self.elements.iter().map(|x| x * x).sum::<f64>().sqrt()
+        }
+
+        /// Reduces `[self | other]` to reduced row echelon form with partial pivoting, returning the
+        /// transformed right-hand side and the determinant of this matrix, or `None` if it is singular.
+        fn eliminate(&self, other: &Matrix) -> Option<(Matrix, f64)> {
+            let size = self.rows;
+            let mut left = self.clone();
+            let mut right = other.clone();
+            let mut determinant = 1.0;
+            // Pivots smaller than this, relative to the largest element, are taken to be zero.
+            let tolerance = 1e-12 * self.elements.iter().fold(0.0_f64, |max, x| max.max(x.abs()));
+            for column in 0..size {
+                let pivot = (column..size).max_by(|a, b| left.get(*a, column).abs().total_cmp(&left.get(*b, column).abs()))?;
+                if left.get(pivot, column).abs() <= tolerance {
+                    return None;
+                }
+                if pivot != column {
+                    left.swap_rows(pivot, column);
+                    right.swap_rows(pivot, column);
+                    determinant = -determinant;
+                }
+                let scale = left.get(column, column);
+                determinant *= scale;
+                left.scale_row(column, 1.0 / scale);
+                right.scale_row(column, 1.0 / scale);
+                for row in (0..size).filter(|row| *row != column) {
+                    let factor = left.get(row, column);
+                    left.subtract_row(row, column, factor);
+                    right.subtract_row(row, column, factor);
+                }
+            }
+            Some((right, determinant))
+        }
+
+        fn swap_rows(&mut self, a: usize, b: usize) {
+            for column in 0..self.columns {
+                self.elements.swap(a * self.columns + column, b * self.columns + column);
+            }
+        }
+
+        fn scale_row(&mut self, row: usize, factor: f64) {
+            for x in &mut self.elements[row * self.columns..(row + 1) * self.columns] {
+                *x *= factor;
+            }
+        }
+
+        /// Subtracts `factor` times the `source` row from the `target` row.
+        fn subtract_row(&mut self, target: usize, source: usize, factor: f64) {
+            for column in 0..self.columns {
+                let delta = factor * self.get(source, column);
+                self.elements[target * self.columns + column] -= delta;
+            }
+        }
+
+        /// The determinant of a square matrix.
+        pub fn determinant(&self) -> f64 {
+            match self.eliminate(&Matrix::zeros(self.rows, 0)) {
+                Some((_, determinant)) => determinant,
+                None => 0.0,
+            }
+        }
+
+        /// The inverse of a square matrix, or `None` if it is singular.
+        pub fn inverse(&self) -> Option<Matrix> {
+            self.eliminate(&Matrix::identity(self.rows)).map(|(inverse, _)| inverse)
+        }
+
+        /// The `x` for which `self x = b`, or `None` if this square matrix is singular.
+        pub fn solve(&self, b: &Matrix) -> Option<Matrix> {
+            self.eliminate(b).map(|(x, _)| x)
+        }
+    }
+
+    impl fmt::Display for Matrix {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{}", self.to_value())
+        }
+    }
+
+    /// Whole numbers are kept as integers, so that `identity(2)` is shown as `[[1, 0], [0, 1]]`.
+    fn number(x: f64) -> Value {
+        if x.fract() == 0.0 && x.abs() < 1e15 {
+            Value::Integer(x as i128)
+        } else {
+            Value::Float(x)
+        }
+    }
+
+    /// Why the operands of `a @ b` can't be multiplied.
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum ProductError {
+        /// One of the operands isn't a matrix or vector.
+        NotAnArray(Value),
+        /// The shapes of the operands, which don't fit together.
+        Shapes(String, String),
+    }
+
+    /// `a @ b`: the matrix product, where a vector on the left is a row and on the right is a column, and the
+    /// product of two vectors is their dot product.
+    pub fn product(lhs: &Value, rhs: &Value) -> Result<Value, ProductError> {
+        let array = |value: &Value| Array::from_value(value).ok_or_else(|| ProductError::NotAnArray(value.clone()));
+        let (lhs, rhs) = (array(lhs)?, array(rhs)?);
+        let mismatch = || ProductError::Shapes(lhs.shape(), rhs.shape());
+        match (&lhs, &rhs) {
+            (Array::Vector(u), Array::Vector(v)) => dot(u, v).map(Value::Float).ok_or_else(mismatch),
+            (Array::Vector(u), Array::Matrix(m)) => {
+                Matrix::column(u).transpose().multiply(m).map(|row| row.to_vector()).ok_or_else(mismatch)
+            }
+            (Array::Matrix(m), Array::Vector(v)) => m.multiply(&Matrix::column(v)).map(|column| column.to_vector()).ok_or_else(mismatch),
+            (Array::Matrix(a), Array::Matrix(b)) => a.multiply(b).map(|product| product.to_value()).ok_or_else(mismatch),
+        }
+    }
+
+    /// The dot product, or `None` if the vectors have different lengths.
+    pub fn dot(u: &[f64], v: &[f64]) -> Option<f64> {
+        (u.len() == v.len()).then(|| u.iter().zip(v).map(|(a, b)| a * b).sum())
+    }
+
+    /// The cross product of two vectors of 3.
+    pub fn cross(u: &[f64], v: &[f64]) -> [f64; 3] {
+        [u[1] * v[2] - u[2] * v[1], u[2] * v[0] - u[0] * v[2], u[0] * v[1] - u[1] * v[0]]
+    }
+
+    fn vector(value: &Value) -> Vec<f64> {
+        match value {
+            Value::List(values) => values.iter().map(Value::as_f64).collect(),
+            value => vec![value.as_f64()],
+        }
+    }
+
+    fn square(value: &Value) -> Result<Matrix, CallError> {
+        match Matrix::from_value(value) {
+            Some(matrix) if matrix.is_square() => Ok(matrix),
+            Some(matrix) => Err(CallError::Failed(format!("expected a square matrix, found a {}", matrix.shape()))),
+            None => unreachable!("Matrix arguments are checked before the call"),
+        }
+    }
+
+    fn size(value: &Value) -> Result<usize, CallError> {
+        match value.as_integer() {
+            Some(size @ 1..=1000) => Ok(size as usize),
+            _ => Err(CallError::Failed(format!("expected a size between 1 and 1000, found {}", value))),
+        }
+    }
+
+    /// Matrix and vector functions, such as `det([[1, 2], [3, 4]])` and `cross(u, v)`.
+    pub struct LinearAlgebra;
+
+    impl Plugin for LinearAlgebra {
+        fn register(&self, registry: &mut Registry) {
+            let matrix = |value: &Value| Matrix::from_value(value).expect("Matrix arguments are checked before the call");
+            registry.register_native("transpose", Parameters::Fixed(vec![ParameterType::Matrix]), move |arguments| {
+                Ok(matrix(&arguments[0]).transpose().to_value())
+            });
+            registry.register_native("det", Parameters::Fixed(vec![ParameterType::Matrix]), |arguments| {
+                Ok(Value::Float(square(&arguments[0])?.determinant()))
+            });
+            registry.register_native("inv", Parameters::Fixed(vec![ParameterType::Matrix]), |arguments| {
+                match square(&arguments[0])?.inverse() {
+                    Some(inverse) => Ok(inverse.to_value()),
+                    None => Err(CallError::Failed("the matrix is singular, so it has no inverse".to_string())),
+                }
+            });
+            registry.register_native(
+                "solve",
+                Parameters::Fixed(vec![ParameterType::Matrix, ParameterType::Array]),
+                |arguments| {
+                    let a = square(&arguments[0])?;
+                    let array = Array::from_value(&arguments[1]).expect("Array arguments are checked before the call");
+                    let (b, vector) = match &array {
+                        Array::Vector(b) => (Matrix::column(b), true),
+                        Array::Matrix(b) => (b.clone(), false),
+                    };
+                    if b.rows() != a.rows() {
+                        return Err(CallError::ShapeMismatch {
+                            lhs: 1,
+                            rhs: 2,
+                            lhs_shape: a.shape(),
+                            rhs_shape: array.shape(),
+                        });
+                    }
+                    match a.solve(&b) {
+                        Some(x) if vector => Ok(x.to_vector()),
+                        Some(x) => Ok(x.to_value()),
+                        None => Err(CallError::Failed("the matrix is singular, so there is no unique solution".to_string())),
+                    }
+                },
+            );
+            registry.register_native("dot", Parameters::Fixed(vec![ParameterType::List, ParameterType::List]), |arguments| {
+                let (u, v) = (vector(&arguments[0]), vector(&arguments[1]));
+                dot(&u, &v).map(Value::Float).ok_or_else(|| CallError::ShapeMismatch {
+                    lhs: 1,
+                    rhs: 2,
+                    lhs_shape: format!("vector of {}", u.len()),
+                    rhs_shape: format!("vector of {}", v.len()),
+                })
+            });
+            registry.register_native("cross", Parameters::Fixed(vec![ParameterType::List, ParameterType::List]), |arguments| {
+                let (u, v) = (vector(&arguments[0]), vector(&arguments[1]));
+                match (u.len(), v.len()) {
+                    (3, 3) => Ok(Value::List(cross(&u, &v).into_iter().map(number).collect())),
+                    (a, b) if a == b => Err(CallError::Failed(format!("the cross product needs vectors of 3, found vectors of {}", a))),
+                    (a, b) => Err(CallError::ShapeMismatch {
+                        lhs: 1,
+                        rhs: 2,
+                        lhs_shape: format!("vector of {}", a),
+                        rhs_shape: format!("vector of {}", b),
+                    }),
+                }
+            });
+            registry.register_native("norm", Parameters::Fixed(vec![ParameterType::Array]), |arguments| {
+                let norm = match Array::from_value(&arguments[0]) {
+                    Some(Array::Vector(v)) => Matrix::column(&v).norm(),
+                    Some(Array::Matrix(m)) => m.norm(),
+                    None => unreachable!("Array arguments are checked before the call"),
+                };
+                Ok(Value::Float(norm))
+            });
+            registry.register_native("identity", Parameters::Fixed(vec![ParameterType::Integer]), |arguments| {
+                Ok(Matrix::identity(size(&arguments[0])?).to_value())
+            });
+            registry.register_native("zeros", Parameters::Fixed(vec![ParameterType::Integer, ParameterType::Integer]), |arguments| {
+                Ok(Matrix::zeros(size(&arguments[0])?, size(&arguments[1])?).to_value())
+            });
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+
+        use super::*;
+
+        fn matrix(rows: &[&[f64]]) -> Matrix {
+            Matrix::new(rows.len(), rows[0].len(), rows.concat())
+        }
+
+        fn assert_close(actual: &Matrix, expected: &Matrix) {
+            assert_eq!((actual.rows(), actual.columns()), (expected.rows(), expected.columns()));
+            for (a, b) in actual.elements.iter().zip(&expected.elements) {
+                assert!((a - b).abs() < 1e-12, "{} != {}", actual, expected);
+            }
+        }
+
+        #[test]
+        fn test_matrix_operations() {
+            let a = matrix(&[&[1.0, 2.0], &[3.0, 4.0]]);
+            assert_eq!(a.transpose(), matrix(&[&[1.0, 3.0], &[2.0, 4.0]]));
+            assert_eq!(a.multiply(&Matrix::identity(2)), Some(a.clone()));
+            assert_eq!(a.multiply(&Matrix::zeros(3, 1)), None);
+            assert!((a.determinant() + 2.0).abs() < 1e-12);
+            assert_close(&a.inverse().unwrap(), &matrix(&[&[-2.0, 1.0], &[1.5, -0.5]]));
+            assert_close(&a.solve(&Matrix::column(&[5.0, 11.0])).unwrap(), &Matrix::column(&[1.0, 2.0]));
+            assert_eq!(matrix(&[&[1.0, 2.0], &[2.0, 4.0]]).inverse(), None);
+            assert_eq!(matrix(&[&[1.0, 2.0], &[2.0, 4.0]]).determinant(), 0.0);
+            // A zero in the first pivot needs a row swap, which flips the sign of the determinant.
+            assert!((matrix(&[&[0.0, 1.0], &[1.0, 0.0]]).determinant() + 1.0).abs() < 1e-12);
+            // The tolerance is relative to the largest element, so a matrix of small numbers isn't singular.
+            let small = matrix(&[&[1e-20, 0.0], &[0.0, 1e-20]]);
+            assert_eq!(small.inverse(), Some(matrix(&[&[1e20, 0.0], &[0.0, 1e20]])));
+            assert_eq!(Matrix::zeros(2, 2).inverse(), None);
+            assert_eq!(cross(&[1.0, 0.0, 0.0], &[0.0, 1.0, 0.0]), [0.0, 0.0, 1.0]);
+        }
+
+        #[test]
+        fn test_product() {
+            let value = |rows: &[&[i128]]| Value::List(rows.iter().map(|row| Value::List(row.iter().copied().map(Value::Integer).collect())).collect());
+            let a = value(&[&[1, 2], &[3, 4]]);
+            assert_eq!(product(&a, &a), Ok(value(&[&[7, 10], &[15, 22]])));
+            let v = Value::List(vec![Value::Integer(1), Value::Integer(1)]);
+            assert_eq!(product(&a, &v), Ok(Value::List(vec![Value::Integer(3), Value::Integer(7)])));
+            assert_eq!(product(&v, &a), Ok(Value::List(vec![Value::Integer(4), Value::Integer(6)])));
+            assert_eq!(product(&v, &v), Ok(Value::Float(2.0)));
+            assert_eq!(
+                product(&a, &value(&[&[1, 2, 3]])),
+                Err(ProductError::Shapes("2×2 matrix".to_string(), "1×3 matrix".to_string()))
+            );
+            assert_eq!(product(&a, &Value::Integer(2)), Err(ProductError::NotAnArray(Value::Integer(2))));
+            assert_eq!(Matrix::from_value(&value(&[&[1, 2], &[3]])), None);
+        }
+    }
+}
@@ -0,0 +1,527 @@
+pub mod plot {
+    use std::io::{self, Write};
+
+    use nu_ansi_term::Style;
+
+    use crate::expr_writer::expr_writer::TreeStyle;
+    use crate::format::format::{NumberFormat, Precision};
+    use crate::grammar::grammar::{reevaluate, Environment, EvalError, Expr};
+    use crate::symbolic::symbolic::{derivative, substitute};
+    use crate::value::value::Value;
+
+    /// The size of the plot in the terminal, not counting the labels. Each character is 2 by 4 braille dots.
+    const COLUMNS: usize = 60;
+    const ROWS: usize = 15;
+    /// The number of intervals each expression is sampled at, which is enough for the SVG as well.
+    const SAMPLES: usize = 600;
+    /// The iterations used to pin down a root or an extremum between two samples.
+    const REFINEMENTS: usize = 60;
+    const SVG_WIDTH: f64 = 640.0;
+    const SVG_HEIGHT: f64 = 400.0;
+    const SVG_MARGIN: f64 = 50.0;
+    /// The colours of the curves in an SVG file, in order.
+    const SVG_COLORS: [&str; 5] = ["#1f77b4", "#d62728", "#2ca02c", "#ff7f0e", "#9467bd"];
+    const ROOT_MARKER: char = '○';
+    const MAXIMUM_MARKER: char = '▲';
+    const MINIMUM_MARKER: char = '▼';
+
+    /// A local maximum or minimum of a curve.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct Extremum {
+        pub x: f64,
+        pub y: f64,
+        pub maximum: bool,
+    }
+
+    /// An expression sampled across the range, where it is NaN wherever it has no value.
+    #[derive(Debug, Clone)]
+    pub struct Curve {
+        pub label: String,
+        pub points: Vec<(f64, f64)>,
+        pub roots: Vec<f64>,
+        pub extrema: Vec<Extremum>,
+    }
+
+    /// One or more curves over the same range of a variable.
+    #[derive(Debug, Clone)]
+    pub struct Plot {
+        pub variable: String,
+        pub x_range: (f64, f64),
+        /// Chosen to fit the curves, leaving out the values near a pole.
+        pub y_range: (f64, f64),
+        pub curves: Vec<Curve>,
+    }
+
+    /// An expression as a function of the variable.
+    struct Function {
+        expr: Expr,
+        name: String,
+        /// The session's variables, with the variable set to wherever the function was last evaluated.
+        environment: Environment,
+    }
+
+    impl Function {
+        fn at(&mut self, x: f64) -> Result<f64, EvalError> {
+            self.environment.variables.insert(self.name.clone(), Expr::literal(Value::Float(x)));
+            Ok(match reevaluate(&self.expr, &self.environment)?.value() {
+                value @ (Value::Integer(_) | Value::Word(_) | Value::Float(_) | Value::Bool(_)) => value.as_f64(),
+                _ => f64::NAN,
+            })
+        }
+
+        /// The value at `x`, where a point that can't be evaluated, such as `0!` at `-1`, is a gap.
+        fn sample(&mut self, x: f64) -> f64 {
+            self.at(x).unwrap_or(f64::NAN)
+        }
+    }
+
+    /// The point of the bracket where the function changes sign.
+    fn bisect(function: &mut Function, (mut low, mut high): (f64, f64)) -> f64 {
+        let low_negative = function.sample(low) < 0.0;
+        for _ in 0..REFINEMENTS {
+            let middle = (low + high) / 2.0;
+            let value = function.sample(middle);
+            if value == 0.0 {
+                return middle;
+            }
+            if (value < 0.0) == low_negative {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+        (low + high) / 2.0
+    }
+
+    /// The highest point in the bracket by golden-section search, or the lowest one if `maximum` is false.
+    fn golden_section(function: &mut Function, (mut low, mut high): (f64, f64), maximum: bool) -> f64 {
+        let ratio = (5f64.sqrt() - 1.0) / 2.0;
+        let mut value = |x: f64| if maximum { function.sample(x) } else { -function.sample(x) };
+        for _ in 0..REFINEMENTS {
+            let (left, right) = (high - ratio * (high - low), low + ratio * (high - low));
+            if value(left) > value(right) {
+                high = right;
+            } else {
+                low = left;
+            }
+        }
+        (low + high) / 2.0
+    }
+
+    /// The range of values the curves are drawn over. The extreme values are left out if they are far from
+    /// the rest, as they are near a pole, so that the rest of the curve doesn't look flat.
+    fn y_range(curves: &[Curve]) -> (f64, f64) {
+        let mut values = curves
+            .iter()
+            .flat_map(|curve| curve.points.iter().map(|(_, y)| *y))
+            .filter(|y| y.is_finite())
+            .collect::<Vec<_>>();
+        if values.is_empty() {
+            return (-1.0, 1.0);
+        }
+        values.sort_by(f64::total_cmp);
+        let percentile = |fraction: f64| values[((values.len() - 1) as f64 * fraction).round() as usize];
+        let (typical_low, typical_high) = (percentile(0.05), percentile(0.95));
+        let spread = typical_high - typical_low;
+        let low = values[0].max(typical_low - spread);
+        let high = values[values.len() - 1].min(typical_high + spread);
+        if high - low <= f64::EPSILON * low.abs().max(1.0) {
+            return (low - 1.0, high + 1.0);
+        }
+        let padding = (high - low) * 0.05;
+        (low - padding, high + padding)
+    }
+
+    /// Samples each expression over the range of the variable, and finds its roots and extrema.
+    pub fn plot(exprs: Vec<(String, Expr)>, variable: &str, x_range: (f64, f64), environment: &Environment) -> Result<Plot, EvalError> {
+        let (low, high) = x_range;
+        let mut functions = vec![];
+        let mut curves = vec![];
+        for (label, expr) in exprs {
+            let expr = substitute(&expr, variable, &Expr::FreeVariable { name: variable.to_string() });
+            // Extrema are found more precisely as the roots of the derivative, if there is one.
+            let slope = derivative(&expr, variable).ok().map(|expr| Function {
+                expr,
+                name: variable.to_string(),
+                environment: environment.clone(),
+            });
+            let mut function = Function {
+                expr,
+                name: variable.to_string(),
+                environment: environment.clone(),
+            };
+            let xs = (0..=SAMPLES).map(|index| low + (high - low) * index as f64 / SAMPLES as f64);
+            let points = xs.map(|x| (x, function.sample(x))).collect::<Vec<_>>();
+            // A curve with no value anywhere is most likely a mistake, which its first point explains.
+            if points.iter().all(|(_, y)| y.is_nan()) {
+                function.at(low)?;
+            }
+            functions.push((function, slope));
+            curves.push(Curve {
+                label,
+                points,
+                roots: vec![],
+                extrema: vec![],
+            });
+        }
+
+        let y_range = y_range(&curves);
+        let tolerance = (y_range.1 - y_range.0) * 1e-6;
+        let visible = |y: f64| y.is_finite() && (y_range.0..=y_range.1).contains(&y);
+        // Refining a point at zero can leave it off by the rounding of the samples, as in `-2.9e-20`.
+        let snap = |x: f64| if x.abs() <= (high - low) * 1e-12 { 0.0 } else { x };
+        for (curve, (function, slope)) in curves.iter_mut().zip(&mut functions) {
+            let points = &curve.points;
+            for (index, &(x, y)) in points.iter().enumerate() {
+                // A stretch where the curve is zero, as in `floor(x)`, doesn't count as roots.
+                let zero = |index: Option<usize>| index.and_then(|index| points.get(index)).is_some_and(|(_, y)| *y == 0.0);
+                if y == 0.0 && !zero(index.checked_sub(1)) && !zero(Some(index + 1)) {
+                    curve.roots.push(x);
+                }
+                let Some(&(next_x, next_y)) = points.get(index + 1) else { break };
+                if y * next_y < 0.0 {
+                    // A sign change across a pole, as in `1/x`, isn't a root.
+                    let root = bisect(function, (x, next_x));
+                    if function.sample(root).abs() <= tolerance {
+                        curve.roots.push(root);
+                    }
+                }
+            }
+            for index in 1..points.len() {
+                let ((before_x, before), (_, y)) = (points[index - 1], points[index]);
+                // A flat stretch is a maximum if the curve goes down on both sides of it, but not a step up.
+                let Some(&(after_x, after)) = points[index..].iter().find(|(_, after)| *after != y) else { break };
+                let maximum = y > before && y > after;
+                if !(maximum || (y < before && y < after)) {
+                    continue;
+                }
+                let brackets_root = slope.as_mut().is_some_and(|slope| slope.sample(before_x) * slope.sample(after_x) <= 0.0);
+                let x = match slope {
+                    Some(slope) if brackets_root => bisect(slope, (before_x, after_x)),
+                    _ => golden_section(function, (before_x, after_x), maximum),
+                };
+                let y = function.sample(x);
+                if visible(y) {
+                    curve.extrema.push(Extremum { x: snap(x), y, maximum });
+                }
+            }
+            for root in &mut curve.roots {
+                *root = snap(*root);
+            }
+        }
+        Ok(Plot {
+            variable: variable.to_string(),
+            x_range,
+            y_range,
+            curves,
+        })
+    }
+
+    /// Round values between `low` and `high` to label an axis with, about `count` of them.
+    fn ticks(low: f64, high: f64, count: usize) -> Vec<f64> {
+        let raw = (high - low) / count as f64;
+        let magnitude = 10f64.powf(raw.log10().floor());
+        let step = [1.0, 2.0, 5.0, 10.0].into_iter().map(|multiple| multiple * magnitude).find(|step| *step >= raw).unwrap();
+        let first = (low / step).ceil() as i64;
+        let last = (high / step).floor() as i64;
+        // Rounded through the step, so that the labels read 0.3 rather than 0.30000000000000004.
+        (first..=last).map(|index| index as f64 * step).collect()
+    }
+
+    fn tick_label(value: f64) -> String {
+        let numbers = NumberFormat {
+            precision: Precision::Significant(6),
+            ..NumberFormat::default()
+        };
+        numbers.format_float(if value.abs() < 1e-12 { 0.0 } else { value })
+    }
+
+    /// The style each curve is drawn in, taken from the styles of the tree.
+    fn curve_style(style: &TreeStyle, index: usize) -> Style {
+        [style.result, style.value, style.string, style.hint, style.variable][index % 5]
+    }
+
+    /// A grid of braille characters, each of which is 2 dots wide and 4 high.
+    struct Canvas {
+        dots: Vec<Vec<u8>>,
+        /// The curve drawn last in each cell, which it is coloured as.
+        curves: Vec<Vec<Option<usize>>>,
+        /// Characters drawn over the dots, such as the markers for roots.
+        overlay: Vec<Vec<Option<(char, Style)>>>,
+    }
+
+    impl Canvas {
+        fn new() -> Self {
+            Canvas {
+                dots: vec![vec![0; COLUMNS]; ROWS],
+                curves: vec![vec![None; COLUMNS]; ROWS],
+                overlay: vec![vec![None; COLUMNS]; ROWS],
+            }
+        }
+
+        fn set(&mut self, x: i64, y: i64, curve: usize) {
+            if x < 0 || y < 0 || x >= 2 * COLUMNS as i64 || y >= 4 * ROWS as i64 {
+                return;
+            }
+            const BITS: [[u8; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+            let (column, row) = (x as usize / 2, y as usize / 4);
+            self.dots[row][column] |= BITS[x as usize % 2][y as usize % 4];
+            self.curves[row][column] = Some(curve);
+        }
+
+        /// Draws a line between two dots, where `y` may be off the canvas.
+        fn line(&mut self, (x0, y0): (i64, i64), (x1, y1): (i64, i64), curve: usize) {
+            let steps = (x1 - x0).abs().max((y1 - y0).abs()).max(1);
+            for step in 0..=steps {
+                let x = x0 + (x1 - x0) * step / steps;
+                let y = y0 + (y1 - y0) * step / steps;
+                self.set(x, y, curve);
+            }
+        }
+    }
+
+    /// Draws the plot in braille, with the ticks of each axis, followed by a legend that lists the roots
+    /// and extrema of each curve.
+    pub fn write_plot(out: &mut dyn Write, plot: &Plot, style: &TreeStyle) -> io::Result<()> {
+        let ((x_low, x_high), (y_low, y_high)) = (plot.x_range, plot.y_range);
+        let dot_x = |x: f64| ((x - x_low) / (x_high - x_low) * (2 * COLUMNS - 1) as f64).round() as i64;
+        let dot_y = |y: f64| ((y_high - y) / (y_high - y_low) * (4 * ROWS - 1) as f64).round() as i64;
+        // How far off the canvas a line is drawn, so that steep lines still reach the edge.
+        let clamp = |y: i64| y.clamp(-1, 4 * ROWS as i64);
+
+        let mut canvas = Canvas::new();
+        for (index, curve) in plot.curves.iter().enumerate() {
+            for window in curve.points.windows(2) {
+                let ((x0, y0), (x1, y1)) = (window[0], window[1]);
+                // A jump from one edge of the plot to the other is a pole, which isn't joined up.
+                let across = (y0 > y_high && y1 < y_low) || (y0 < y_low && y1 > y_high);
+                if y0.is_finite() && y1.is_finite() && !across {
+                    canvas.line((dot_x(x0), clamp(dot_y(y0))), (dot_x(x1), clamp(dot_y(y1))), index);
+                }
+            }
+        }
+        for curve in &plot.curves {
+            let markers = curve.roots.iter().map(|x| (*x, 0.0, ROOT_MARKER)).chain(curve.extrema.iter().map(|extremum| {
+                let marker = if extremum.maximum { MAXIMUM_MARKER } else { MINIMUM_MARKER };
+                (extremum.x, extremum.y, marker)
+            }));
+            for (x, y, marker) in markers {
+                let (column, row) = (dot_x(x) / 2, dot_y(y) / 4);
+                if (0..COLUMNS as i64).contains(&column) && (0..ROWS as i64).contains(&row) {
+                    canvas.overlay[row as usize][column as usize] = Some((marker, style.hint));
+                }
+            }
+        }
+
+        // The axes go through zero, if it is in the range.
+        let axis_row = (y_low..=y_high).contains(&0.0).then(|| dot_y(0.0) as usize / 4);
+        let axis_column = (x_low..=x_high).contains(&0.0).then(|| dot_x(0.0) as usize / 2);
+        let y_ticks = ticks(y_low, y_high, 4)
+            .into_iter()
+            .map(|y| (dot_y(y) as usize / 4, tick_label(y)))
+            .collect::<Vec<_>>();
+        let margin = y_ticks.iter().map(|(_, label)| label.chars().count()).max().unwrap_or(0);
+
+        for row in 0..ROWS {
+            let tick = y_ticks.iter().find(|(tick_row, _)| *tick_row == row);
+            let label = tick.map_or("", |(_, label)| label.as_str());
+            let edge = if tick.is_some() { "┤" } else { "│" };
+            write!(out, "{}", style.annotation.paint(format!("{:>margin$} {}", label, edge, margin = margin)))?;
+            for column in 0..COLUMNS {
+                let text = match (canvas.overlay[row][column], canvas.curves[row][column]) {
+                    (Some((marker, marker_style)), _) => marker_style.paint(marker.to_string()),
+                    (None, Some(curve)) => curve_style(style, curve).paint(char::from_u32(0x2800 + canvas.dots[row][column] as u32).unwrap().to_string()),
+                    (None, None) => {
+                        let axis = match (Some(row) == axis_row, Some(column) == axis_column) {
+                            (true, true) => "┼",
+                            (true, false) => "─",
+                            (false, true) => "│",
+                            (false, false) => " ",
+                        };
+                        style.annotation.paint(axis.to_string())
+                    }
+                };
+                write!(out, "{}", text)?;
+            }
+            writeln!(out)?;
+        }
+
+        // The x axis, with a label under each tick that doesn't run into the one before it.
+        let x_ticks = ticks(x_low, x_high, COLUMNS / 12);
+        let columns = x_ticks.iter().map(|x| dot_x(*x) as usize / 2).collect::<Vec<_>>();
+        let axis = (0..COLUMNS).map(|column| if columns.contains(&column) { '┬' } else { '─' }).collect::<String>();
+        writeln!(out, "{}", style.annotation.paint(format!("{:>margin$} └{}", "", axis, margin = margin)))?;
+        let mut labels = String::new();
+        for (x, column) in x_ticks.iter().zip(columns) {
+            let label = tick_label(*x);
+            let start = (margin + 2 + column).saturating_sub(label.chars().count() / 2);
+            let length = labels.chars().count();
+            if start > length || length == 0 {
+                labels.push_str(&" ".repeat(start - length));
+                labels.push_str(&label);
+            }
+        }
+        writeln!(out, "{}", style.annotation.paint(format!("{}  {}", labels, plot.variable)))?;
+
+        let numbers = &style.numbers;
+        for (index, curve) in plot.curves.iter().enumerate() {
+            writeln!(out, "{} {}", curve_style(style, index).paint("⣿"), curve.label)?;
+            if !curve.roots.is_empty() {
+                let roots = curve.roots.iter().map(|x| numbers.format_float(*x)).collect::<Vec<_>>();
+                writeln!(out, "  {} roots at {} = {}", style.hint.paint(ROOT_MARKER.to_string()), plot.variable, roots.join(", "))?;
+            }
+            for (maximum, marker, name) in [(true, MAXIMUM_MARKER, "maxima"), (false, MINIMUM_MARKER, "minima")] {
+                let extrema = curve
+                    .extrema
+                    .iter()
+                    .filter(|extremum| extremum.maximum == maximum)
+                    .map(|extremum| format!("({}, {})", numbers.format_float(extremum.x), numbers.format_float(extremum.y)))
+                    .collect::<Vec<_>>();
+                if !extrema.is_empty() {
+                    writeln!(out, "  {} {} at {}", style.hint.paint(marker.to_string()), name, extrema.join(", "))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn escape(text: &str) -> String {
+        text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+    }
+
+    /// Writes the plot as an SVG image, with a grid at the ticks of each axis and the same markers.
+    pub fn write_svg(out: &mut dyn Write, plot: &Plot) -> io::Result<()> {
+        let ((x_low, x_high), (y_low, y_high)) = (plot.x_range, plot.y_range);
+        let (left, top, right, bottom) = (SVG_MARGIN, SVG_MARGIN / 2.0, SVG_WIDTH - SVG_MARGIN / 2.0, SVG_HEIGHT - SVG_MARGIN);
+        let px = |x: f64| left + (x - x_low) / (x_high - x_low) * (right - left);
+        let py = |y: f64| top + (y_high - y) / (y_high - y_low) * (bottom - top);
+
+        writeln!(
+            out,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="sans-serif" font-size="12">"#,
+            w = SVG_WIDTH,
+            h = SVG_HEIGHT
+        )?;
+        writeln!(out, r#"<rect width="100%" height="100%" fill="white"/>"#)?;
+        writeln!(
+            out,
+            r#"<defs><clipPath id="area"><rect x="{}" y="{}" width="{}" height="{}"/></clipPath></defs>"#,
+            left,
+            top,
+            right - left,
+            bottom - top
+        )?;
+        for x in ticks(x_low, x_high, 8) {
+            let stroke = if x == 0.0 { "#888" } else { "#eee" };
+            writeln!(out, r#"<line x1="{x:.2}" y1="{top}" x2="{x:.2}" y2="{bottom}" stroke="{stroke}"/>"#, x = px(x))?;
+            writeln!(out, r#"<text x="{:.2}" y="{}" text-anchor="middle">{}</text>"#, px(x), bottom + 16.0, tick_label(x))?;
+        }
+        for y in ticks(y_low, y_high, 6) {
+            let stroke = if y == 0.0 { "#888" } else { "#eee" };
+            writeln!(out, r#"<line x1="{left}" y1="{y:.2}" x2="{right}" y2="{y:.2}" stroke="{stroke}"/>"#, y = py(y))?;
+            writeln!(out, r#"<text x="{}" y="{:.2}" text-anchor="end" dominant-baseline="middle">{}</text>"#, left - 6.0, py(y), tick_label(y))?;
+        }
+        writeln!(
+            out,
+            r##"<rect x="{}" y="{}" width="{}" height="{}" fill="none" stroke="#888"/>"##,
+            left,
+            top,
+            right - left,
+            bottom - top
+        )?;
+        writeln!(out, r#"<text x="{}" y="{}" text-anchor="middle">{}</text>"#, (left + right) / 2.0, SVG_HEIGHT - 10.0, escape(&plot.variable))?;
+
+        for (index, curve) in plot.curves.iter().enumerate() {
+            let color = SVG_COLORS[index % SVG_COLORS.len()];
+            // A new piece starts after a gap or a pole, and points far off the plot are pulled in to its edge.
+            let limit = (y_high - y_low) * 10.0;
+            let mut path = String::new();
+            let mut previous: Option<f64> = None;
+            for &(x, y) in &curve.points {
+                let joined = previous.is_some_and(|previous| !((previous > y_high && y < y_low) || (previous < y_low && y > y_high)));
+                if !y.is_finite() {
+                    previous = None;
+                    continue;
+                }
+                let y = y.clamp(y_low - limit, y_high + limit);
+                path.push_str(&format!("{}{:.2},{:.2} ", if joined { "L" } else { "M" }, px(x), py(y)));
+                previous = Some(y);
+            }
+            writeln!(
+                out,
+                r#"<path d="{}" fill="none" stroke="{}" stroke-width="1.5" clip-path="url(#area)"/>"#,
+                path.trim_end(),
+                color
+            )?;
+            for x in &curve.roots {
+                writeln!(out, r#"<circle cx="{:.2}" cy="{:.2}" r="3.5" fill="white" stroke="{}"/>"#, px(*x), py(0.0), color)?;
+            }
+            for extremum in &curve.extrema {
+                writeln!(out, r#"<circle cx="{:.2}" cy="{:.2}" r="3.5" fill="{}"/>"#, px(extremum.x), py(extremum.y), color)?;
+            }
+            writeln!(out, r#"<text x="{}" y="{}" fill="{}">{}</text>"#, left + 8.0, top + 16.0 * (index + 1) as f64, color, escape(&curve.label))?;
+        }
+        writeln!(out, "</svg>")
+    }
+
+    #[cfg(test)]
+    mod tests {
+
+        use super::*;
+        use crate::context::context::Context;
+
+        fn sampled(inputs: &[&str], range: (f64, f64)) -> Plot {
+            let mut context = Context::new();
+            context.environment_mut().symbolic = true;
+            let exprs = inputs.iter().map(|input| (input.to_string(), context.evaluate(input).unwrap().expr)).collect();
+            plot(exprs, "x", range, context.environment()).unwrap()
+        }
+
+        fn close(actual: &[f64], expected: &[f64]) -> bool {
+            actual.len() == expected.len() && actual.iter().zip(expected).all(|(actual, expected)| (actual - expected).abs() < 1e-9)
+        }
+
+        #[test]
+        fn test_roots_and_extrema() {
+            let plot = sampled(&["x^2 - 2", "sin(x)"], (-5.0, 5.0));
+            let [parabola, sine] = &plot.curves[..] else { panic!() };
+            assert!(close(&parabola.roots, &[-2f64.sqrt(), 2f64.sqrt()]));
+            assert_eq!(parabola.extrema.len(), 1);
+            assert!(!parabola.extrema[0].maximum && close(&[parabola.extrema[0].x, parabola.extrema[0].y], &[0.0, -2.0]));
+            let pi = std::f64::consts::PI;
+            assert!(close(&sine.roots, &[-pi, 0.0, pi]));
+            let maxima = sine.extrema.iter().filter(|extremum| extremum.maximum).map(|extremum| extremum.x).collect::<Vec<_>>();
+            assert!(maxima.iter().zip([-1.5 * pi, 0.5 * pi]).all(|(x, expected)| (x - expected).abs() < 1e-6));
+            assert!(plot.y_range.0 < -2.0 && plot.y_range.1 > 23.0);
+        }
+
+        #[test]
+        fn test_poles() {
+            let plot = sampled(&["1/x"], (-2.0, 2.0));
+            // The sign change at the pole isn't a root, and the range isn't stretched to fit it.
+            assert!(plot.curves[0].roots.is_empty());
+            assert!(plot.y_range.1 < 100.0);
+        }
+
+        #[test]
+        fn test_write_plot() {
+            let mut out = vec![];
+            write_plot(&mut out, &sampled(&["x^2 - 1"], (-2.0, 2.0)), &TreeStyle::plain()).unwrap();
+            let text = String::from_utf8(out).unwrap();
+            let lines = text.lines().collect::<Vec<_>>();
+            assert_eq!(lines.len(), ROWS + 5);
+            assert!(lines[4].starts_with("2 ┤") && lines[10].starts_with("0 ┤─"));
+            assert!(lines[10].contains('┼'));
+            assert!(lines[ROWS + 1].starts_with("  -2 ") && lines[ROWS + 1].ends_with(" 2  x"));
+            assert_eq!(lines[ROWS + 3], "  ○ roots at x = -1, 1");
+            assert_eq!(lines[ROWS + 4], "  ▼ minima at (0, -1)");
+            assert!(text.contains(ROOT_MARKER) && text.contains(MINIMUM_MARKER));
+
+            let mut out = vec![];
+            write_svg(&mut out, &sampled(&["x < 0"], (-1.0, 1.0))).unwrap();
+            let svg = String::from_utf8(out).unwrap();
+            assert!(svg.starts_with("<svg") && svg.trim_end().ends_with("</svg>"));
+            assert!(svg.contains(">x &lt; 0</text>"));
+        }
+    }
+}
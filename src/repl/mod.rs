@@ -0,0 +1,604 @@
+pub mod repl {
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::fs::File;
+    use std::io::{self, Write};
+    use std::path::PathBuf;
+
+    use crate::commands::commands::{self, find_command, help, setting, Argument, Setting};
+    use crate::config::config::{Config, EditMode};
+    use crate::constants::constants::CONSTANTS;
+    use crate::context::context::{describe_unbound_variables, Context, Error, Evaluation};
+    use crate::expr_writer::expr_writer::{write_expr, write_programmer_view, ColorChoice, Layout, LayoutOptions, TreeStyle};
+    use crate::format::format::Precision;
+    use crate::grammar::grammar::{parse_equation, AngleUnit, EvalError, Expr, Radix, Rule};
+    use crate::plot::plot::{self, write_plot, write_svg};
+    use crate::simplify::simplify::{simplify, steps};
+    use crate::suggest::suggest::did_you_mean;
+    use crate::value::value::{IntegerMode, Overflow, Value, Word, WordSize};
+
+    /// An interactive session: evaluates each line of input, handles the `:` commands, and writes
+    /// the results to the given output.
+    #[derive(Debug)]
+    pub struct Session {
+        pub context: Context,
+        /// Warn when an expression evaluates differently under the old precedence rules.
+        pub warn_precedence: bool,
+        /// How the expression trees are coloured.
+        pub style: TreeStyle,
+        /// The colour setting the style was chosen with, if one was given.
+        pub color: Option<ColorChoice>,
+        /// How the expression trees are laid out.
+        pub layout: LayoutOptions,
+        /// The prompt, where `{n}` is replaced by the number of the next result.
+        pub prompt: String,
+        pub edit_mode: EditMode,
+        /// The config file the settings were read from, if there was one.
+        pub config_path: Option<PathBuf>,
+        last_expr: Option<Expr>,
+    }
+
+    impl Default for Session {
+        fn default() -> Self {
+            Session {
+                context: Context::default(),
+                warn_precedence: false,
+                style: TreeStyle::default(),
+                color: None,
+                layout: LayoutOptions::default(),
+                prompt: "${n} ".to_string(),
+                edit_mode: EditMode::default(),
+                config_path: None,
+                last_expr: None,
+            }
+        }
+    }
+
+    impl Session {
+        pub fn new(context: Context) -> Self {
+            Session {
+                context,
+                ..Default::default()
+            }
+        }
+
+        /// The prompt shows the number the next result will have, for referring back to it with `$n`.
+        pub fn prompt(&self) -> String {
+            let next = self.context.environment().history.len() + 1;
+            self.prompt.replace("{n}", &next.to_string())
+        }
+
+        /// The angle mode, which is shown on the right of the prompt.
+        pub fn right_prompt(&self) -> String {
+            self.context.environment().angle_unit.to_string()
+        }
+
+        /// Changes the angle mode, both for evaluating and for showing angles.
+        pub fn set_angle_unit(&mut self, unit: AngleUnit) {
+            self.context.environment_mut().angle_unit = unit;
+            self.style.numbers.angle = unit;
+        }
+
+        /// Runs a script of definitions and commands, such as the rc file, without printing the results.
+        /// Errors are reported with the line they occurred on, and the script carries on after them. The
+        /// results are not added to the history, so that `$1` is still the first result typed in.
+        pub fn run_script(&mut self, source: &str, name: &str, out: &mut dyn Write) -> io::Result<()> {
+            for (index, line) in source.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with("//") {
+                    continue;
+                }
+                if line.starts_with(':') {
+                    self.handle_input(line, out)?;
+                    continue;
+                }
+                if let Err(e) = self.context.eval(line) {
+                    writeln!(out, "{}:{}: {}", name, index + 1, e)?;
+                }
+            }
+            self.context.environment_mut().history.clear();
+            Ok(())
+        }
+
+        pub fn handle_input(&mut self, buffer: &str, out: &mut dyn Write) -> io::Result<()> {
+            if let Ok(mut pairs) = parse_equation(buffer) {
+                if let Some(pair) = pairs.next().filter(|pair| pair.as_rule() == Rule::command) {
+                    let mut inner_pairs = pair.into_inner();
+                    let command = inner_pairs.next().unwrap().as_str();
+                    let arguments = inner_pairs.map(|pair| pair.as_str()).collect::<Vec<_>>();
+                    return self.handle_command(command, &arguments, out);
+                }
+            }
+
+            let legacy = if self.warn_precedence {
+                self.context.evaluate_legacy(buffer).ok()
+            } else {
+                None
+            };
+
+            match self.context.evaluate(buffer) {
+                Ok(evaluation) => {
+                    if let Some(legacy) = legacy {
+                        warn_if_precedence_changed(out, buffer, &evaluation.expr, &legacy)?;
+                    }
+                    self.write_evaluation(out, &evaluation)?;
+                    self.last_expr.replace(evaluation.expr);
+                    Ok(())
+                }
+                Err(e) => write_error(out, buffer, &e),
+            }
+        }
+
+        /// Prints the tree for the evaluated expression, along with the programmer view of the result in programmer mode.
+        fn write_evaluation(&self, out: &mut dyn Write, evaluation: &Evaluation) -> io::Result<()> {
+            let unbound = evaluation.expr.unbound_variables();
+            if !unbound.is_empty() {
+                writeln!(out, "{}", describe_unbound_variables(&unbound))?;
+            }
+            // Without a value, the best that can be shown is the expression in its simplest form.
+            if !unbound.is_empty() || !evaluation.expr.free_variables().is_empty() {
+                match simplify(&evaluation.expr, self.context.environment()) {
+                    Ok(simplified) if simplified.to_string() != evaluation.expr.to_string() => writeln!(out, "= {}", simplified)?,
+                    _ => {}
+                }
+            }
+
+            write_expr(out, &evaluation.expr, &self.style, &self.layout)?;
+            if let Some(mode) = self.context.environment().integer_mode {
+                let word = match evaluation.value() {
+                    Value::Integer(value) => Some(Word::new(value, mode.word)),
+                    Value::Word(word) => Some(word.resize(mode.word)),
+                    _ => None,
+                };
+                if let Some(word) = word {
+                    write_programmer_view(out, word, &self.style)?;
+                }
+            }
+            Ok(())
+        }
+
+        fn handle_command(&mut self, name: &str, arguments: &[&str], out: &mut dyn Write) -> io::Result<()> {
+            let parsed = find_command(name).and_then(|command| Ok((command, command.parse(arguments)?)));
+            let (command, arguments) = match parsed {
+                Ok(parsed) => parsed,
+                Err(e) => return writeln!(out, "{}", e),
+            };
+
+            let environment = self.context.environment_mut();
+            match (command.name, arguments.as_slice()) {
+                ("state", _) => {
+                    for (key, value) in environment.variables.iter() {
+                        writeln!(out, "{} = {:?}", key, value)?;
+                    }
+                    writeln!(out, "Constants:")?;
+                    for constant in CONSTANTS {
+                        let shadowed = if environment.variables.contains_key(constant.name) {
+                            " (shadowed)"
+                        } else {
+                            ""
+                        };
+                        writeln!(out, "{} = {} ({}){}", constant.name, constant.value, constant.description, shadowed)?;
+                    }
+                }
+                ("reset", _) => {
+                    environment.variables.clear();
+                }
+                ("del", [Argument::Word(variable)]) => {
+                    if environment.variables.remove(*variable).is_none() {
+                        match did_you_mean(variable, environment.variables.keys().map(String::as_str)) {
+                            Some(suggestion) => writeln!(out, "No variable `{}`, did you mean `{}`?", variable, suggestion)?,
+                            None => writeln!(out, "No variable `{}`", variable)?,
+                        }
+                    }
+                }
+                ("debug", _) => {
+                    writeln!(out, "{:?}", self.last_expr)?;
+                }
+                ("history", _) => {
+                    for (index, entry) in environment.history.iter().enumerate() {
+                        let value = self.style.numbers.format(&entry.expr.value());
+                        writeln!(out, "${} = {}  ({})", index + 1, value, entry.source)?;
+                    }
+                }
+                ("word", []) => match environment.integer_mode {
+                    Some(mode) => writeln!(out, "Programmer mode: {} ({})", mode.word, mode.overflow)?,
+                    None => writeln!(out, "Programmer mode is off")?,
+                },
+                ("word", [Argument::Word("off")]) => environment.integer_mode = None,
+                ("word", [Argument::Word(word)]) => {
+                    let word = word.parse::<WordSize>().expect("word sizes are checked by the command definition");
+                    let overflow = environment.integer_mode.map(|mode| mode.overflow).unwrap_or_default();
+                    environment.integer_mode = Some(IntegerMode { word, overflow });
+                }
+                ("overflow", [Argument::Word(overflow)]) => match environment.integer_mode.as_mut() {
+                    Some(mode) => mode.overflow = overflow.parse::<Overflow>().expect("overflow modes are checked by the command definition"),
+                    None => writeln!(out, "Programmer mode is off, enable it with :word first")?,
+                },
+                ("angle", []) => writeln!(out, "Angle mode: {}", environment.angle_unit)?,
+                ("layout", []) => writeln!(out, "Layout: {}", self.layout.layout)?,
+                ("depth", []) => match self.layout.max_depth {
+                    Some(depth) => writeln!(out, "Depth: {}", depth)?,
+                    None => writeln!(out, "Depth: unlimited")?,
+                },
+                // `:angle deg`, `:layout tree` and `:depth 3` are shorthands for `:set angle deg` and so on.
+                ("angle" | "layout" | "depth", [value]) => {
+                    let setting = setting(command.name).expect("angle, layout and depth are settings");
+                    self.apply_setting(setting, value);
+                }
+                ("diff", [Argument::Text(text)]) => self.differentiate(text, out)?,
+                ("simplify", [Argument::Text(text)]) => self.simplify(text, out)?,
+                ("steps", []) => match self.last_expr.clone() {
+                    Some(expr) => self.write_steps(&expr, out)?,
+                    None => writeln!(out, "Nothing to simplify yet")?,
+                },
+                ("steps", [Argument::Text(text)]) => match self.context.evaluate(text) {
+                    Ok(evaluation) => {
+                        self.write_steps(&evaluation.expr, out)?;
+                        self.last_expr.replace(evaluation.expr);
+                    }
+                    Err(e) => write_error(out, text, &e)?,
+                },
+                ("plot", [Argument::Text(text)]) => self.plot(text, out)?,
+                ("set", []) => self.write_settings(out)?,
+                ("set", [Argument::Setting(setting), value]) => self.apply_setting(setting, value),
+                ("config", _) => {
+                    match &self.config_path {
+                        Some(path) => writeln!(out, "# {}", path.display())?,
+                        None => writeln!(out, "# no config file")?,
+                    }
+                    write!(out, "{}", Config::from_session(self))?;
+                }
+                ("help", []) => write!(out, "{}", help())?,
+                ("help", [Argument::Word(name)]) => {
+                    write!(out, "{}", commands::command(name).expect("command names are checked by the command definition").help())?
+                }
+                (name, arguments) => unreachable!("`:{}` doesn't take the arguments {:?}", name, arguments),
+            }
+            Ok(())
+        }
+
+        /// `:diff f wrt x`, or `:diff f wrt x at p`, which is `diff(f, x)` or `diff(f, x, p)` written out.
+        fn differentiate(&mut self, text: &str, out: &mut dyn Write) -> io::Result<()> {
+            let Some((expr, variable)) = text.rsplit_once(" wrt ") else {
+                return writeln!(out, "Usage: :diff <expression> wrt <variable> [at <point>]");
+            };
+            let input = match variable.split_once(" at ") {
+                Some((variable, at)) => format!("diff({}, {}, {})", expr.trim(), variable.trim(), at.trim()),
+                None => format!("diff({}, {})", expr.trim(), variable.trim()),
+            };
+            match self.context.evaluate(&input) {
+                Ok(evaluation) => {
+                    if let Expr::Derivative { expr, variable, derivative, .. } = &evaluation.expr {
+                        writeln!(out, "d/d{} {} = {}", variable, expr, derivative)?;
+                    }
+                    self.write_evaluation(out, &evaluation)?;
+                    self.last_expr.replace(evaluation.expr);
+                    Ok(())
+                }
+                Err(e) => write_error(out, &input, &e),
+            }
+        }
+
+        /// `:simplify f`, which prints the simplified form of `f` and its tree.
+        fn simplify(&mut self, text: &str, out: &mut dyn Write) -> io::Result<()> {
+            let evaluation = match self.context.evaluate(text) {
+                Ok(evaluation) => evaluation,
+                Err(e) => return write_error(out, text, &e),
+            };
+            match simplify(&evaluation.expr, self.context.environment()) {
+                Ok(simplified) => {
+                    writeln!(out, "{} = {}", evaluation.expr, simplified)?;
+                    write_expr(out, &simplified, &self.style, &self.layout)?;
+                    self.last_expr.replace(evaluation.expr);
+                    Ok(())
+                }
+                Err(e) => write_error(out, text, &Error::Eval(e)),
+            }
+        }
+
+        /// Writes the expression, then each rewrite that simplifies it along with the rule that made it.
+        fn write_steps(&self, expr: &Expr, out: &mut dyn Write) -> io::Result<()> {
+            let steps = match steps(expr, self.context.environment()) {
+                Ok(steps) => steps,
+                Err(e) => return writeln!(out, "Error: {}", e),
+            };
+            writeln!(out, "{}", expr)?;
+            if steps.is_empty() {
+                return writeln!(out, "(already as simple as it gets)");
+            }
+            let lines = steps.iter().map(|step| format!("= {}", step.expr)).collect::<Vec<_>>();
+            let width = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0);
+            for (line, step) in lines.iter().zip(&steps) {
+                writeln!(out, "{:width$}  ({})", line, step.rule, width = width)?;
+            }
+            Ok(())
+        }
+
+        /// `:plot f, g for x in a..b`, which draws the expressions over the range, or `:plot f to plot.svg`
+        /// to write them to a file instead. Without a range, the free variable goes from -10 to 10.
+        fn plot(&self, text: &str, out: &mut dyn Write) -> io::Result<()> {
+            const USAGE: &str = "Usage: :plot <expression>[, <expression>...] [for <variable> in <start>..<end>] [to <file>]";
+            let (text, file) = match text.rsplit_once(" to ") {
+                Some((text, file)) => (text, Some(file.trim())),
+                None => (text, None),
+            };
+            let (text, range) = match text.rsplit_once(" for ") {
+                Some((text, range)) => (text, Some(range)),
+                None => (text, None),
+            };
+
+            // Evaluated in a copy of the session, so that nothing is added to the history, and with the
+            // names that have no value left free.
+            let mut context = self.context.clone();
+            context.environment_mut().symbolic = true;
+            let mut exprs = vec![];
+            for source in split_arguments(text) {
+                match context.evaluate(source) {
+                    Ok(evaluation) => exprs.push((source.to_string(), evaluation.expr)),
+                    Err(e) => return write_error(out, source, &e),
+                }
+            }
+            let (variable, bounds) = match range.map(|range| range.split_once(" in ")) {
+                Some(Some((variable, bounds))) => (variable.trim().to_string(), bounds),
+                Some(None) => return writeln!(out, "{}", USAGE),
+                None => {
+                    let free = exprs.iter().flat_map(|(_, expr)| expr.free_variables()).collect::<BTreeSet<_>>();
+                    (free.into_iter().next().unwrap_or_else(|| "x".to_string()), "-10..10")
+                }
+            };
+            let Some((low, high)) = bounds.split_once("..") else {
+                return writeln!(out, "{}", USAGE);
+            };
+            let mut range = vec![];
+            for bound in [low.trim(), high.trim()] {
+                match context.eval(bound) {
+                    Ok(value) => range.push(value.as_f64()),
+                    Err(e) => return write_error(out, bound, &e),
+                }
+            }
+            let (low, high) = (range[0], range[1]);
+            if !(low.is_finite() && high.is_finite() && low < high) {
+                return writeln!(out, "The range must go up from one number to another, found {}..{}", low, high);
+            }
+            let unknown = exprs
+                .iter()
+                .flat_map(|(_, expr)| expr.free_variables())
+                .filter(|name| *name != variable)
+                .map(|name| (name, None))
+                .collect::<BTreeMap<_, _>>();
+            if !unknown.is_empty() {
+                return writeln!(out, "{}", describe_unbound_variables(&unknown));
+            }
+
+            let plot = match plot::plot(exprs, &variable, (low, high), context.environment()) {
+                Ok(plot) => plot,
+                Err(e) => return write_error(out, text, &Error::Eval(e)),
+            };
+            match file {
+                Some(path) => match File::create(path).and_then(|mut file| write_svg(&mut file, &plot)) {
+                    Ok(()) => writeln!(out, "Wrote the plot to {}", path),
+                    Err(e) => writeln!(out, "Can't write {}: {}", path, e),
+                },
+                None => write_plot(out, &plot, &self.style),
+            }
+        }
+
+        fn write_settings(&self, out: &mut dyn Write) -> io::Result<()> {
+            let numbers = &self.style.numbers;
+            let on_off = |on| if on { "on" } else { "off" };
+            match numbers.precision {
+                Precision::Significant(digits) => {
+                    writeln!(out, "digits = {}", digits)?;
+                    writeln!(out, "decimals = off")?;
+                }
+                Precision::Decimals(decimals) => {
+                    writeln!(out, "digits = off")?;
+                    writeln!(out, "decimals = {}", decimals)?;
+                }
+            }
+            writeln!(out, "notation = {}", numbers.notation)?;
+            writeln!(out, "separators = {}", on_off(numbers.separators))?;
+            writeln!(out, "radix = {}", numbers.radix)?;
+            writeln!(out, "angle = {}", self.context.environment().angle_unit)?;
+            writeln!(out, "layout = {}", self.layout.layout)?;
+            match self.layout.max_depth {
+                Some(depth) => writeln!(out, "depth = {}", depth)?,
+                None => writeln!(out, "depth = off")?,
+            }
+            writeln!(out, "strict = {}", on_off(self.context.environment().strict))?;
+            writeln!(out, "symbolic = {}", on_off(self.context.environment().symbolic))?;
+            writeln!(out, "warn-precedence = {}", on_off(self.warn_precedence))
+        }
+
+        /// Changes one of the settings, e.g. `:set digits 6`.
+        pub fn set(&mut self, setting: &str, value: &str) -> Result<(), String> {
+            match commands::command("set").unwrap().parse(&[setting, value])?.as_slice() {
+                [Argument::Setting(setting), value] => {
+                    self.apply_setting(setting, value);
+                    Ok(())
+                }
+                arguments => unreachable!("`:set` was parsed into {:?}", arguments),
+            }
+        }
+
+        /// Applies a value that has already been checked against the setting's type.
+        fn apply_setting(&mut self, setting: &Setting, value: &Argument) {
+            let numbers = &mut self.style.numbers;
+            match (setting.name, value) {
+                ("digits" | "decimals", Argument::Number(None)) => numbers.precision = Precision::DEFAULT,
+                ("digits", Argument::Number(Some(digits))) => numbers.precision = Precision::Significant(*digits),
+                ("decimals", Argument::Number(Some(decimals))) => numbers.precision = Precision::Decimals(*decimals),
+                ("notation", Argument::Word(notation)) => numbers.notation = notation.parse().unwrap(),
+                ("separators", Argument::Word(on_off)) => numbers.separators = *on_off == "on",
+                ("radix", Argument::Word(radix)) => numbers.radix = radix.parse::<Radix>().unwrap(),
+                ("angle", Argument::Word(angle)) => self.set_angle_unit(angle.parse().unwrap()),
+                ("layout", Argument::Word(layout)) => self.layout.layout = layout.parse::<Layout>().unwrap(),
+                ("depth", Argument::Number(depth)) => self.layout.max_depth = *depth,
+                ("strict", Argument::Word(on_off)) => self.context.environment_mut().strict = *on_off == "on",
+                ("symbolic", Argument::Word(on_off)) => self.context.environment_mut().symbolic = *on_off == "on",
+                ("warn-precedence", Argument::Word(on_off)) => self.warn_precedence = *on_off == "on",
+                (name, value) => unreachable!("`{}` can't be set to {:?}", name, value),
+            }
+        }
+    }
+
+    /// Warns if the expression evaluated differently under the old precedence rules.
+    fn warn_if_precedence_changed(out: &mut dyn Write, buffer: &str, expr: &Expr, legacy: &Expr) -> io::Result<()> {
+        let value: f64 = expr.clone().into();
+        let legacy_value: f64 = legacy.clone().into();
+        if value != legacy_value && !(value.is_nan() && legacy_value.is_nan()) {
+            writeln!(
+                out,
+                "warning: `{}` now evaluates to {}, but evaluated to {} before `^` became right-associative and bound tighter than unary minus",
+                buffer.trim(),
+                value,
+                legacy_value
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Splits the text at the commas that aren't inside brackets, such as those between the arguments of `max(a, b)`.
+    fn split_arguments(text: &str) -> Vec<&str> {
+        let mut arguments = vec![];
+        let (mut depth, mut start) = (0, 0);
+        for (index, c) in text.char_indices() {
+            match c {
+                '(' | '[' => depth += 1,
+                ')' | ']' => depth -= 1,
+                ',' if depth == 0 => {
+                    arguments.push(text[start..index].trim());
+                    start = index + 1;
+                }
+                _ => {}
+            }
+        }
+        arguments.push(text[start..].trim());
+        arguments
+    }
+
+    pub fn write_error(out: &mut dyn Write, buffer: &str, error: &Error) -> io::Result<()> {
+        let spans = error.spans();
+        if !spans.is_empty() {
+            writeln!(out, "{}", buffer)?;
+            // Underline each part of the input the error refers to with ^
+            let mut marker = String::new();
+            for span in spans {
+                let start = buffer[..span.start.min(buffer.len())].chars().count();
+                let width = buffer.get(span.clone()).map_or(1, |text| text.chars().count().max(1));
+                marker.push_str(&" ".repeat(start.saturating_sub(marker.chars().count())));
+                marker.push_str(&"^".repeat(width));
+            }
+            writeln!(out, "{}", marker)?;
+        }
+        match error {
+            Error::Parse(_)
+            | Error::ImplicitMultiplication { .. }
+            | Error::Eval(EvalError::ImplicitMultiplication { .. })
+            | Error::ProtectedConstant(_) => writeln!(out, "{}", error),
+            _ => writeln!(out, "Error: {}", error),
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+
+        use super::*;
+
+        fn run(session: &mut Session, input: &str) -> String {
+            let mut out = Vec::new();
+            session.handle_input(input, &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        }
+
+        #[test]
+        fn test_commands() {
+            let mut session = Session::default();
+            run(&mut session, "a := 1 + 2");
+            assert_eq!(session.prompt(), "$2 ");
+            assert_eq!(run(&mut session, ":history"), "$1 = 3  (a := 1 + 2)\n");
+            assert_eq!(run(&mut session, ":stat"), "Unknown command `:stat`, did you mean `:state`?\n");
+            assert_eq!(run(&mut session, ":del aa"), "No variable `aa`, did you mean `a`?\n");
+            run(&mut session, ":del a");
+            assert!(session.context.variables().is_empty());
+            assert_eq!(run(&mut session, ":layout sideways"), "invalid layout `sideways`, expected `auto`, `tree`, `top-down` or `inline`\n");
+            assert!(run(&mut session, ":help").contains(":del <variable>  "));
+            assert!(run(&mut session, ":help set").contains("warn-precedence"));
+            run(&mut session, "b := 2");
+            run(&mut session, ":reset");
+            assert!(session.context.variables().is_empty());
+            session.style = TreeStyle::plain();
+            assert!(run(&mut session, ":diff x^2 - x wrt x").starts_with("d/dx x^2 - x = 2 * x - 1\n"));
+            assert!(run(&mut session, ":diff x^2 wrt x at 3").contains("6 = d/dx (at x = 3)"));
+            assert_eq!(run(&mut session, ":diff x^2"), "Usage: :diff <expression> wrt <variable> [at <point>]\n");
+            assert!(run(&mut session, ":simplify x * 1 + 0").starts_with("x * 1 + 0 = x\n"));
+            assert_eq!(
+                run(&mut session, ":steps"),
+                "x * 1 + 0\n= x + 0  (remove identity)\n= x      (remove identity)\n"
+            );
+            assert!(run(&mut session, "y + y").starts_with("unknown variable `y`\n= 2 * y\n"));
+            let history = session.context.environment().history.len();
+            assert!(run(&mut session, ":plot max(x, 0) - 1, x^2 for x in -2..2").contains("  ○ roots at x = 1\n"));
+            assert_eq!(session.context.environment().history.len(), history);
+            assert_eq!(run(&mut session, ":plot x + z for x in 0..1"), "unknown variable `z`\n");
+        }
+
+        #[test]
+        fn test_settings() {
+            let mut session = Session {
+                style: TreeStyle::plain(),
+                ..Default::default()
+            };
+            run(&mut session, ":set separators on");
+            run(&mut session, ":set decimals 2");
+            assert_eq!(run(&mut session, "1234.5 * 2"), "2,469.00 = *\n           ├─ 1,234.50\n           └─ 2\n");
+            run(&mut session, ":set notation si");
+            run(&mut session, ":set digits 2");
+            assert_eq!(run(&mut session, ":history"), "$1 = 2.5k  (1234.5 * 2)\n");
+            assert_eq!(run(&mut session, ":set digit 3"), "Unknown setting `digit`, did you mean `digits`?\n");
+            assert_eq!(run(&mut session, ":set radix 7"), "invalid radix `7`, expected `bin`, `oct`, `dec` or `hex`\n");
+        }
+
+        #[test]
+        fn test_angle_mode() {
+            let mut session = Session {
+                style: TreeStyle::plain(),
+                ..Default::default()
+            };
+            run(&mut session, ":angle deg");
+            assert_eq!(session.right_prompt(), "deg");
+            assert_eq!(run(&mut session, "acos(0.5)"), "60deg = acos()\n             └─ 0.5\n");
+            assert_eq!(run(&mut session, "sin(pi/6 * 1rad)").lines().next(), Some("0.5 = sin()"));
+            assert_eq!(run(&mut session, "2sin(30)").lines().nth(2), Some("    └─ 0.5 = sin() (deg)"));
+            run(&mut session, ":set angle grad");
+            assert_eq!(run(&mut session, "90deg"), "90deg (100grad)\n");
+        }
+
+        #[test]
+        fn test_run_script() {
+            let mut session = Session::default();
+            let mut out = Vec::new();
+            session
+                .run_script("// rates\nrate := 0.2\n\n:set digits 4\nbad := 1 +\ntax := rate * 100", "rc", &mut out)
+                .unwrap();
+            assert!(String::from_utf8(out).unwrap().starts_with("rc:5: parse failed"));
+            assert_eq!(session.context.eval("tax").unwrap(), Value::Float(20.0));
+            assert_eq!(session.style.numbers.precision, Precision::Significant(4));
+            assert_eq!(session.prompt(), "$2 ");
+        }
+
+        #[test]
+        fn test_errors() {
+            let mut session = Session::default();
+            assert!(run(&mut session, "1 +").starts_with("1 +\n   ^\nparse failed: "));
+            assert_eq!(
+                run(&mut session, "xs := [1, 2] * (1..3)"),
+                "xs := [1, 2] * (1..3)\n      ^^^^^^   ^^^^^^\n\
+                 Error: `*` can't combine lists of different lengths, 2 on the left and 3 on the right\n"
+            );
+            assert_eq!(
+                run(&mut session, "pi := 3"),
+                "`pi` is a built-in constant, use `shadow pi := ...` to replace it for this session\n"
+            );
+        }
+    }
+}
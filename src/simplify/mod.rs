@@ -0,0 +1,375 @@
+pub mod simplify {
+    use crate::grammar::grammar::{reevaluate, BinaryOperator, Environment, EvalError, Expr, UnaryOperator};
+    use crate::symbolic::symbolic::{binary, constant, number, same};
+    use crate::value::value::Value;
+
+    /// A rewrite rule, which returns the replacement for an expression it applies to.
+    pub struct Rule {
+        pub name: &'static str,
+        apply: fn(&Expr) -> Option<Expr>,
+    }
+
+    /// The rules in the order they are tried. The first rule that applies anywhere in the expression is
+    /// used, at the innermost operation it applies to, and then the rules are tried again from the start.
+    pub const RULES: &[Rule] = &[
+        Rule {
+            name: "fold constants",
+            apply: fold_constants,
+        },
+        Rule {
+            name: "remove identity",
+            apply: remove_identity,
+        },
+        Rule {
+            name: "multiply by zero",
+            apply: multiply_by_zero,
+        },
+        Rule {
+            name: "simplify signs",
+            apply: simplify_signs,
+        },
+        Rule {
+            name: "cancel",
+            apply: cancel,
+        },
+        Rule {
+            name: "collect like terms",
+            apply: collect_like_terms,
+        },
+        Rule {
+            name: "combine coefficients",
+            apply: combine_coefficients,
+        },
+    ];
+
+    /// The most rewrites made to one expression, in case the rules undo each other.
+    const MAX_STEPS: usize = 1000;
+
+    /// One rewrite: the rule that was applied and the whole expression after it.
+    #[derive(Debug, Clone)]
+    pub struct Step {
+        pub rule: &'static str,
+        pub expr: Expr,
+    }
+
+    /// Whether the expression has a value, rather than depending on a variable without one.
+    fn is_constant(expr: &Expr) -> bool {
+        expr.unbound_variables().is_empty() && expr.free_variables().is_empty()
+    }
+
+    fn is_multiply(op: &BinaryOperator) -> bool {
+        matches!(op, BinaryOperator::Multiply | BinaryOperator::ImplicitMultiply)
+    }
+
+    fn negated(expr: Expr) -> Expr {
+        Expr::UnaryOperation {
+            op: UnaryOperator::Minus,
+            expr: Box::new(expr),
+            value: Value::Float(f64::NAN),
+        }
+    }
+
+    /// `2 + 3` to `5`, for any operation that doesn't depend on a variable without a value.
+    fn fold_constants(expr: &Expr) -> Option<Expr> {
+        if !matches!(expr, Expr::BinaryOperation { .. } | Expr::UnaryOperation { .. } | Expr::FunctionCall { .. }) || !is_constant(expr) {
+            return None;
+        }
+        match expr.value() {
+            Value::Float(value) if value.is_nan() => None,
+            Value::Integer(_) | Value::Word(_) | Value::Float(_) => Some(number(expr.value().as_f64())),
+            value => Some(Expr::literal(value)),
+        }
+    }
+
+    /// `x + 0`, `x - 0`, `x * 1`, `x / 1` and `x^1` to `x`, and `-1 * x` and `0 - x` to `-x`.
+    fn remove_identity(expr: &Expr) -> Option<Expr> {
+        let Expr::BinaryOperation { lhs, op, rhs, .. } = expr else {
+            return None;
+        };
+        match (constant(lhs), op, constant(rhs)) {
+            (Some(0.0), BinaryOperator::Add, _) => Some(*rhs.clone()),
+            (_, BinaryOperator::Add | BinaryOperator::Subtract, Some(0.0)) => Some(*lhs.clone()),
+            (Some(0.0), BinaryOperator::Subtract, _) => Some(negated(*rhs.clone())),
+            (Some(1.0), op, _) if is_multiply(op) => Some(*rhs.clone()),
+            (_, op, Some(1.0)) if is_multiply(op) => Some(*lhs.clone()),
+            (Some(-1.0), op, _) if is_multiply(op) => Some(negated(*rhs.clone())),
+            (_, BinaryOperator::Divide | BinaryOperator::Power, Some(1.0)) => Some(*lhs.clone()),
+            _ => None,
+        }
+    }
+
+    /// `x * 0` and `0 / x` to `0`, and `x^0` to `1`. Zero times an infinite or undefined constant, or
+    /// divided by zero, is left alone, so that `0/0` doesn't become `0`.
+    fn multiply_by_zero(expr: &Expr) -> Option<Expr> {
+        let Expr::BinaryOperation { lhs, op, rhs, .. } = expr else {
+            return None;
+        };
+        let finite = |expr: &Expr| !is_constant(expr) || expr.value().as_f64().is_finite();
+        match (constant(lhs), op, constant(rhs)) {
+            (Some(0.0), op, _) if is_multiply(op) && finite(rhs) => Some(number(0.0)),
+            (_, op, Some(0.0)) if is_multiply(op) && finite(lhs) => Some(number(0.0)),
+            (Some(0.0), BinaryOperator::Divide, divisor) if finite(rhs) && divisor != Some(0.0) => Some(number(0.0)),
+            (_, BinaryOperator::Power, Some(0.0)) => Some(number(1.0)),
+            _ => None,
+        }
+    }
+
+    /// The operand of a negation, or the size of a negative number.
+    fn negation(expr: &Expr) -> Option<Expr> {
+        match expr {
+            Expr::UnaryOperation {
+                op: UnaryOperator::Minus,
+                expr,
+                ..
+            } => Some(*expr.clone()),
+            expr => constant(expr).filter(|value| *value < 0.0).map(|value| number(-value)),
+        }
+    }
+
+    /// `--x` to `x`, `x - -y` to `x + y`, and `x + -y` to `x - y`.
+    fn simplify_signs(expr: &Expr) -> Option<Expr> {
+        match expr {
+            Expr::UnaryOperation {
+                op: UnaryOperator::Minus,
+                expr,
+                ..
+            } if matches!(**expr, Expr::UnaryOperation { op: UnaryOperator::Minus, .. }) => negation(expr),
+            Expr::BinaryOperation { lhs, op: BinaryOperator::Subtract, rhs, .. } => {
+                negation(rhs).map(|rhs| binary(*lhs.clone(), BinaryOperator::Add, rhs))
+            }
+            Expr::BinaryOperation { lhs, op: BinaryOperator::Add, rhs, .. } => {
+                negation(rhs).map(|rhs| binary(*lhs.clone(), BinaryOperator::Subtract, rhs))
+            }
+            _ => None,
+        }
+    }
+
+    /// `x - x` to `0`, and `x / x` to `1`.
+    fn cancel(expr: &Expr) -> Option<Expr> {
+        match expr {
+            Expr::BinaryOperation { lhs, op: BinaryOperator::Subtract, rhs, .. } if same(lhs, rhs) => Some(number(0.0)),
+            Expr::BinaryOperation { lhs, op: BinaryOperator::Divide, rhs, .. } if same(lhs, rhs) && !is_constant(lhs) => Some(number(1.0)),
+            _ => None,
+        }
+    }
+
+    /// The terms of a sum, each with whether it is added or subtracted.
+    fn terms(expr: &Expr, positive: bool, terms: &mut Vec<(bool, Expr)>) {
+        match expr {
+            Expr::BinaryOperation { lhs, op: BinaryOperator::Add, rhs, .. } => {
+                self::terms(lhs, positive, terms);
+                self::terms(rhs, positive, terms);
+            }
+            Expr::BinaryOperation { lhs, op: BinaryOperator::Subtract, rhs, .. } => {
+                self::terms(lhs, positive, terms);
+                self::terms(rhs, !positive, terms);
+            }
+            Expr::UnaryOperation {
+                op: UnaryOperator::Minus,
+                expr,
+                ..
+            } => self::terms(expr, !positive, terms),
+            term => terms.push((positive, term.clone())),
+        }
+    }
+
+    /// A term as its numeric coefficient and the rest of it, which is `None` for a number on its own.
+    fn coefficient(term: &Expr) -> (Expr, Option<Expr>) {
+        match term {
+            term if constant(term).is_some() => (term.clone(), None),
+            Expr::BinaryOperation { lhs, op, rhs, .. } if is_multiply(op) && constant(lhs).is_some() => (*lhs.clone(), Some(*rhs.clone())),
+            term => (number(1.0), Some(term.clone())),
+        }
+    }
+
+    /// `2x + 3x` to `(2 + 3) * x`, and `x + 1 + 2` to `x + (1 + 2)`, wherever the terms are in the sum.
+    fn collect_like_terms(expr: &Expr) -> Option<Expr> {
+        if !matches!(expr, Expr::BinaryOperation { op: BinaryOperator::Add | BinaryOperator::Subtract, .. }) {
+            return None;
+        }
+        let mut terms = vec![];
+        self::terms(expr, true, &mut terms);
+        let split = terms.iter().map(|(_, term)| coefficient(term)).collect::<Vec<_>>();
+        let (first, second) = (0..split.len()).flat_map(|i| (i + 1..split.len()).map(move |j| (i, j))).find(|&(i, j)| {
+            match (&split[i].1, &split[j].1) {
+                (Some(lhs), Some(rhs)) => same(lhs, rhs),
+                (None, None) => true,
+                _ => false,
+            }
+        })?;
+
+        let op = if terms[first].0 == terms[second].0 {
+            BinaryOperator::Add
+        } else {
+            BinaryOperator::Subtract
+        };
+        let coefficients = binary(split[first].0.clone(), op, split[second].0.clone());
+        let collected = match split[first].1.clone() {
+            Some(rest) => binary(coefficients, BinaryOperator::Multiply, rest),
+            None => coefficients,
+        };
+        terms[first].1 = collected;
+        terms.remove(second);
+
+        let mut terms = terms.into_iter();
+        let (positive, first) = terms.next()?;
+        let sum = if positive { first } else { negated(first) };
+        Some(terms.fold(sum, |sum, (positive, term)| {
+            let op = if positive { BinaryOperator::Add } else { BinaryOperator::Subtract };
+            binary(sum, op, term)
+        }))
+    }
+
+    /// `x * 2` to `2 * x`, and `2 * (3 * x)` to `(2 * 3) * x`, so that a product has one number and
+    /// it comes first.
+    fn combine_coefficients(expr: &Expr) -> Option<Expr> {
+        let Expr::BinaryOperation { lhs, op, rhs, .. } = expr else {
+            return None;
+        };
+        if !is_multiply(op) {
+            return None;
+        }
+        match &**rhs {
+            _ if constant(rhs).is_some() && constant(lhs).is_none() => Some(binary(*rhs.clone(), BinaryOperator::Multiply, *lhs.clone())),
+            Expr::BinaryOperation {
+                lhs: inner,
+                op: inner_op,
+                rhs: factor,
+                ..
+            } if is_multiply(inner_op) && constant(lhs).is_some() && constant(inner).is_some() => Some(binary(
+                binary(*lhs.clone(), BinaryOperator::Multiply, *inner.clone()),
+                BinaryOperator::Multiply,
+                *factor.clone(),
+            )),
+            _ => None,
+        }
+    }
+
+    /// Applies the rule once, to the first operation it applies to with the operands before the
+    /// operations they belong to, and returns the rewritten expression if it applied anywhere.
+    fn rewrite(expr: &Expr, rule: &Rule) -> Option<Expr> {
+        match expr {
+            Expr::BinaryOperation { lhs, op, rhs, value } => {
+                if let Some(lhs) = rewrite(lhs, rule) {
+                    return Some(Expr::BinaryOperation {
+                        lhs: Box::new(lhs),
+                        op: op.clone(),
+                        rhs: rhs.clone(),
+                        value: value.clone(),
+                    });
+                }
+                if let Some(rhs) = rewrite(rhs, rule) {
+                    return Some(Expr::BinaryOperation {
+                        lhs: lhs.clone(),
+                        op: op.clone(),
+                        rhs: Box::new(rhs),
+                        value: value.clone(),
+                    });
+                }
+            }
+            Expr::UnaryOperation { op, expr: operand, value } => {
+                if let Some(operand) = rewrite(operand, rule) {
+                    return Some(Expr::UnaryOperation {
+                        op: op.clone(),
+                        expr: Box::new(operand),
+                        value: value.clone(),
+                    });
+                }
+            }
+            Expr::FunctionCall { name, arguments, angle, value } => {
+                for (index, argument) in arguments.iter().enumerate() {
+                    if let Some(argument) = rewrite(argument, rule) {
+                        let mut arguments = arguments.clone();
+                        arguments[index] = argument;
+                        return Some(Expr::FunctionCall {
+                            name: name.clone(),
+                            arguments,
+                            angle: *angle,
+                            value: value.clone(),
+                        });
+                    }
+                }
+            }
+            Expr::List { elements, variable: None } => {
+                for (index, element) in elements.iter().enumerate() {
+                    if let Some(element) = rewrite(element, rule) {
+                        let mut elements = elements.clone();
+                        elements[index] = element;
+                        return Some(Expr::List { elements, variable: None });
+                    }
+                }
+            }
+            _ => {}
+        }
+        (rule.apply)(expr)
+    }
+
+    /// Every rewrite made while simplifying the expression, in order. The values of the rewritten
+    /// expressions are calculated again after each one.
+    pub fn steps(expr: &Expr, environment: &Environment) -> Result<Vec<Step>, EvalError> {
+        let mut steps: Vec<Step> = vec![];
+        while steps.len() < MAX_STEPS {
+            let current = steps.last().map_or(expr, |step| &step.expr);
+            let Some((rule, rewritten)) = RULES.iter().find_map(|rule| rewrite(current, rule).map(|rewritten| (rule, rewritten))) else {
+                break;
+            };
+            steps.push(Step {
+                rule: rule.name,
+                expr: reevaluate(&rewritten, environment)?,
+            });
+        }
+        Ok(steps)
+    }
+
+    /// The expression with all of the rules applied, e.g. `5 * x` for `2x + 3x`.
+    pub fn simplify(expr: &Expr, environment: &Environment) -> Result<Expr, EvalError> {
+        Ok(steps(expr, environment)?.pop().map_or_else(|| expr.clone(), |step| step.expr))
+    }
+
+    #[cfg(test)]
+    mod tests {
+
+        use super::*;
+        use crate::context::context::Context;
+
+        fn simplified(input: &str) -> String {
+            let mut context = Context::new();
+            let expr = context.evaluate(input).unwrap().expr;
+            simplify(&expr, context.environment()).unwrap().to_string()
+        }
+
+        #[test]
+        fn test_simplify() {
+            assert_eq!(simplified("x * 1"), "x");
+            assert_eq!(simplified("0 + x"), "x");
+            assert_eq!(simplified("x - x"), "0");
+            assert_eq!(simplified("2*x + 3*x"), "5 * x");
+            assert_eq!(simplified("2x + 1 - x + 4"), "x + 5");
+            assert_eq!(simplified("(1 + 2) * y * 0 + z^(4 - 3)"), "z");
+            assert_eq!(simplified("x * 2 * 3"), "6 * x");
+            assert_eq!(simplified("-(-sin(x)) / sin(x)"), "1");
+            assert_eq!(simplified("2 * (3 + 4)"), "14");
+            assert_eq!(simplified("x + 2y"), "x + 2y");
+            assert_eq!(simplified("x - y + 2y + -3"), "x + y - 3");
+            assert_eq!(simplified("0 / x"), "0");
+            assert_eq!(simplified("0/0"), "0 / 0");
+            assert_eq!(simplified("0 * (1/0)"), "0 * inf");
+        }
+
+        #[test]
+        fn test_steps() {
+            let mut context = Context::new();
+            let expr = context.evaluate("2*x + 3*x + 0").unwrap().expr;
+            let steps = steps(&expr, context.environment()).unwrap();
+            let steps = steps.iter().map(|step| (step.rule, step.expr.to_string())).collect::<Vec<_>>();
+            assert_eq!(
+                steps,
+                vec![
+                    ("remove identity", "2 * x + 3 * x".to_string()),
+                    ("collect like terms", "(2 + 3) * x".to_string()),
+                    ("fold constants", "5 * x".to_string()),
+                ]
+            );
+        }
+    }
+}
@@ -0,0 +1,367 @@
+pub mod solve {
+    use crate::functions::functions::ParameterType;
+    use crate::grammar::grammar::{reevaluate, BinaryOperator, Environment, EvalError, Expr};
+    use crate::symbolic::symbolic::{self, binary, substitute, variable_name};
+    use crate::value::value::Value;
+
+    /// The most steps of Newton's method before falling back to bisection.
+    const NEWTON_ITERATIONS: usize = 50;
+    /// The most halvings of a bracket, which is more than enough to reach the precision of a float.
+    const BISECTION_ITERATIONS: usize = 200;
+    /// The most doublings of the search for a sign change around the guess.
+    const BRACKET_EXPANSIONS: usize = 60;
+
+    fn is_number(value: &Value) -> bool {
+        matches!(value, Value::Integer(_) | Value::Word(_) | Value::Float(_))
+    }
+
+    /// An error that the equation has no solution, or that none could be found.
+    fn unsolvable(reason: impl Into<String>) -> EvalError {
+        EvalError::Unsolvable { reason: reason.into() }
+    }
+
+    /// The difference between the two sides of the equation, as a function of the variable.
+    struct Residual {
+        expr: Expr,
+        name: String,
+        /// The session's variables, with the variable set to wherever the residual was last evaluated.
+        environment: Environment,
+    }
+
+    impl Residual {
+        fn at(&mut self, x: f64) -> Result<f64, EvalError> {
+            self.environment.variables.insert(self.name.clone(), Expr::literal(Value::Float(x)));
+            Ok(reevaluate(&self.expr, &self.environment)?.value().as_f64())
+        }
+    }
+
+    /// A root, and how it was found.
+    struct Roots {
+        roots: Vec<f64>,
+        method: &'static str,
+        iterations: usize,
+    }
+
+    /// The coefficients `[a, b, c]` of `a x^2 + b x + c`, if the residual is a polynomial of at most the
+    /// second degree. They are read off from three points and checked against a few more.
+    fn quadratic(residual: &mut Residual) -> Result<Option<[f64; 3]>, EvalError> {
+        let (minus, zero, plus) = (residual.at(-1.0)?, residual.at(0.0)?, residual.at(1.0)?);
+        let (a, b, c) = ((plus + minus) / 2.0 - zero, (plus - minus) / 2.0, zero);
+        if ![minus, zero, plus, a, b, c].iter().all(|value| value.is_finite()) {
+            return Ok(None);
+        }
+        for x in [-3.7, 0.45, 2.5, 11.0] {
+            let expected = a * x * x + b * x + c;
+            let actual = residual.at(x)?;
+            let difference = (actual - expected).abs();
+            if !actual.is_finite() || difference.is_nan() || difference > 1e-9 * actual.abs().max(1.0) {
+                return Ok(None);
+            }
+        }
+        Ok(Some([a, b, c]))
+    }
+
+    /// All the real roots of `a x^2 + b x + c`, in increasing order.
+    fn closed_form([a, b, c]: [f64; 3]) -> Result<Vec<f64>, EvalError> {
+        let scale = a.abs().max(b.abs()).max(c.abs()).max(f64::MIN_POSITIVE);
+        let negligible = |coefficient: f64| coefficient.abs() <= 1e-12 * scale;
+        if negligible(a) && negligible(b) {
+            return Err(unsolvable(if negligible(c) {
+                "it holds whatever the value of the variable"
+            } else {
+                "it never holds"
+            }));
+        }
+        if negligible(a) {
+            return Ok(vec![-c / b]);
+        }
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < -1e-12 * (b * b).max((4.0 * a * c).abs()) {
+            return Err(unsolvable("it has no real solutions"));
+        }
+        if discriminant <= 0.0 {
+            return Ok(vec![-b / (2.0 * a)]);
+        }
+        // Written this way to avoid cancellation between `-b` and the square root.
+        let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+        let (mut first, mut second) = if q == 0.0 { (0.0, -b / a) } else { (q / a, c / q) };
+        if first > second {
+            std::mem::swap(&mut first, &mut second);
+        }
+        Ok(vec![first, second])
+    }
+
+    /// Whether the residual at `x` is as close to zero as the precision of floats allows.
+    fn converged(step: f64, x: f64) -> bool {
+        step.abs() <= 1e-14 * x.abs().max(1.0)
+    }
+
+    /// Newton's method from the guess, or `None` if it doesn't converge.
+    fn newton(residual: &mut Residual, derivative: &mut Residual, guess: f64) -> Result<Option<(f64, usize)>, EvalError> {
+        let mut x = guess;
+        for iteration in 1..=NEWTON_ITERATIONS {
+            let value = residual.at(x)?;
+            if value == 0.0 {
+                return Ok(Some((x, iteration - 1)));
+            }
+            let slope = derivative.at(x)?;
+            let step = value / slope;
+            if !step.is_finite() {
+                return Ok(None);
+            }
+            x -= step;
+            if converged(step, x) {
+                return Ok(Some((x, iteration)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Bisection of a bracket whose ends the residual has opposite signs at.
+    fn bisection(residual: &mut Residual, (mut low, mut high): (f64, f64)) -> Result<(f64, usize), EvalError> {
+        let mut low_value = residual.at(low)?;
+        for iteration in 1..=BISECTION_ITERATIONS {
+            let middle = low + (high - low) / 2.0;
+            let value = residual.at(middle)?;
+            if value == 0.0 || converged(high - low, middle) {
+                return Ok((middle, iteration));
+            }
+            if (value < 0.0) == (low_value < 0.0) {
+                (low, low_value) = (middle, value);
+            } else {
+                high = middle;
+            }
+        }
+        Ok((low + (high - low) / 2.0, BISECTION_ITERATIONS))
+    }
+
+    /// A bracket around the guess that the residual changes sign in, widening it until it finds one.
+    fn find_bracket(residual: &mut Residual, guess: f64) -> Result<Option<(f64, f64)>, EvalError> {
+        let mut width = 0.1 * guess.abs().max(1.0);
+        for _ in 0..BRACKET_EXPANSIONS {
+            let (low, high) = (guess - width, guess + width);
+            let (low_value, high_value) = (residual.at(low)?, residual.at(high)?);
+            if low_value.is_finite() && high_value.is_finite() && (low_value < 0.0) != (high_value < 0.0) {
+                return Ok(Some((low, high)));
+            }
+            width *= 2.0;
+        }
+        Ok(None)
+    }
+
+    /// Finds a root from a guess with Newton's method if the residual can be differentiated, and by
+    /// bisection otherwise or if that fails.
+    fn from_guess(residual: &mut Residual, guess: f64) -> Result<Roots, EvalError> {
+        if let Ok(derivative) = symbolic::derivative(&residual.expr, &residual.name) {
+            let mut derivative = Residual {
+                expr: derivative,
+                name: residual.name.clone(),
+                environment: residual.environment.clone(),
+            };
+            if let Some((root, iterations)) = newton(residual, &mut derivative, guess)? {
+                return Ok(Roots {
+                    roots: vec![root],
+                    method: "Newton's method",
+                    iterations,
+                });
+            }
+        }
+        match find_bracket(residual, guess)? {
+            Some(bracket) => from_bracket(residual, bracket),
+            None => Err(unsolvable(format!("no solution was found near {}", Value::Float(guess)))),
+        }
+    }
+
+    fn from_bracket(residual: &mut Residual, (low, high): (f64, f64)) -> Result<Roots, EvalError> {
+        let (low_value, high_value) = (residual.at(low)?, residual.at(high)?);
+        if (low_value < 0.0) == (high_value < 0.0) && low_value != 0.0 && high_value != 0.0 {
+            return Err(unsolvable(format!(
+                "the two sides differ by {} at {} and {} at {}, so the bracket may not contain a solution",
+                Value::Float(low_value),
+                Value::Float(low),
+                Value::Float(high_value),
+                Value::Float(high)
+            )));
+        }
+        let (root, iterations) = bisection(residual, (low, high))?;
+        Ok(Roots {
+            roots: vec![root],
+            method: "bisection",
+            iterations,
+        })
+    }
+
+    /// Evaluates `solve(lhs = rhs, x)`, where `rhs` is 0 if there is no `=`, and the optional third
+    /// argument is a starting point or a bracket `[a, b]`. Linear and quadratic equations are solved in
+    /// closed form, with all of their solutions, and anything else numerically.
+    pub fn eval_solve(mut arguments: Vec<Expr>, rhs: Option<Expr>, environment: &Environment) -> Result<Expr, EvalError> {
+        if !(2..=3).contains(&arguments.len()) {
+            return Err(EvalError::WrongArity {
+                function: "solve".to_string(),
+                expected: "2 or 3 arguments".to_string(),
+                found: arguments.len(),
+            });
+        }
+        let guess = (arguments.len() == 3).then(|| arguments.pop().unwrap());
+        let name = variable_name("solve", &arguments.pop().unwrap())?;
+        let lhs = arguments.pop().unwrap();
+        let rhs = rhs.unwrap_or_else(|| Expr::literal(Value::Integer(0)));
+
+        let free = Expr::FreeVariable { name: name.clone() };
+        let (lhs, rhs) = (substitute(&lhs, &name, &free), substitute(&rhs, &name, &free));
+        let expr = binary(lhs.clone(), BinaryOperator::Subtract, rhs.clone());
+        let mut unknown = expr.unbound_variables().into_keys().collect::<Vec<_>>();
+        unknown.extend(expr.free_variables().into_iter().filter(|free| *free != name));
+        if let Some(unknown) = unknown.first() {
+            return Err(unsolvable(format!("`{}` has no value", unknown)));
+        }
+        let mut residual = Residual {
+            expr,
+            name: name.clone(),
+            environment: environment.clone(),
+        };
+
+        let found = match guess.as_ref().map(Expr::value) {
+            Some(Value::List(bounds)) => match bounds.as_slice() {
+                [low, high] if is_number(low) && is_number(high) => from_bracket(&mut residual, (low.as_f64(), high.as_f64()))?,
+                _ => return Err(unsolvable(format!("a bracket is two numbers, such as `[0, 1]`, found {}", Value::List(bounds)))),
+            },
+            Some(value) if !is_number(&value) => {
+                return Err(EvalError::ArgumentType {
+                    function: "solve".to_string(),
+                    position: 3,
+                    expected: ParameterType::Number,
+                    found: value,
+                })
+            }
+            guess => match quadratic(&mut residual)? {
+                Some(coefficients) => Roots {
+                    roots: closed_form(coefficients)?,
+                    method: "closed form",
+                    iterations: 0,
+                },
+                None => from_guess(&mut residual, guess.map_or(1.0, |guess| guess.as_f64()))?,
+            },
+        };
+
+        let mut residuals = vec![];
+        for root in &found.roots {
+            residuals.push(residual.at(*root)?.abs());
+        }
+        if residuals.iter().any(|residual| residual.is_nan()) {
+            return Err(unsolvable("one of the sides is undefined at the solution"));
+        }
+        let residual_error = residuals.iter().copied().fold(0.0, f64::max);
+        if !residual_error.is_finite() || residual_error > 1e-6 * lhs.value().as_f64().abs().max(rhs.value().as_f64().abs()).max(1.0) {
+            return Err(unsolvable(format!(
+                "the two sides still differ by {} at {}, which may be a discontinuity",
+                Value::Float(residual_error),
+                Value::Float(found.roots[0])
+            )));
+        }
+
+        // With a single solution, the tree shows both sides at it.
+        let mut scope = environment.clone();
+        match found.roots.as_slice() {
+            [root] => scope.variables.insert(name.clone(), Expr::literal(Value::Float(*root))),
+            _ => scope.variables.remove(&name),
+        };
+        let value = match found.roots.as_slice() {
+            [root] => symbolic::number(*root).value(),
+            roots => Value::List(roots.iter().map(|root| symbolic::number(*root).value()).collect()),
+        };
+        Ok(Expr::Solution {
+            lhs: Box::new(reevaluate(&lhs, &scope)?),
+            rhs: Box::new(reevaluate(&rhs, &scope)?),
+            variable: name,
+            guess: guess.map(Box::new),
+            method: found.method,
+            iterations: found.iterations,
+            residual: residual_error,
+            value,
+        })
+    }
+
+    #[cfg(test)]
+    mod tests {
+
+        use super::*;
+        use crate::context::context::Context;
+
+        fn solution(input: &str) -> (Value, &'static str) {
+            let mut context = Context::new();
+            match context.evaluate(input).unwrap().expr {
+                Expr::Solution { value, method, residual, .. } => {
+                    assert!(residual < 1e-9, "residual {} for {}", residual, input);
+                    (value, method)
+                }
+                expr => panic!("expected a solution, found {:?}", expr),
+            }
+        }
+
+        fn close(value: &Value, expected: f64) -> bool {
+            (value.as_f64() - expected).abs() < 1e-9
+        }
+
+        #[test]
+        fn test_closed_form() {
+            let (value, method) = solution("solve(x^2 - 2 = 0, x)");
+            assert_eq!(method, "closed form");
+            match value {
+                Value::List(roots) => assert!(close(&roots[0], -2f64.sqrt()) && close(&roots[1], 2f64.sqrt())),
+                value => panic!("expected two roots, found {}", value),
+            }
+            assert_eq!(solution("solve(3x + 1 = 10, x)"), (Value::Integer(3), "closed form"));
+            assert_eq!(solution("solve((x - 1)^2, x)"), (Value::Integer(1), "closed form"));
+        }
+
+        #[test]
+        fn test_numeric() {
+            let (value, method) = solution("solve(cos(x) = x, x)");
+            assert_eq!(method, "Newton's method");
+            assert!(close(&value, 0.7390851332151607));
+            let (value, method) = solution("solve(x^3 = 10, x, 2)");
+            assert_eq!(method, "Newton's method");
+            assert!(close(&value, 10f64.cbrt()));
+            let (value, method) = solution("solve(x % 5 = 2.5, x, [0, 4])");
+            assert_eq!(method, "bisection");
+            assert!(close(&value, 2.5));
+            let (value, _) = solution("solve(floor(x) * 0 + x^3 - 8, x, 3)");
+            assert!(close(&value, 2.0));
+        }
+
+        #[test]
+        fn test_formulas() {
+            let mut context = Context::new();
+            context.environment_mut().symbolic = true;
+            context.eval("profit := 12 * units - (4 * units + 2000)").unwrap();
+            assert_eq!(context.eval("breakeven := solve(profit = 0, units)").unwrap(), Value::Integer(250));
+            assert_eq!(context.eval("breakeven * 2").unwrap(), Value::Float(500.0));
+            // The variable solved for stays free afterwards.
+            assert!(context.eval("profit").unwrap().as_f64().is_nan());
+            assert!(close(&context.eval("solve(sin(t) = 0.5, t, 0)").unwrap(), std::f64::consts::FRAC_PI_6));
+        }
+
+        #[test]
+        fn test_unsolvable() {
+            let mut context = Context::new();
+            let unsolvable = |context: &mut Context, input: &str| {
+                matches!(context.evaluate(input), Err(crate::context::context::Error::Eval(EvalError::Unsolvable { .. })))
+            };
+            assert!(unsolvable(&mut context, "solve(x^2 = -1, x)"));
+            assert!(unsolvable(&mut context, "solve(x + 1 = x, x)"));
+            assert!(unsolvable(&mut context, "solve(exp(x) = 0, x)"));
+            assert!(unsolvable(&mut context, "solve(x = a, x)"));
+            assert!(unsolvable(&mut context, "solve(x^3 = 1, x, [2, 3])"));
+            assert!(unsolvable(&mut context, "solve(1/x = 0, x)"));
+            // Undefined at 0, so no longer mistaken for an identity.
+            assert_eq!(context.evaluate("solve(x/x = 1, x)").unwrap().value(), Value::Integer(1));
+            assert!(matches!(
+                context.evaluate("max(x = 1)"),
+                Err(crate::context::context::Error::Eval(EvalError::MisplacedEquation { .. }))
+            ));
+            // A matrix on the left is still a system of linear equations.
+            assert_eq!(context.eval("solve([[2, 0], [0, 4]], [2, 2])").unwrap(), Value::List(vec![Value::Integer(1), Value::Float(0.5)]));
+        }
+    }
+}
@@ -0,0 +1,165 @@
+pub mod statistics {
+    use std::cmp::Ordering;
+
+    use crate::functions::functions::{ParameterType, Parameters, Plugin, Registry};
+    use crate::value::value::Value;
+
+    /// The numbers in the arguments, where a list counts as all of its elements, so that both
+    /// `mean(1, 2, 3)` and `mean([1, 2, 3])` work.
+    fn numbers(arguments: &[Value]) -> Vec<f64> {
+        arguments
+            .iter()
+            .flat_map(|argument| match argument {
+                Value::List(values) => values.iter().map(Value::as_f64).collect(),
+                value => vec![value.as_f64()],
+            })
+            .collect()
+    }
+
+    fn non_empty(numbers: Vec<f64>) -> Result<Vec<f64>, String> {
+        if numbers.is_empty() {
+            Err("expected at least one number, found an empty list".to_string())
+        } else {
+            Ok(numbers)
+        }
+    }
+
+    fn sorted(mut numbers: Vec<f64>) -> Vec<f64> {
+        numbers.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+        numbers
+    }
+
+    pub fn sum(numbers: &[f64]) -> f64 {
+        numbers.iter().sum()
+    }
+
+    pub fn mean(numbers: &[f64]) -> f64 {
+        sum(numbers) / numbers.len() as f64
+    }
+
+    pub fn median(numbers: &[f64]) -> f64 {
+        percentile(numbers, 50.0)
+    }
+
+    /// The most frequent number, or the smallest of them if there is a tie.
+    pub fn mode(numbers: &[f64]) -> f64 {
+        let sorted = sorted(numbers.to_vec());
+        let mut best = (sorted[0], 0);
+        for run in sorted.chunk_by(|a, b| a == b) {
+            if run.len() > best.1 {
+                best = (run[0], run.len());
+            }
+        }
+        best.0
+    }
+
+    /// The variance of a sample, dividing by `n - 1`, or of a whole population, dividing by `n`.
+    pub fn variance(numbers: &[f64], sample: bool) -> f64 {
+        let mean = mean(numbers);
+        let squares = numbers.iter().map(|x| (x - mean).powi(2)).sum::<f64>();
+        squares / (numbers.len() - sample as usize) as f64
+    }
+
+    /// The `p`th percentile, interpolating linearly between the closest ranks.
+    pub fn percentile(numbers: &[f64], p: f64) -> f64 {
+        let sorted = sorted(numbers.to_vec());
+        let rank = p / 100.0 * (sorted.len() - 1) as f64;
+        let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+        sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+    }
+
+    pub fn geometric_mean(numbers: &[f64]) -> f64 {
+        (numbers.iter().map(|x| x.ln()).sum::<f64>() / numbers.len() as f64).exp()
+    }
+
+    pub fn harmonic_mean(numbers: &[f64]) -> f64 {
+        numbers.len() as f64 / numbers.iter().map(|x| 1.0 / x).sum::<f64>()
+    }
+
+    /// Aggregate functions over lists of numbers, such as `mean([1, 2, 3])`.
+    pub struct Statistics;
+
+    impl Statistics {
+        /// Registers a function taking numbers or lists of numbers, which must not all be empty.
+        fn aggregate(registry: &mut Registry, name: &str, function: fn(&[f64]) -> Result<f64, String>) {
+            registry.register_function(name, Parameters::Variadic(ParameterType::Numbers), move |arguments| {
+                function(&non_empty(numbers(arguments))?).map(Value::Float)
+            });
+        }
+    }
+
+    impl Plugin for Statistics {
+        fn register(&self, registry: &mut Registry) {
+            Statistics::aggregate(registry, "sum", |numbers| Ok(sum(numbers)));
+            Statistics::aggregate(registry, "mean", |numbers| Ok(mean(numbers)));
+            Statistics::aggregate(registry, "median", |numbers| Ok(median(numbers)));
+            Statistics::aggregate(registry, "mode", |numbers| Ok(mode(numbers)));
+            Statistics::aggregate(registry, "min", |numbers| Ok(numbers.iter().copied().fold(f64::INFINITY, f64::min)));
+            Statistics::aggregate(registry, "max", |numbers| Ok(numbers.iter().copied().fold(f64::NEG_INFINITY, f64::max)));
+            Statistics::aggregate(registry, "variance", |numbers| match numbers.len() {
+                1 => Err("the sample variance needs at least two numbers, use `pvariance` for a population".to_string()),
+                _ => Ok(variance(numbers, true)),
+            });
+            Statistics::aggregate(registry, "pvariance", |numbers| Ok(variance(numbers, false)));
+            Statistics::aggregate(registry, "stdev", |numbers| match numbers.len() {
+                1 => Err("the sample standard deviation needs at least two numbers, use `pstdev` for a population".to_string()),
+                _ => Ok(variance(numbers, true).sqrt()),
+            });
+            Statistics::aggregate(registry, "pstdev", |numbers| Ok(variance(numbers, false).sqrt()));
+            Statistics::aggregate(registry, "geomean", |numbers| match numbers.iter().all(|x| *x > 0.0) {
+                true => Ok(geometric_mean(numbers)),
+                false => Err("the geometric mean is only defined for positive numbers".to_string()),
+            });
+            Statistics::aggregate(registry, "harmean", |numbers| match numbers.iter().all(|x| *x > 0.0) {
+                true => Ok(harmonic_mean(numbers)),
+                false => Err("the harmonic mean is only defined for positive numbers".to_string()),
+            });
+            // Unlike the others, an empty list has a count.
+            registry.register_function("count", Parameters::Variadic(ParameterType::Numbers), |arguments| {
+                Ok(Value::Integer(numbers(arguments).len() as i128))
+            });
+            registry.register_function(
+                "percentile",
+                Parameters::Fixed(vec![ParameterType::List, ParameterType::Number]),
+                |arguments| match arguments[1].as_f64() {
+                    p if (0.0..=100.0).contains(&p) => Ok(Value::Float(percentile(&non_empty(numbers(&arguments[..1]))?, p))),
+                    p => Err(format!("the percentile must be between 0 and 100, found {}", p)),
+                },
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+
+        use super::*;
+
+        #[test]
+        fn test_statistics() {
+            let numbers = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+            assert_eq!(mean(&numbers), 5.0);
+            assert_eq!(median(&numbers), 4.5);
+            assert_eq!(mode(&numbers), 4.0);
+            assert_eq!(variance(&numbers, false), 4.0);
+            assert_eq!(variance(&numbers, true), 32.0 / 7.0);
+            assert_eq!(percentile(&numbers, 25.0), 4.0);
+            assert_eq!(percentile(&[1.0, 2.0, 3.0, 4.0], 50.0), 2.5);
+            assert!((geometric_mean(&[1.0, 3.0, 9.0]) - 3.0).abs() < 1e-12);
+            assert_eq!(harmonic_mean(&[1.0, 4.0, 4.0]), 2.0);
+        }
+
+        #[test]
+        fn test_registered_functions() {
+            let registry = Registry::default();
+            let call = |name: &str, arguments: &[Value]| registry.function(name).unwrap().call(arguments);
+            let list = Value::List(vec![Value::Integer(1), Value::Integer(2), Value::Integer(6)]);
+            assert_eq!(call("sum", std::slice::from_ref(&list)), Ok(Value::Float(9.0)));
+            assert_eq!(call("max", &[list.clone(), Value::Integer(7)]), Ok(Value::Float(7.0)));
+            assert_eq!(call("count", &[Value::List(vec![])]), Ok(Value::Integer(0)));
+            assert_eq!(call("percentile", &[list.clone(), Value::Integer(50)]), Ok(Value::Float(2.0)));
+            assert!(call("mean", &[Value::List(vec![])]).is_err());
+            assert!(call("stdev", &[Value::Integer(1)]).is_err());
+            assert!(call("percentile", &[Value::Integer(1), Value::Integer(50)]).is_err());
+        }
+    }
+}
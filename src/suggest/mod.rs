@@ -0,0 +1,74 @@
+pub mod suggest {
+
+    /// Optimal string alignment distance: the Levenshtein distance extended so that swapping
+    /// two adjacent characters (a very common typo, e.g. `vlaue`) counts as a single edit.
+    pub fn edit_distance(a: &str, b: &str) -> usize {
+        let a: Vec<char> = a.chars().collect();
+        let b: Vec<char> = b.chars().collect();
+
+        let mut distances = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+        for (i, row) in distances.iter_mut().enumerate() {
+            row[0] = i;
+        }
+        for (j, cell) in distances[0].iter_mut().enumerate() {
+            *cell = j;
+        }
+
+        for i in 1..=a.len() {
+            for j in 1..=b.len() {
+                let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+                let mut distance = (distances[i - 1][j] + 1)
+                    .min(distances[i][j - 1] + 1)
+                    .min(distances[i - 1][j - 1] + cost);
+                if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                    distance = distance.min(distances[i - 2][j - 2] + 1);
+                }
+                distances[i][j] = distance;
+            }
+        }
+
+        distances[a.len()][b.len()]
+    }
+
+    /// Finds the candidate closest to `name`, provided it is close enough to plausibly be a typo.
+    pub fn did_you_mean<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<String> {
+        // Allow roughly one edit for every three characters, but always at least one. A candidate that
+        // shares no characters with the name (e.g. `r` and `G`) is never a useful suggestion.
+        let length = name.chars().count();
+        let threshold = (length / 3).max(1).min(length.saturating_sub(1));
+
+        candidates
+            .into_iter()
+            .filter(|candidate| *candidate != name)
+            .map(|candidate| (edit_distance(name, candidate), candidate))
+            .filter(|(distance, _)| *distance <= threshold)
+            // Break ties alphabetically so that suggestions are stable between runs.
+            .min_by(|(d1, c1), (d2, c2)| d1.cmp(d2).then_with(|| c1.cmp(c2)))
+            .map(|(_, candidate)| candidate.to_string())
+    }
+
+    #[cfg(test)]
+    mod tests {
+
+        use super::*;
+
+        #[test]
+        fn test_edit_distance() {
+            assert_eq!(edit_distance("value", "value"), 0);
+            assert_eq!(edit_distance("vlaue", "value"), 1);
+            assert_eq!(edit_distance("stat", "state"), 1);
+            assert_eq!(edit_distance("", "abc"), 3);
+            assert_eq!(edit_distance("kitten", "sitting"), 3);
+        }
+
+        #[test]
+        fn test_did_you_mean() {
+            let candidates = ["value", "total", "state"];
+            assert_eq!(did_you_mean("vlaue", candidates), Some("value".to_string()));
+            assert_eq!(did_you_mean("stat", candidates), Some("state".to_string()));
+            assert_eq!(did_you_mean("xyz", candidates), None);
+            assert_eq!(did_you_mean("r", ["G", "e"]), None);
+            assert_eq!(did_you_mean("pj", ["pi", "e"]), Some("pi".to_string()));
+        }
+    }
+}
@@ -0,0 +1,422 @@
+pub mod symbolic {
+    use crate::grammar::grammar::{reevaluate, BinaryOperator, Environment, EvalError, Expr, UnaryOperator};
+    use crate::value::value::Value;
+
+    /// Whether the expression is a use of the named variable, whether or not it has a value.
+    fn is_variable(expr: &Expr, name: &str) -> bool {
+        match expr {
+            Expr::FreeVariable { name: free } | Expr::UnboundVariable { name: free, .. } => free == name,
+            Expr::Integer { variable, .. } | Expr::Word { variable, .. } | Expr::Float { variable, .. } => {
+                variable.as_ref().is_some_and(|variable| variable.name == name)
+            }
+            _ => false,
+        }
+    }
+
+    /// The formula stored in a variable, if the expression refers to one that still has free variables.
+    fn formula(expr: &Expr) -> Option<&Expr> {
+        match expr {
+            Expr::Integer { variable: Some(variable), .. }
+            | Expr::Word { variable: Some(variable), .. }
+            | Expr::Float { variable: Some(variable), .. } => {
+                Some(&*variable.expr).filter(|formula| !formula.free_variables().is_empty())
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the value of the expression changes with the named variable.
+    pub fn depends_on(expr: &Expr, name: &str) -> bool {
+        is_variable(expr, name)
+            || formula(expr).is_some_and(|formula| depends_on(formula, name))
+            || expr.operands().into_iter().any(|operand| depends_on(operand, name))
+    }
+
+    /// Replaces every use of the named variable with the replacement.
+    pub fn substitute(expr: &Expr, name: &str, replacement: &Expr) -> Expr {
+        if is_variable(expr, name) {
+            return replacement.clone();
+        }
+        let operand = |expr: &Expr| Box::new(substitute(expr, name, replacement));
+        match expr {
+            Expr::BinaryOperation { lhs, op, rhs, value } => Expr::BinaryOperation {
+                lhs: operand(lhs),
+                op: op.clone(),
+                rhs: operand(rhs),
+                value: value.clone(),
+            },
+            Expr::UnaryOperation { op, expr, value } => Expr::UnaryOperation {
+                op: op.clone(),
+                expr: operand(expr),
+                value: value.clone(),
+            },
+            Expr::FunctionCall { name: function, arguments, angle, value } => Expr::FunctionCall {
+                name: function.clone(),
+                arguments: arguments.iter().map(|argument| substitute(argument, name, replacement)).collect(),
+                angle: *angle,
+                value: value.clone(),
+            },
+            Expr::List { elements, variable: None } => Expr::List {
+                elements: elements.iter().map(|element| substitute(element, name, replacement)).collect(),
+                variable: None,
+            },
+            // The bounds are outside of the operation, but its body has its own variable of that name.
+            Expr::BoundOperation {
+                operator,
+                body,
+                variable,
+                lower,
+                upper,
+                at,
+                error,
+                evaluations,
+                value,
+            } => Expr::BoundOperation {
+                operator: *operator,
+                body: if variable == name { body.clone() } else { operand(body) },
+                variable: variable.clone(),
+                lower: operand(lower),
+                upper: operand(upper),
+                at: *at,
+                error: *error,
+                evaluations: *evaluations,
+                value: value.clone(),
+            },
+            expr => expr.clone(),
+        }
+    }
+
+    /// The value of a number that was written as a literal, rather than calculated or named.
+    pub fn constant(expr: &Expr) -> Option<f64> {
+        match expr {
+            Expr::Integer { value, variable: None, .. } => Some(*value as f64),
+            Expr::Word { value, variable: None, .. } => Some(value.to_f64()),
+            Expr::Float { value, variable: None } => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Expressions are compared by how they are written, so that `x` is `x` whether or not it has a value.
+    pub(crate) fn same(lhs: &Expr, rhs: &Expr) -> bool {
+        lhs.to_string() == rhs.to_string()
+    }
+
+    /// A literal, which is an integer if it is a whole number.
+    pub fn number(value: f64) -> Expr {
+        if value.fract() == 0.0 && value.abs() < 1e15 {
+            Expr::literal(Value::Integer(value as i128))
+        } else {
+            Expr::literal(Value::Float(value))
+        }
+    }
+
+    /// The nodes below are built without their values, which `reevaluate` fills in once the tree is complete.
+    pub fn binary(lhs: Expr, op: BinaryOperator, rhs: Expr) -> Expr {
+        Expr::BinaryOperation {
+            lhs: Box::new(lhs),
+            op,
+            rhs: Box::new(rhs),
+            value: Value::Float(f64::NAN),
+        }
+    }
+
+    pub fn call(name: &str, arguments: Vec<Expr>) -> Expr {
+        Expr::FunctionCall {
+            name: name.to_string(),
+            arguments,
+            angle: None,
+            value: Value::Float(f64::NAN),
+        }
+    }
+
+    pub fn add(lhs: Expr, rhs: Expr) -> Expr {
+        match (constant(&lhs), constant(&rhs)) {
+            (Some(a), Some(b)) => number(a + b),
+            (Some(0.0), _) => rhs,
+            (_, Some(0.0)) => lhs,
+            _ => binary(lhs, BinaryOperator::Add, rhs),
+        }
+    }
+
+    pub fn subtract(lhs: Expr, rhs: Expr) -> Expr {
+        match (constant(&lhs), constant(&rhs)) {
+            (Some(a), Some(b)) => number(a - b),
+            (Some(0.0), _) => negate(rhs),
+            (_, Some(0.0)) => lhs,
+            _ if same(&lhs, &rhs) => number(0.0),
+            _ => binary(lhs, BinaryOperator::Subtract, rhs),
+        }
+    }
+
+    /// The product, with any literal factor written first and merged with the others.
+    pub fn multiply(lhs: Expr, rhs: Expr) -> Expr {
+        match (constant(&lhs), constant(&rhs)) {
+            (Some(a), Some(b)) => number(a * b),
+            (Some(0.0), _) | (_, Some(0.0)) => number(0.0),
+            (Some(1.0), _) => rhs,
+            (_, Some(1.0)) => lhs,
+            (Some(-1.0), _) => negate(rhs),
+            (_, Some(-1.0)) => negate(lhs),
+            (None, Some(_)) => multiply(rhs, lhs),
+            (Some(a), None) => match rhs {
+                Expr::BinaryOperation {
+                    lhs: inner,
+                    op: BinaryOperator::Multiply,
+                    rhs: factor,
+                    ..
+                } if constant(&inner).is_some() => multiply(number(a * constant(&inner).unwrap()), *factor),
+                Expr::UnaryOperation {
+                    op: UnaryOperator::Minus,
+                    expr,
+                    ..
+                } => multiply(number(-a), *expr),
+                rhs => binary(lhs, BinaryOperator::Multiply, rhs),
+            },
+            _ => binary(lhs, BinaryOperator::Multiply, rhs),
+        }
+    }
+
+    pub fn divide(lhs: Expr, rhs: Expr) -> Expr {
+        match (constant(&lhs), constant(&rhs)) {
+            // Only exact quotients are folded, so that `1/3` isn't written as a long decimal.
+            (Some(a), Some(b)) if b != 0.0 && (a / b).fract() == 0.0 => number(a / b),
+            (Some(0.0), _) => number(0.0),
+            (_, Some(1.0)) => lhs,
+            (None, None) if same(&lhs, &rhs) => number(1.0),
+            _ => binary(lhs, BinaryOperator::Divide, rhs),
+        }
+    }
+
+    pub fn power(base: Expr, exponent: Expr) -> Expr {
+        match (constant(&base), constant(&exponent)) {
+            (Some(a), Some(b)) if a.powf(b).fract() == 0.0 => number(a.powf(b)),
+            (_, Some(0.0)) => number(1.0),
+            (_, Some(1.0)) => base,
+            (Some(1.0), _) => number(1.0),
+            _ => binary(base, BinaryOperator::Power, exponent),
+        }
+    }
+
+    pub fn negate(expr: Expr) -> Expr {
+        match expr {
+            expr if constant(&expr).is_some() => number(-constant(&expr).unwrap()),
+            Expr::UnaryOperation {
+                op: UnaryOperator::Minus,
+                expr,
+                ..
+            } => *expr,
+            expr => Expr::UnaryOperation {
+                op: UnaryOperator::Minus,
+                expr: Box::new(expr),
+                value: Value::Float(f64::NAN),
+            },
+        }
+    }
+
+    /// The derivative of the expression with respect to the named variable, which is left free in the
+    /// result. The result is simplified as it is built, but its values still have to be calculated.
+    pub fn derivative(expr: &Expr, name: &str) -> Result<Expr, EvalError> {
+        if is_variable(expr, name) {
+            return Ok(number(1.0));
+        }
+        if !depends_on(expr, name) {
+            return Ok(number(0.0));
+        }
+        if let Some(formula) = formula(expr) {
+            return derivative(formula, name);
+        }
+        // The variable itself, as opposed to its value, in the parts of the expression that are copied.
+        let free = |expr: &Expr| {
+            substitute(
+                expr,
+                name,
+                &Expr::FreeVariable {
+                    name: name.to_string(),
+                },
+            )
+        };
+        let not_differentiable = || EvalError::NotDifferentiable {
+            what: format!("`{}`", expr),
+        };
+        match expr {
+            Expr::BinaryOperation { lhs, op, rhs, .. } => {
+                let (du, dv) = (derivative(lhs, name)?, derivative(rhs, name)?);
+                let (u, v) = (free(lhs), free(rhs));
+                match op {
+                    BinaryOperator::Add => Ok(add(du, dv)),
+                    BinaryOperator::Subtract => Ok(subtract(du, dv)),
+                    BinaryOperator::Multiply | BinaryOperator::ImplicitMultiply => Ok(add(multiply(du, v), multiply(u, dv))),
+                    BinaryOperator::Divide => Ok(divide(
+                        subtract(multiply(du, v.clone()), multiply(u, dv)),
+                        power(v, number(2.0)),
+                    )),
+                    // `u^c` for a constant `c`, `c^v` for a constant `c`, and otherwise `e^(v ln u)`.
+                    BinaryOperator::Power if !depends_on(rhs, name) => Ok(multiply(
+                        multiply(v.clone(), power(u, subtract(v, number(1.0)))),
+                        du,
+                    )),
+                    BinaryOperator::Power if !depends_on(lhs, name) => {
+                        Ok(multiply(multiply(power(u.clone(), v), call("ln", vec![u])), dv))
+                    }
+                    BinaryOperator::Power => Ok(multiply(
+                        power(u.clone(), v.clone()),
+                        add(multiply(dv, call("ln", vec![u.clone()])), divide(multiply(v, du), u)),
+                    )),
+                    _ => Err(not_differentiable()),
+                }
+            }
+            Expr::UnaryOperation {
+                op: UnaryOperator::Minus,
+                expr,
+                ..
+            } => Ok(negate(derivative(expr, name)?)),
+            Expr::FunctionCall {
+                name: function,
+                arguments,
+                angle,
+                ..
+            } => {
+                let u = arguments.iter().map(free).collect::<Vec<_>>();
+                let du = arguments.iter().map(|argument| derivative(argument, name)).collect::<Result<Vec<_>, _>>()?;
+                let outer = match (function.as_str(), u.as_slice()) {
+                    ("sin", [u]) => call("cos", vec![u.clone()]),
+                    ("cos", [u]) => negate(call("sin", vec![u.clone()])),
+                    ("tan", [u]) => divide(number(1.0), power(call("cos", vec![u.clone()]), number(2.0))),
+                    ("asin", [u]) => divide(number(1.0), call("sqrt", vec![subtract(number(1.0), power(u.clone(), number(2.0)))])),
+                    ("acos", [u]) => negate(divide(number(1.0), call("sqrt", vec![subtract(number(1.0), power(u.clone(), number(2.0)))]))),
+                    ("atan", [u]) => divide(number(1.0), add(number(1.0), power(u.clone(), number(2.0)))),
+                    ("sinh", [u]) => call("cosh", vec![u.clone()]),
+                    ("cosh", [u]) => call("sinh", vec![u.clone()]),
+                    ("tanh", [u]) => divide(number(1.0), power(call("cosh", vec![u.clone()]), number(2.0))),
+                    ("sqrt", [u]) => divide(number(1.0), multiply(number(2.0), call("sqrt", vec![u.clone()]))),
+                    ("cbrt", [u]) => divide(number(1.0), multiply(number(3.0), power(call("cbrt", vec![u.clone()]), number(2.0)))),
+                    ("exp", [u]) => call("exp", vec![u.clone()]),
+                    ("ln", [u]) => divide(number(1.0), u.clone()),
+                    ("log10", [u]) => divide(number(1.0), multiply(u.clone(), call("ln", vec![number(10.0)]))),
+                    ("log2", [u]) => divide(number(1.0), multiply(u.clone(), call("ln", vec![number(2.0)]))),
+                    ("abs", [u]) => call("sign", vec![u.clone()]),
+                    ("floor" | "ceil" | "round" | "trunc" | "sign", [_]) => number(0.0),
+                    // Functions of two arguments are differentiated through both of them at once.
+                    ("log", [u, base]) => {
+                        return derivative(&divide(call("ln", vec![u.clone()]), call("ln", vec![base.clone()])), name);
+                    }
+                    ("atan2", [y, x]) => {
+                        let numerator = subtract(multiply(x.clone(), du[0].clone()), multiply(y.clone(), du[1].clone()));
+                        return Ok(divide(numerator, add(power(x.clone(), number(2.0)), power(y.clone(), number(2.0)))));
+                    }
+                    ("hypot", [a, b]) => {
+                        let numerator = add(multiply(a.clone(), du[0].clone()), multiply(b.clone(), du[1].clone()));
+                        return Ok(divide(numerator, call("hypot", vec![a.clone(), b.clone()])));
+                    }
+                    _ => return Err(not_differentiable()),
+                };
+                // A plain number passed to `sin` in degrees is converted to radians first.
+                let outer = match angle {
+                    Some(unit) if unit.to_radians(1.0) != 1.0 => multiply(number(unit.to_radians(1.0)), outer),
+                    _ => outer,
+                };
+                Ok(multiply(du[0].clone(), outer))
+            }
+            Expr::List { elements, variable: None } => Ok(Expr::List {
+                elements: elements.iter().map(|element| derivative(element, name)).collect::<Result<Vec<_>, _>>()?,
+                variable: None,
+            }),
+            _ => Err(not_differentiable()),
+        }
+    }
+
+    /// The name of the variable an argument refers to, for functions such as `diff` that take one.
+    pub fn variable_name(function: &str, argument: &Expr) -> Result<String, EvalError> {
+        match argument {
+            Expr::UnboundVariable { name, .. } | Expr::FreeVariable { name } => Ok(name.clone()),
+            Expr::Integer { variable: Some(variable), .. }
+            | Expr::Word { variable: Some(variable), .. }
+            | Expr::Float { variable: Some(variable), .. } => Ok(variable.name.clone()),
+            found => Err(EvalError::ExpectedVariable {
+                function: function.to_string(),
+                found: found.to_string(),
+            }),
+        }
+    }
+
+    /// Evaluates `diff(expr, x)`, or `diff(expr, x, point)`, whose arguments have already been evaluated.
+    pub fn eval_diff(mut arguments: Vec<Expr>, environment: &Environment) -> Result<Expr, EvalError> {
+        if !(2..=3).contains(&arguments.len()) {
+            return Err(EvalError::WrongArity {
+                function: "diff".to_string(),
+                expected: "2 or 3 arguments".to_string(),
+                found: arguments.len(),
+            });
+        }
+        let at = (arguments.len() == 3).then(|| arguments.pop().unwrap());
+        let name = variable_name("diff", &arguments.pop().unwrap())?;
+        let expr = arguments.pop().unwrap();
+
+        let derivative = derivative(&expr, &name)?;
+        let derivative = match &at {
+            Some(at) => {
+                let mut scoped = environment.clone();
+                scoped.variables.insert(name.clone(), Expr::literal(at.value()));
+                reevaluate(&derivative, &scoped)?
+            }
+            None => reevaluate(&derivative, environment)?,
+        };
+        Ok(Expr::Derivative {
+            expr: Box::new(expr),
+            variable: name,
+            value: derivative.value(),
+            derivative: Box::new(derivative),
+            at: at.map(Box::new),
+        })
+    }
+
+    #[cfg(test)]
+    mod tests {
+
+        use super::*;
+        use crate::context::context::Context;
+
+        fn derivative_of(input: &str) -> String {
+            let mut context = Context::new();
+            match context.evaluate(input).unwrap().expr {
+                Expr::Derivative { derivative, .. } => derivative.to_string(),
+                expr => panic!("expected a derivative, found {:?}", expr),
+            }
+        }
+
+        #[test]
+        fn test_derivative() {
+            assert_eq!(derivative_of("diff(x^3 + 2x, x)"), "3 * x^2 + 2");
+            assert_eq!(derivative_of("diff(5, x)"), "0");
+            assert_eq!(derivative_of("diff(x * x, x)"), "x + x");
+            assert_eq!(derivative_of("diff(sin(2x), x)"), "2 * cos(2x)");
+            assert_eq!(derivative_of("diff(1 / x, x)"), "-1 / x^2");
+            assert_eq!(derivative_of("diff(exp(x^2), x)"), "2 * x * exp(x^2)");
+            assert_eq!(derivative_of("diff(2^x, x)"), "2^x * ln(2)");
+            assert_eq!(derivative_of("diff(ln(x) - sqrt(x), x)"), "1 / x - 1 / (2 * sqrt(x))");
+            assert_eq!(derivative_of("diff(-cos(x), x)"), "sin(x)");
+            assert_eq!(derivative_of("diff(x^x, x)"), "x^x * (ln(x) + 1)");
+        }
+
+        #[test]
+        fn test_derivative_at_a_point() {
+            let mut context = Context::new();
+            assert_eq!(context.eval("diff(x^3, x, 2)").unwrap(), Value::Float(12.0));
+            context.eval("t := 3").unwrap();
+            assert_eq!(context.eval("diff(t^2 + 1, t)").unwrap(), Value::Float(6.0));
+            assert!(matches!(context.eval("diff(x^2, 2)"), Err(crate::context::context::Error::Eval(EvalError::ExpectedVariable { .. }))));
+            assert!(matches!(context.eval("diff(x % 2, x)"), Err(crate::context::context::Error::Eval(EvalError::NotDifferentiable { .. }))));
+        }
+
+        #[test]
+        fn test_pretty_printer() {
+            let mut context = Context::new();
+            let print = |context: &mut Context, input: &str| context.evaluate(input).unwrap().expr.to_string();
+            assert_eq!(print(&mut context, "(1 + 2) * 3 - (4 - 5)"), "(1 + 2) * 3 - (4 - 5)");
+            assert_eq!(print(&mut context, "2^3^2 + (2^3)^2"), "2^3^2 + (2^3)^2");
+            assert_eq!(print(&mut context, "-(2^2) + (-2)^2 - -pi"), "-2^2 + (-2)^2 - -pi");
+            assert_eq!(print(&mut context, "1/2x + 0xFF"), "1 / 2x + 0xFF");
+            assert_eq!(print(&mut context, "max(1, 2) > 1 ? [1, 2] : []"), "if max(1, 2) > 1 then [1, 2] else []");
+        }
+    }
+}
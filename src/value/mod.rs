@@ -0,0 +1,310 @@
+pub mod value {
+    use std::fmt;
+    use std::str::FromStr;
+
+    /// The result of evaluating an expression.
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Value {
+        Integer(i128),
+        Float(f64),
+        Bool(bool),
+        /// Only produced by string literals and native functions, e.g. the argument in `price("SKU1")`.
+        String(String),
+        /// A list literal such as `[1, 2, 3]`, which the statistics functions summarise.
+        List(Vec<Value>),
+        /// An integer in programmer mode, which is read according to its word.
+        Word(Word),
+    }
+
+    impl Value {
+        pub fn as_f64(&self) -> f64 {
+            match self {
+                Value::Integer(value) => *value as f64,
+                Value::Float(value) => *value,
+                Value::Bool(value) => *value as i32 as f64,
+                Value::Word(word) => word.to_f64(),
+                Value::String(_) | Value::List(_) => f64::NAN,
+            }
+        }
+
+        /// The value as an integer, if it is one or is a float with no fractional part.
+        pub fn as_integer(&self) -> Option<i128> {
+            match self {
+                Value::Integer(value) => Some(*value),
+                Value::Float(value) if value.fract() == 0.0 && value.abs() < i128::MAX as f64 => Some(*value as i128),
+                Value::Word(word) => word.to_i128(),
+                Value::Float(_) | Value::Bool(_) | Value::String(_) | Value::List(_) => None,
+            }
+        }
+    }
+
+    impl fmt::Display for Value {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                Value::Integer(value) => write!(f, "{}", value),
+                Value::Float(value) if value.is_nan() => write!(f, "???"),
+                Value::Float(value) => write!(f, "{}", value),
+                Value::Bool(value) => write!(f, "{}", value),
+                Value::Word(word) => write!(f, "{}", word),
+                Value::String(value) => write!(f, "\"{}\"", value),
+                Value::List(values) => {
+                    let values = values.iter().map(Value::to_string).collect::<Vec<_>>();
+                    write!(f, "[{}]", values.join(", "))
+                }
+            }
+        }
+    }
+
+    /// The width and signedness of the integers used in programmer mode, e.g. `u8` or `i64`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct WordSize {
+        pub bits: u32,
+        pub signed: bool,
+    }
+
+    impl WordSize {
+        pub fn min(&self) -> i128 {
+            if self.signed {
+                -(1i128 << (self.bits - 1))
+            } else {
+                0
+            }
+        }
+
+        pub fn max(&self) -> u128 {
+            if self.signed {
+                self.mask() >> 1
+            } else {
+                self.mask()
+            }
+        }
+
+        fn mask(&self) -> u128 {
+            if self.bits == 128 {
+                u128::MAX
+            } else {
+                (1u128 << self.bits) - 1
+            }
+        }
+
+        /// The two's complement bit pattern of the value, truncated to the word.
+        pub fn bits_of(&self, value: i128) -> u128 {
+            (value as u128) & self.mask()
+        }
+
+        /// Interprets a bit pattern as a value of this word, sign extending if the word is signed.
+        pub fn value_of(&self, bits: u128) -> i128 {
+            let bits = bits & self.mask();
+            if self.signed && self.bits < 128 && bits >> (self.bits - 1) & 1 == 1 {
+                (bits | !self.mask()) as i128
+            } else {
+                bits as i128
+            }
+        }
+
+        /// Wraps the value around to fit in the word.
+        pub fn wrap(&self, value: i128) -> i128 {
+            self.value_of(value as u128)
+        }
+
+        pub fn contains(&self, value: i128) -> bool {
+            value >= self.min() && (value < 0 || value as u128 <= self.max())
+        }
+    }
+
+    impl fmt::Display for WordSize {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{}{}", if self.signed { "i" } else { "u" }, self.bits)
+        }
+    }
+
+    impl FromStr for WordSize {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let error = || format!("invalid word size `{}`, expected one of u8, u16, u32, u64, u128, i8, i16, i32, i64 or i128", s);
+            let signed = match s.chars().next() {
+                Some('i') => true,
+                Some('u') => false,
+                _ => return Err(error()),
+            };
+            match s[1..].parse::<u32>() {
+                Ok(bits @ (8 | 16 | 32 | 64 | 128)) => Ok(WordSize { bits, signed }),
+                _ => Err(error()),
+            }
+        }
+    }
+
+    /// An integer in programmer mode: its bit pattern, and the word that says how to read it.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Word {
+        pub bits: u128,
+        pub size: WordSize,
+    }
+
+    impl Word {
+        /// The value wrapped around to fit in the word.
+        pub fn new(value: i128, size: WordSize) -> Self {
+            Word {
+                bits: size.bits_of(value),
+                size,
+            }
+        }
+
+        /// The bit pattern truncated to the word.
+        pub fn from_bits(bits: u128, size: WordSize) -> Self {
+            Word {
+                bits: bits & size.mask(),
+                size,
+            }
+        }
+
+        /// The same value in another word, wrapping around if it doesn't fit.
+        pub fn resize(&self, size: WordSize) -> Self {
+            match self.to_i128() {
+                Some(value) => Word::new(value, size),
+                None => Word::from_bits(self.bits, size),
+            }
+        }
+
+        /// The value, unless it is in the upper half of a `u128`, which an `i128` can't hold.
+        pub fn to_i128(&self) -> Option<i128> {
+            if self.size.signed {
+                Some(self.size.value_of(self.bits))
+            } else {
+                i128::try_from(self.bits).ok()
+            }
+        }
+
+        pub fn to_f64(&self) -> f64 {
+            match self.to_i128() {
+                Some(value) => value as f64,
+                None => self.bits as f64,
+            }
+        }
+    }
+
+    impl fmt::Display for Word {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self.to_i128() {
+                Some(value) => write!(f, "{}", value),
+                None => write!(f, "{}", self.bits),
+            }
+        }
+    }
+
+    /// What happens when an integer operation in programmer mode does not fit in the word.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub enum Overflow {
+        #[default]
+        Wrapping,
+        Checked,
+    }
+
+    impl fmt::Display for Overflow {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                Overflow::Wrapping => write!(f, "wrapping"),
+                Overflow::Checked => write!(f, "checked"),
+            }
+        }
+    }
+
+    impl FromStr for Overflow {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s {
+                "wrapping" => Ok(Overflow::Wrapping),
+                "checked" => Ok(Overflow::Checked),
+                _ => Err(format!("invalid overflow mode `{}`, expected `wrapping` or `checked`", s)),
+            }
+        }
+    }
+
+    /// Fixed-width integer semantics used by programmer mode.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct IntegerMode {
+        pub word: WordSize,
+        pub overflow: Overflow,
+    }
+
+    impl IntegerMode {
+        /// Used for bitwise operators outside of programmer mode.
+        pub const DEFAULT: IntegerMode = IntegerMode {
+            word: WordSize { bits: 64, signed: true },
+            overflow: Overflow::Wrapping,
+        };
+
+        /// Fits the result of an operation on a signed word into the word, given both its exact result
+        /// (if that fits in an `i128`) and its result wrapped at 128 bits. Returns `None` if it overflows
+        /// in checked mode.
+        pub fn fit(&self, checked: Option<i128>, wrapped: i128) -> Option<Word> {
+            match self.overflow {
+                Overflow::Wrapping => Some(Word::new(wrapped, self.word)),
+                Overflow::Checked => checked.filter(|value| self.word.contains(*value)).map(|value| Word::new(value, self.word)),
+            }
+        }
+
+        /// Like `fit`, for an operation on an unsigned word, which is done with `u128` so that all of a
+        /// `u128` word can be used.
+        pub fn fit_unsigned(&self, checked: Option<u128>, wrapped: u128) -> Option<Word> {
+            match self.overflow {
+                Overflow::Wrapping => Some(Word::from_bits(wrapped, self.word)),
+                Overflow::Checked => checked.filter(|value| *value <= self.word.max()).map(|value| Word::from_bits(value, self.word)),
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+
+        use super::*;
+
+        #[test]
+        fn test_word_size() {
+            let u8: WordSize = "u8".parse().unwrap();
+            let i8: WordSize = "i8".parse().unwrap();
+            assert_eq!(u8.max(), 255);
+            assert_eq!(i8.min(), -128);
+            assert_eq!("u128".parse::<WordSize>().unwrap().max(), u128::MAX);
+            assert_eq!(u8.wrap(256 + 7), 7);
+            assert_eq!(i8.wrap(255), -1);
+            assert_eq!(i8.bits_of(-1), 0xFF);
+            assert!("u7".parse::<WordSize>().is_err());
+        }
+
+        #[test]
+        fn test_word() {
+            let u128: WordSize = "u128".parse().unwrap();
+            let i8: WordSize = "i8".parse().unwrap();
+            let top = Word::from_bits(1 << 127, u128);
+            assert_eq!(top.to_i128(), None);
+            assert_eq!(top.to_string(), "170141183460469231731687303715884105728");
+            assert_eq!(Word::new(-1, u128).to_string(), u128::MAX.to_string());
+            assert_eq!(Word::new(255, i8).to_string(), "-1");
+            assert_eq!(Word::new(-1, i8).resize(u128).bits, u128::MAX);
+        }
+
+        #[test]
+        fn test_fit() {
+            let checked = IntegerMode {
+                word: "u8".parse().unwrap(),
+                overflow: Overflow::Checked,
+            };
+            let word = |value| Some(Word::new(value, checked.word));
+            assert_eq!(checked.fit(Some(255), 255), word(255));
+            assert_eq!(checked.fit(Some(256), 256), None);
+            assert_eq!(checked.fit(Some(-1), -1), None);
+            assert_eq!(checked.fit_unsigned(None, 0), None);
+
+            let wrapping = IntegerMode {
+                overflow: Overflow::Wrapping,
+                ..checked
+            };
+            assert_eq!(wrapping.fit(Some(256), 256), word(0));
+            assert_eq!(wrapping.fit(None, -1), word(255));
+            assert_eq!(wrapping.fit_unsigned(None, 0x1FF), word(255));
+        }
+    }
+}